@@ -6,6 +6,7 @@ extern crate rbf;
 use rbf::record::{AsciiMode, UTF8Mode, ReadMode};
 use rbf::layout::{Layout};
 use rbf::reader::Reader;
+use rbf::parallel::ParallelReader;
 
 // how long is it to read a Layout
 fn load_layout(bench: &mut Bencher) {
@@ -52,21 +53,95 @@ fn set_value_huge_utf8_1000(bench: &mut Bencher) {
     })
 }
 
-fn next_record_id(bench: &mut Bencher) {
-    // load our layout
-    let layout = Layout::<AsciiMode>::new("./tests/test.xml");
+// UTF-8 mode, but only ASCII chars in the line
+fn set_value_huge_utf8_ascii_1000(bench: &mut Bencher) {
+    let mut rec = rbf::record::setup::set_up_by_length_huge::<UTF8Mode>(1000);
+    let s = "A".to_string().repeat(10000);
+
+    bench.iter(|| {
+        rec.set_value(&s)
+    })
+}
+
+// same with zero-copy parsing
+fn parse_huge_1000(bench: &mut Bencher) {
+    let mut rec = rbf::record::setup::set_up_by_length_huge::<AsciiMode>(1000);
+    let s = "A".to_string().repeat(10000);
+
+    bench.iter(|| {
+        rec.parse(&s)
+    })
+}
+
+// same with zero-copy parsing
+fn parse_huge_utf8_1000(bench: &mut Bencher) {
+    let mut rec = rbf::record::setup::set_up_by_length_huge::<UTF8Mode>(1000);
+    let s = "α".to_string().repeat(10000);
+
+    bench.iter(|| {
+        rec.parse(&s)
+    })
+}
+
+// UTF-8 mode, but only ASCII chars in the line
+fn parse_huge_utf8_ascii_1000(bench: &mut Bencher) {
+    let mut rec = rbf::record::setup::set_up_by_length_huge::<UTF8Mode>(1000);
+    let s = "A".to_string().repeat(10000);
+
+    bench.iter(|| {
+        rec.parse(&s)
+    })
+}
+
+// look up a field value by name
+fn get_value_huge_1000(bench: &mut Bencher) {
+    let mut rec = rbf::record::setup::set_up_by_length_huge::<AsciiMode>(1000);
+    let s = "A".to_string().repeat(10000);
+    rec.parse(&s);
+
+    bench.iter(|| {
+        rec.get_value_with_index("FIELD1", 999).len()
+    })
+}
+
+// reads a whole file
+fn read_file(bench: &mut Bencher, zero_copy: bool) {
+    bench.iter(|| {
+        // load our layout
+        let layout = Layout::<AsciiMode>::new("./tests/test.xml");
 
-    // create reader
-    fn mapper(x: &str) -> &str { &x[0..2] };
-    let mut reader = Reader::<AsciiMode>::new("./tests/test_ascii.data", layout, mapper); 
+        // create reader
+        let mapper = Box::new(|x: &str| x[0..2].to_string());
+        let mut reader = Reader::<AsciiMode>::new("./tests/test_ascii.data", layout, mapper);
+        reader.set_zero_copy(zero_copy);
 
+        while reader.next().is_some() {}
+    })
+}
 
+fn read_file_copy(bench: &mut Bencher) {
+    read_file(bench, false)
+}
+
+fn read_file_zero_copy(bench: &mut Bencher) {
+    read_file(bench, true)
+}
+
+// reads a whole file with several threads
+fn read_file_parallel(bench: &mut Bencher) {
     bench.iter(|| {
-        reader.next_record_id()             
+        let mut layout = Layout::<AsciiMode>::new("./tests/test.xml");
+        layout.mapper = Box::new(|x: &str| x[0..2].to_string());
+        let mut reader = ParallelReader::<AsciiMode>::new("./tests/test_ascii.data", layout);
+        reader.set_chunk_size(16384);
+
+        reader.for_each_chunk(|chunk| while chunk.next().is_some() {})
     })
 }
 
 
 benchmark_group!(benches, load_layout, set_value, set_value_huge_100, 
-    set_value_huge_1000, set_value_huge_utf8_1000, next_record_id);
-benchmark_main!(benches);
\ No newline at end of file
+    set_value_huge_1000, set_value_huge_utf8_1000, set_value_huge_utf8_ascii_1000, parse_huge_1000,
+    parse_huge_utf8_1000, parse_huge_utf8_ascii_1000, get_value_huge_1000,
+    read_file_copy, read_file_zero_copy, read_file_parallel);
+benchmark_main!(benches);
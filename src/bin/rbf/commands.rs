@@ -0,0 +1,670 @@
+//! Implementation of each subcommand. Each one returns the process exit code, or an error message.
+use std::io::{self, BufReader, BufWriter, Write};
+use std::fs::{self, File};
+use std::path::Path;
+use std::collections::{HashMap, VecDeque};
+
+use rbf::record::{AsciiMode, ReadMode, Record};
+use rbf::layout::Layout;
+use rbf::reader::Reader;
+use rbf::query::Query;
+use rbf::profile::Profile;
+use rbf::infer::LayoutGuess;
+use rbf::format::{self, LayoutModel};
+use rbf::util::read_line;
+use rbf::export::csv::{CsvExporter, CsvOptions};
+#[cfg(feature = "json")]
+use rbf::export::json::{JsonLinesExporter, JsonOptions};
+#[cfg(feature = "arrow")]
+use rbf::export::arrow::ParquetExporter;
+#[cfg(any(feature = "sqlite", feature = "arrow"))]
+use rbf::export::ExportCounts;
+#[cfg(feature = "sqlite")]
+use rbf::export::sqlite::SqliteLoader;
+
+use options::{Encoding, Format, Options};
+
+/// Exit code when everything went fine
+const EXIT_OK: i32 = 0;
+/// Exit code when `validate` finds invalid data
+const EXIT_INVALID: i32 = 3;
+
+// converts an I/O error into an error message. A closed stdout (e.g.: piped into `head`) is not an error
+fn io_result<R: Default>(result: io::Result<R>) -> Result<R, String> {
+    match result {
+        Ok(r) => Ok(r),
+        Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(R::default()),
+        Err(e) => Err(format!("error when writing output: {}", e)),
+    }
+}
+
+// only text, CSV and JSON are meaningful for records
+fn check_record_format(format: Format) -> Result<(), String> {
+    match format {
+        Format::Html => Err("html format is only supported by `layout show` and `profile`".to_string()),
+        _ => Ok(()),
+    }
+}
+
+// writes an HTML page around tables built by Record::to_html() or Profile::to_html(), which use Bootstrap classes
+fn write_html_page<W: Write>(w: &mut W, title: &str, body: &str) -> io::Result<()> {
+    writeln!(w, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>", title)?;
+    writeln!(w, "<link rel=\"stylesheet\" href=\"https://maxcdn.bootstrapcdn.com/bootstrap/3.3.7/css/bootstrap.min.css\">")?;
+    writeln!(w, "</head>\n<body>\n<div class=\"container\">")?;
+    writeln!(w, "{}", body)?;
+    writeln!(w, "</div>\n</body>\n</html>")
+}
+
+// reads the next record, also returning its line number
+fn next_record<T>(reader: &mut Reader<T>) -> Option<(u64, &Record<T>)>
+    where Record<T>: ReadMode
+{
+    let name = reader.next()?.name.clone();
+    Some((reader.nblines_read, &reader.layout.rec_map[&name]))
+}
+
+// writes a single record according to the output format
+fn write_record<T, W: Write>(w: &mut W, rec: &Record<T>, line_number: u64, format: Format) -> io::Result<()> {
+    match format {
+        Format::Csv => {
+            let values = vec![line_number.to_string(), rec.name.clone()];
+            CsvOptions::default().write_row(w, values.into_iter().chain(rec.field_values().map(|fv| fv.value().to_string())))
+        },
+        #[cfg(feature = "json")]
+        Format::Json => {
+            let options = JsonOptions { with_record_name: true, with_line_number: true, ..JsonOptions::default() };
+            writeln!(w, "{}", rec.to_json_with(&options, Some(line_number)))
+        },
+        _ => writeln!(w, "{}:{}:{}", line_number, rec.name, rec.view()),
+    }
+}
+
+/// `rbf count`: counts records by record type.
+pub fn count<T>(opts: &Options) -> Result<i32, String>
+    where Record<T>: ReadMode
+{
+    let mut reader = opts.reader::<T>()?;
+    let mut nb_records: HashMap<String, u64> = HashMap::new();
+
+    while let Some(rec) = reader.next() {
+        match nb_records.get_mut(&rec.name) {
+            Some(n) => *n += 1,
+            None => { nb_records.insert(rec.name.clone(), 1); },
+        }
+    }
+
+    let mut names: Vec<_> = nb_records.keys().collect();
+    names.sort();
+
+    let stdout = io::stdout();
+    let mut w = BufWriter::new(stdout.lock());
+
+    io_result(match opts.format {
+        Format::Csv => {
+            let csv = CsvOptions::default();
+            csv.write_row(&mut w, vec!["record", "count"])
+                .and_then(|_| names.iter().try_for_each(|name| csv.write_row(&mut w, vec![name.to_string(), nb_records[*name].to_string()])))
+        },
+        #[cfg(feature = "json")]
+        Format::Json => {
+            let records: ::serde_json::Map<_, _> = names.iter().map(|name| (name.to_string(), json!(nb_records[*name]))).collect();
+            writeln!(w, "{}", json!({ "lines": reader.nblines_read, "records": records }))
+        },
+        _ => {
+            writeln!(w, "Input file has {} lines", reader.nblines_read)
+                .and_then(|_| names.iter().try_for_each(|name| writeln!(w, "Number of {} records = {}", name, nb_records[*name])))
+        },
+    }.and_then(|_| w.flush()))?;
+
+    Ok(EXIT_OK)
+}
+
+/// `rbf cat` & `rbf head`: displays all records, or only the first `limit` ones.
+pub fn cat<T>(opts: &Options, limit: Option<usize>) -> Result<i32, String>
+    where Record<T>: ReadMode
+{
+    check_record_format(opts.format)?;
+    let mut reader = opts.reader::<T>()?;
+
+    let stdout = io::stdout();
+    let mut w = BufWriter::new(stdout.lock());
+    let mut nb_records = 0;
+
+    io_result((|| {
+        while limit.is_none_or(|n| nb_records < n) {
+            match next_record(&mut reader) {
+                Some((line_number, rec)) => write_record(&mut w, rec, line_number, opts.format)?,
+                None => break,
+            }
+            nb_records += 1;
+        }
+        w.flush()
+    })())?;
+
+    Ok(EXIT_OK)
+}
+
+/// `rbf tail`: displays the last `n` records.
+pub fn tail<T>(opts: &Options, n: usize) -> Result<i32, String>
+    where Record<T>: ReadMode
+{
+    check_record_format(opts.format)?;
+    let mut reader = opts.reader::<T>()?;
+
+    // only keep last lines, records are built again at the end
+    let mut last: VecDeque<(u64, String, String)> = VecDeque::with_capacity(n);
+
+    while let Some(name) = reader.next().map(|rec| rec.name.clone()) {
+        if n == 0 {
+            continue;
+        }
+        if last.len() == n {
+            last.pop_front();
+        }
+        last.push_back((reader.nblines_read, name, reader.current_line().to_string()));
+    }
+
+    let stdout = io::stdout();
+    let mut w = BufWriter::new(stdout.lock());
+
+    io_result((|| {
+        for (line_number, name, line) in last {
+            let rec = reader.layout.get_mut(&name).unwrap();
+            rec.parse(&line);
+            write_record(&mut w, rec, line_number, opts.format)?;
+        }
+        w.flush()
+    })())?;
+
+    Ok(EXIT_OK)
+}
+
+/// `rbf grep`: displays records matching the query (or not matching it when `invert` is set), either as
+/// original lines, or as selected fields when a selection is given. Only counts them if `count` is set.
+pub fn grep<T>(opts: &Options, query: &str, invert: bool, line_number: bool, count: bool) -> Result<i32, String>
+    where Record<T>: ReadMode, T: 'static
+{
+    check_record_format(opts.format)?;
+
+    // query is checked against the whole layout, selection is only used for display
+    let layout = opts.full_layout::<T>()?;
+    let query = Query::compile(query, &layout).map_err(|e| format!("invalid query <{}>: {}", query, e))?;
+    let mut selected = match opts.select {
+        Some(_) => Some(opts.layout::<T>()?),
+        None => None,
+    };
+
+    let mut reader = opts.reader_with(layout)?;
+    reader.set_filter(Box::new(move |rec| query.matches(rec) != invert));
+
+    let stdout = io::stdout();
+    let mut w = BufWriter::new(stdout.lock());
+    let mut nb_records = 0u64;
+
+    io_result((|| {
+        while let Some(name) = reader.next().map(|rec| rec.name.clone()) {
+            nb_records += 1;
+            if count {
+                continue;
+            }
+
+            let line = reader.current_line().trim_end_matches(['\n', '\r']);
+
+            match selected {
+                // read the line again using the selected fields
+                Some(ref mut layout) => if let Some(rec) = layout.get_mut(&name) {
+                    rec.parse(line);
+                    write_record(&mut w, rec, reader.nblines_read, opts.format)?;
+                },
+                None if line_number => writeln!(w, "{}:{}", reader.nblines_read, line)?,
+                None => writeln!(w, "{}", line)?,
+            }
+        }
+
+        if count {
+            writeln!(w, "{}", nb_records)?;
+        }
+        w.flush()
+    })())?;
+
+    Ok(EXIT_OK)
+}
+
+// a data error found by `validate`
+struct Issue {
+    line_number: u64,
+    record: String,
+    field: String,
+    message: String,
+}
+
+impl Issue {
+    fn new(line_number: u64, record: &str, field: &str, message: String) -> Issue {
+        Issue { line_number, record: record.to_string(), field: field.to_string(), message }
+    }
+
+    fn write<W: Write>(&self, w: &mut W, format: Format) -> io::Result<()> {
+        match format {
+            Format::Csv => CsvOptions::default().write_row(w,
+                vec![self.line_number.to_string(), self.record.clone(), self.field.clone(), self.message.clone()]),
+            #[cfg(feature = "json")]
+            Format::Json => writeln!(w, "{}", json!({
+                "line": self.line_number,
+                "record": self.record,
+                "field": self.field,
+                "message": self.message,
+            })),
+            _ => writeln!(w, "line {}: {}", self.line_number, self.message),
+        }
+    }
+}
+
+/// `rbf validate`: checks the layout, then checks each line has a known record ID, the expected length and
+/// fields matching their field type pattern. Stops after `max_errors` errors (if not 0), or at the first one
+/// when lazyness is stringent.
+pub fn validate<T>(opts: &Options, max_errors: u64) -> Result<i32, String>
+    where Record<T>: ReadMode
+{
+    check_record_format(opts.format)?;
+
+    let data_file = opts.data_file()?;
+    let mut layout = opts.layout::<T>()?;
+    let mapper = opts.mapper(&mut layout);
+    let max_errors = if opts.stringent { 1 } else { max_errors };
+
+    let file = File::open(data_file).map_err(|e| format!("couldn't open {}: {}", data_file, e))?;
+
+    let stdout = io::stdout();
+    let mut w = BufWriter::new(stdout.lock());
+    let mut nb_errors = 0u64;
+    let mut nb_lines = 0u64;
+
+    // layout first
+    for diagnostic in layout.validate().iter().filter(|d| d.is_error()) {
+        let message = format!("layout {}: {}", opts.layout_file, diagnostic);
+        io_result(Issue::new(0, &diagnostic.record, &diagnostic.field, message).write(&mut w, opts.format))?;
+        nb_errors += 1;
+    }
+
+    let ignore_line = !layout.ignore_line.as_str().is_empty();
+
+    let mut bufreader = BufReader::new(file);
+    let mut buf = String::new();
+
+    loop {
+        if max_errors != 0 && nb_errors >= max_errors {
+            break;
+        }
+
+        // invalid UTF-8 (e.g. HIGH-VALUES) is replaced, as when reading records
+        let length = read_line(&mut bufreader, &mut buf).map_err(|e| format!("error when reading file {}: {}", data_file, e))?;
+        if length == 0 {
+            break;
+        }
+        let line = buf.strip_suffix('\n').unwrap_or(&buf).trim_end_matches('\r');
+        nb_lines += 1;
+
+        if ignore_line && layout.ignore_line.is_match(line) {
+            continue;
+        }
+
+        let mut issues = Vec::new();
+
+        if line.is_empty() {
+            issues.push(Issue::new(nb_lines, "", "", "empty line".to_string()));
+        }
+        else {
+            let rec_id = mapper(line);
+
+            match layout.get_mut(&rec_id) {
+                None => if opts.select.is_none() {
+                    issues.push(Issue::new(nb_lines, "", "", format!("unknown record ID <{}>", rec_id)));
+                },
+                Some(rec) => {
+                    let length = match opts.encoding {
+                        Encoding::Ascii => line.len(),
+                        Encoding::Utf8 => line.chars().count(),
+                    };
+                    if length != rec.calculated_length {
+                        let message = format!("record {} has a length of {} instead of {}", rec.name, length, rec.calculated_length);
+                        issues.push(Issue::new(nb_lines, &rec.name, "", message));
+                    }
+
+                    rec.parse(line);
+
+                    for fv in rec.field_values().filter(|fv| !fv.is_match()) {
+                        let f = fv.field;
+                        let message = format!("field {} of record {}: value <{}> doesn't match pattern <{}> of type {}",
+                            f.name, rec.name, fv.raw_value(), f.ftype.pattern, f.ftype.id);
+                        issues.push(Issue::new(nb_lines, &rec.name, &f.name, message));
+                    }
+                },
+            }
+        }
+
+        for issue in issues {
+            if max_errors != 0 && nb_errors >= max_errors {
+                break;
+            }
+            io_result(issue.write(&mut w, opts.format))?;
+            nb_errors += 1;
+        }
+    }
+
+    io_result(w.flush())?;
+    eprintln!("{} lines read, {} error(s) found", nb_lines, nb_errors);
+
+    Ok(if nb_errors == 0 { EXIT_OK } else { EXIT_INVALID })
+}
+
+// prints the number of rows written per record type
+fn print_rows(nb_rows: &HashMap<String, u64>) {
+    let mut names: Vec<_> = nb_rows.keys().collect();
+    names.sort();
+
+    for name in names {
+        eprintln!("{}: {} rows", name, nb_rows[name]);
+    }
+}
+
+// prints the number of values which couldn't be converted, per record and column
+#[cfg(any(feature = "sqlite", feature = "arrow"))]
+fn print_rejects(counts: &ExportCounts) {
+    let mut columns: Vec<_> = counts.rejects.keys().collect();
+    columns.sort();
+
+    for column in columns {
+        eprintln!("warning: {}.{}: {} value(s) couldn't be converted to the field type", column.0, column.1, counts.rejects[column]);
+    }
+}
+
+/// `rbf convert`: converts the data file to CSV, JSON Lines, Parquet or SQLite, depending on the
+/// features rbf is built with.
+pub fn convert<T>(opts: &Options, to: &str, output: Option<&str>) -> Result<i32, String>
+    where Record<T>: ReadMode
+{
+    let mut reader = opts.reader::<T>()?;
+    let output_required = || output.ok_or(format!("--output is required when converting to {}", to));
+
+    match to {
+        "csv" => {
+            let dir = output_required()?;
+            fs::create_dir_all(dir).map_err(|e| format!("couldn't create directory {}: {}", dir, e))?;
+
+            let nb_rows = CsvExporter::new(dir, CsvOptions::default()).export(&mut reader).map_err(|e| e.to_string())?;
+            print_rows(&nb_rows);
+        },
+        #[cfg(feature = "json")]
+        "json" => {
+            let options = JsonOptions { with_record_name: true, with_line_number: true, ..JsonOptions::default() };
+            let exporter = JsonLinesExporter::new(options);
+
+            let nb_records = match output {
+                Some(file) => {
+                    let f = File::create(file).map_err(|e| format!("couldn't create {}: {}", file, e))?;
+                    exporter.export(&mut reader, &mut BufWriter::new(f)).map_err(|e| e.to_string())?
+                },
+                None => {
+                    let stdout = io::stdout();
+                    let mut w = BufWriter::new(stdout.lock());
+                    io_result(exporter.export(&mut reader, &mut w))?
+                },
+            };
+            eprintln!("{} records written", nb_records);
+        },
+        #[cfg(feature = "arrow")]
+        "parquet" => {
+            let dir = output_required()?;
+            fs::create_dir_all(dir).map_err(|e| format!("couldn't create directory {}: {}", dir, e))?;
+
+            let counts = ParquetExporter::new(dir, 10000).export(&mut reader).map_err(|e| e.to_string())?;
+            print_rows(&counts.rows);
+            print_rejects(&counts);
+        },
+        #[cfg(feature = "sqlite")]
+        "sqlite" => {
+            let mut loader = SqliteLoader::new(output_required()?);
+            loader.replace = true;
+
+            let counts = loader.load(&mut reader).map_err(|e| e.to_string())?;
+            print_rows(&counts.rows);
+            print_rejects(&counts);
+        },
+        _ => {
+            let feature = if to == "parquet" { "arrow" } else { to };
+            return Err(format!("conversion to {} requires rbf to be built with the `{}` feature", to, feature));
+        },
+    }
+
+    Ok(EXIT_OK)
+}
+
+// field table of a record, as text
+fn write_layout_text<W: Write>(w: &mut W, layout: &Layout<AsciiMode>, names: &[&String]) -> io::Result<()> {
+    writeln!(w, "layout: {}", layout.xml_file)?;
+    writeln!(w, "version: {}", layout.version)?;
+    writeln!(w, "description: {}", layout.description)?;
+    if !layout.schema.is_empty() {
+        writeln!(w, "schema: {}", layout.schema)?;
+    }
+    if layout.rec_length != 0 {
+        writeln!(w, "record length: {}", layout.rec_length)?;
+    }
+
+    let mut ftypes: Vec<_> = layout.ftypes.values().collect();
+    ftypes.sort_by(|a, b| a.id.cmp(&b.id));
+
+    writeln!(w, "\nfield types:")?;
+    for ft in ftypes {
+        writeln!(w, "  {:<10} {} {}", ft.id, ft.base_data_type, ft.pattern)?;
+    }
+
+    for name in names {
+        let rec = &layout.rec_map[*name];
+        writeln!(w, "\nrecord {} ({}), length {}:", rec.name, rec.description, rec.calculated_length)?;
+
+        // column widths
+        let name_width = rec.flist.iter().map(|f| f.name.len()).max().unwrap_or(0).max(4);
+        let desc_width = rec.flist.iter().map(|f| f.description.chars().count()).max().unwrap_or(0).max(11);
+        let type_width = rec.flist.iter().map(|f| f.ftype.id.len()).max().unwrap_or(0).max(4);
+
+        writeln!(w, "  {:>3} {:<nw$} {:<dw$} {:<tw$} {:>6} {:>6} {:>6}", "#", "name", "description", "type", "length", "start", "end",
+            nw = name_width, dw = desc_width, tw = type_width)?;
+        for f in rec {
+            writeln!(w, "  {:>3} {:<nw$} {:<dw$} {:<tw$} {:>6} {:>6} {:>6}", f.index, f.name, f.description, f.ftype.id,
+                f.length, f.lower_offset + 1, f.upper_offset + 1, nw = name_width, dw = desc_width, tw = type_width)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// `rbf profile`: profiles each record type and field of the data file, keeping the `top` most frequent
+/// values of each field.
+pub fn profile<T>(opts: &Options, top: usize) -> Result<i32, String>
+    where Record<T>: ReadMode
+{
+    if opts.format == Format::Csv {
+        return Err("csv format is not supported by `profile`".to_string());
+    }
+
+    let mut reader = opts.reader::<T>()?;
+    let profile = Profile::from_reader(&mut reader, top);
+
+    let stdout = io::stdout();
+    let mut w = BufWriter::new(stdout.lock());
+
+    io_result(match opts.format {
+        #[cfg(feature = "json")]
+        Format::Json => writeln!(w, "{}", profile.to_json()),
+        Format::Html => write_html_page(&mut w, &format!("Profile of {}", reader.rbf_file), &profile.to_html()),
+        _ => profile.write_text(&mut w),
+    }.and_then(|_| w.flush()))?;
+
+    Ok(EXIT_OK)
+}
+
+/// `rbf layout show`: displays field types, records and fields of a layout.
+pub fn layout_show(opts: &Options) -> Result<i32, String> {
+    let layout = opts.layout::<AsciiMode>()?;
+
+    let mut names: Vec<_> = layout.rec_map.keys().collect();
+    names.sort();
+
+    let stdout = io::stdout();
+    let mut w = BufWriter::new(stdout.lock());
+
+    io_result(match opts.format {
+        Format::Csv => (|| {
+            let csv = CsvOptions::default();
+            csv.write_row(&mut w, vec!["record", "index", "field", "description", "type", "length", "start", "end"])?;
+
+            for name in &names {
+                for f in &layout.rec_map[*name] {
+                    csv.write_row(&mut w, vec![
+                        name.to_string(), f.index.to_string(), f.name.clone(), f.description.clone(), f.ftype.id.clone(),
+                        f.length.to_string(), (f.lower_offset + 1).to_string(), (f.upper_offset + 1).to_string()])?;
+                }
+            }
+            Ok(())
+        })(),
+        #[cfg(feature = "json")]
+        Format::Json => {
+            let records: Vec<_> = names.iter().map(|name| {
+                let rec = &layout.rec_map[*name];
+                let fields: Vec<_> = rec.flist.iter().map(|f| json!({
+                    "index": f.index,
+                    "name": f.name,
+                    "description": f.description,
+                    "type": f.ftype.id,
+                    "length": f.length,
+                    "start": f.lower_offset + 1,
+                    "end": f.upper_offset + 1,
+                })).collect();
+                json!({ "name": rec.name, "description": rec.description, "length": rec.calculated_length, "fields": fields })
+            }).collect();
+
+            writeln!(w, "{}", json!({
+                "file": layout.xml_file,
+                "version": layout.version,
+                "description": layout.description,
+                "schema": layout.schema,
+                "records": records,
+            }))
+        },
+        Format::Html => {
+            let tables: Vec<_> = names.iter().map(|name| layout.rec_map[*name].to_html()).collect();
+            write_html_page(&mut w, &layout.description, &tables.join("\n"))
+        },
+        _ => write_layout_text(&mut w, &layout, &names),
+    }.and_then(|_| w.flush()))?;
+
+    Ok(EXIT_OK)
+}
+
+/// `rbf layout check`: reports all problems found in the layout, errors first. Exit code is 3 when errors are
+/// found.
+pub fn layout_check(opts: &Options) -> Result<i32, String> {
+    check_record_format(opts.format)?;
+
+    // the definition is checked first, as errors found there prevent the layout from being loaded
+    let mut model = LayoutModel::from_file(&opts.layout_file)?;
+    model.resolve_includes(&opts.layout_file)?;
+    let mut diagnostics = model.validate();
+    if diagnostics.is_empty() {
+        let mut layout = Layout::<AsciiMode>::from_model(&model, &opts.layout_file);
+        if let Some(ref pattern) = opts.mapper {
+            layout.mapper_pattern = pattern.clone();
+        }
+        diagnostics = layout.validate();
+    }
+    diagnostics.sort_by_key(|d| d.severity);
+
+    let stdout = io::stdout();
+    let mut w = BufWriter::new(stdout.lock());
+
+    io_result((|| {
+        if opts.format == Format::Csv {
+            CsvOptions::default().write_row(&mut w, vec!["severity", "record", "field", "message"])?;
+        }
+
+        for d in &diagnostics {
+            match opts.format {
+                Format::Csv => CsvOptions::default().write_row(&mut w,
+                    vec![d.severity.to_string(), d.record.clone(), d.field.clone(), d.message.clone()])?,
+                #[cfg(feature = "json")]
+                Format::Json => writeln!(w, "{}", json!({
+                    "severity": d.severity.to_string(),
+                    "record": d.record,
+                    "field": d.field,
+                    "message": d.message,
+                }))?,
+                _ => writeln!(w, "{}", d)?,
+            }
+        }
+
+        if opts.format == Format::Text {
+            let nb_errors = diagnostics.iter().filter(|d| d.is_error()).count();
+            writeln!(w, "{}: {} error(s), {} warning(s)", opts.layout_file, nb_errors, diagnostics.len() - nb_errors)?;
+        }
+        w.flush()
+    })())?;
+
+    Ok(if diagnostics.iter().any(|d| d.is_error()) { EXIT_INVALID } else { EXIT_OK })
+}
+
+/// `rbf layout diff`: reports changes between the layout (old version) and `new_file`. Exit code is 3 when some
+/// changes break existing data.
+pub fn layout_diff(opts: &Options, new_file: &str) -> Result<i32, String> {
+    if opts.format == Format::Csv || opts.format == Format::Html {
+        return Err("only text and json formats are supported by `layout diff`".to_string());
+    }
+
+    let old = opts.full_layout::<AsciiMode>()?;
+    if !Path::new(new_file).is_file() {
+        return Err(format!("layout file {} not found", new_file));
+    }
+    let new = Layout::<AsciiMode>::new(new_file);
+    let diff = old.diff(&new);
+
+    let stdout = io::stdout();
+    let mut w = BufWriter::new(stdout.lock());
+
+    io_result(match opts.format {
+        #[cfg(feature = "json")]
+        Format::Json => writeln!(w, "{}", diff.to_json()),
+        _ => diff.write_text(&mut w),
+    }.and_then(|_| w.flush()))?;
+
+    Ok(if diff.is_breaking() { EXIT_INVALID } else { EXIT_OK })
+}
+
+/// `rbf layout infer`: guesses a layout from the first `sample` lines of a data file, and writes it as XML
+/// to `output` or stdout.
+pub fn layout_infer(data_file: &str, sample: usize, output: Option<&str>) -> Result<i32, String> {
+    let guess = LayoutGuess::from_file(data_file, sample).map_err(|e| format!("couldn't read {}: {}", data_file, e))?;
+    let description = format!("Layout guessed from {}", data_file);
+
+    match output {
+        Some(xml_file) => {
+            let f = File::create(xml_file).map_err(|e| format!("couldn't create {}: {}", xml_file, e))?;
+            let mut w = BufWriter::new(f);
+            guess.write_xml(&mut w, &description).and_then(|_| w.flush())
+                .map_err(|e| format!("error when writing {}: {}", xml_file, e))?;
+        },
+        None => {
+            let stdout = io::stdout();
+            let mut w = BufWriter::new(stdout.lock());
+            io_result(guess.write_xml(&mut w, &description).and_then(|_| w.flush()))?;
+        },
+    }
+
+    Ok(EXIT_OK)
+}
+
+/// `rbf layout convert`: converts a layout file into another format, both formats being guessed from file
+/// extensions.
+pub fn layout_convert(layout_file: &str, output: &str) -> Result<i32, String> {
+    format::convert(layout_file, output)?;
+    Ok(EXIT_OK)
+}
@@ -0,0 +1,209 @@
+//! `rbf` command-line tool: reads a record-based file using its XML layout.
+//!
+//! ```text
+//! rbf count LAYOUT DATA                  counts records by record type
+//! rbf cat LAYOUT DATA                    displays all records (alias: display)
+//! rbf head LAYOUT DATA -n 10             displays the first records
+//! rbf tail LAYOUT DATA -n 10             displays the last records
+//! rbf grep QUERY LAYOUT DATA             displays records matching a query (e.g.: 'NB.N3 > 300')
+//! rbf validate LAYOUT DATA               checks record IDs, line lengths and field patterns
+//! rbf convert LAYOUT DATA --to csv -o DIR
+//! rbf profile LAYOUT DATA --top 5        profiles fields of each record type
+//! rbf layout show LAYOUT
+//! rbf layout check LAYOUT                reports overlapping fields, gaps, length mismatches, unknown field types...
+//! rbf layout diff OLD NEW                reports changes between two layout versions
+//! rbf layout infer DATA -o LAYOUT        guesses a layout from the first lines of a data file
+//! rbf layout convert LAYOUT OUTPUT       converts a layout file into another format (e.g.: XML to YAML)
+//! ```
+//!
+//! Global options select the data encoding (`--encoding ascii|utf8`), override the layout mapper
+//! (`--mapper "type:1 map:0..2"`), set the reader lazyness (`--lazyness lazy|stringent`), keep only some
+//! records and fields (`--select "LL:ID,W1; NB:N1"`), choose the output format (`--format text|csv|json|html`)
+//! display a progress bar on stderr (`--progress`) and memory-map the data file (`--mmap`, needs the `mmap`
+//! feature).
+//!
+//! Exit code is 0 on success, 1 on error, 2 on a command-line usage error and 3 when `validate` finds
+//! invalid data.
+extern crate clap;
+extern crate regex;
+#[cfg(feature = "json")]
+#[macro_use]
+extern crate serde_json;
+extern crate rbf;
+
+mod options;
+mod commands;
+
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
+
+use clap::{Arg, ArgAction, ArgMatches, Command, value_parser};
+
+use rbf::record::{AsciiMode, ReadMode, Record, UTF8Mode};
+
+use options::{Encoding, Options};
+
+/// Exit code when an error occurred
+const EXIT_ERROR: i32 = 1;
+
+// subcommand reading a data file
+fn data_command(name: &'static str, about: &'static str) -> Command {
+    Command::new(name)
+        .about(about)
+        .arg(Arg::new("layout").required(true).help("layout file (XML, or JSON, YAML, TOML depending on features)"))
+        .arg(Arg::new("data").required(true).help("record-based file to read"))
+}
+
+// number of records for head & tail
+fn lines_arg() -> Arg {
+    Arg::new("lines").short('n').long("lines")
+        .value_parser(value_parser!(usize)).default_value("10")
+        .help("number of records to display")
+}
+
+fn cli() -> Command {
+    Command::new("rbf")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Reads record-based files described by an XML layout")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .arg(Arg::new("encoding").short('e').long("encoding").global(true)
+            .value_parser(["ascii", "utf8"]).default_value("ascii")
+            .help("data file encoding"))
+        .arg(Arg::new("mapper").short('m').long("mapper").global(true)
+            .help("mapper used instead of the layout one (e.g.: \"type:1 map:0..2\")"))
+        .arg(Arg::new("lazyness").short('z').long("lazyness").global(true)
+            .value_parser(["lazy", "stringent"]).default_value("lazy")
+            .help("skip unknown record IDs or stop reading (validate: stop at first error)"))
+        .arg(Arg::new("select").short('s').long("select").global(true)
+            .help("records and fields to keep (e.g.: \"LL:ID,W1; NB:N1\")"))
+        .arg(Arg::new("format").short('f').long("format").global(true)
+            .value_parser(["text", "csv", "json", "html"]).default_value("text")
+            .help("output format"))
+        .arg(Arg::new("progress").short('p').long("progress").global(true).action(ArgAction::SetTrue)
+            .help("display a progress bar on stderr while reading"))
+        .arg(Arg::new("mmap").long("mmap").global(true).action(ArgAction::SetTrue)
+            .help("read the data file through a memory mapping"))
+        .subcommand(data_command("count", "Counts records by record type"))
+        .subcommand(data_command("cat", "Displays all records").visible_alias("display"))
+        .subcommand(data_command("head", "Displays the first records").arg(lines_arg()))
+        .subcommand(data_command("tail", "Displays the last records").arg(lines_arg()))
+        .subcommand(Command::new("grep")
+            .about("Displays records matching a query, e.g.: 'COUN.POPULATION > 1e8 and NAME =~ \"^C\"'")
+            .arg(Arg::new("query").required(true).help("query selecting records"))
+            .arg(Arg::new("layout").required(true).help("layout file (XML, or JSON, YAML, TOML depending on features)"))
+            .arg(Arg::new("data").required(true).help("record-based file to read"))
+            .arg(Arg::new("invert-match").short('v').long("invert-match").action(ArgAction::SetTrue)
+                .help("display records not matching the query"))
+            .arg(Arg::new("line-number").short('n').long("line-number").action(ArgAction::SetTrue)
+                .help("prefix each line with its line number"))
+            .arg(Arg::new("count").short('c').long("count").action(ArgAction::SetTrue)
+                .help("only display the number of matching records")))
+        .subcommand(data_command("validate", "Checks record IDs, line lengths and field patterns")
+            .arg(Arg::new("max-errors").long("max-errors")
+                .value_parser(value_parser!(u64)).default_value("0")
+                .help("stop after this number of errors (0: no limit)")))
+        .subcommand(data_command("convert", "Converts a record-based file to another format")
+            .arg(Arg::new("to").short('t').long("to").required(true)
+                .value_parser(["csv", "json", "parquet", "sqlite"])
+                .help("target format"))
+            .arg(Arg::new("output").short('o').long("output")
+                .help("output directory (csv, parquet) or file (json, sqlite)")))
+        .subcommand(data_command("profile", "Profiles fields of each record type: fill rate, distinct values, lengths, top values")
+            .arg(Arg::new("top").long("top")
+                .value_parser(value_parser!(usize)).default_value("5")
+                .help("number of most frequent values displayed for each field")))
+        .subcommand(Command::new("layout")
+            .about("Layout related commands")
+            .subcommand_required(true)
+            .subcommand(Command::new("show")
+                .about("Displays field types, records and fields of a layout")
+                .arg(Arg::new("layout").required(true).help("layout file (XML, or JSON, YAML, TOML depending on features)")))
+            .subcommand(Command::new("check")
+                .about("Checks a layout: record lengths, overlapping fields, gaps, duplicated records, field types, mapper")
+                .arg(Arg::new("layout").required(true).help("layout file (XML, or JSON, YAML, TOML depending on features)")))
+            .subcommand(Command::new("diff")
+                .about("Reports changes between two versions of a layout, flagging changes breaking existing data")
+                .arg(Arg::new("layout").required(true).help("old layout file"))
+                .arg(Arg::new("new").required(true).help("new layout file")))
+            .subcommand(Command::new("infer")
+                .about("Guesses an XML layout from the first lines of a data file")
+                .arg(Arg::new("data").required(true).help("record-based file to read"))
+                .arg(Arg::new("sample").long("sample")
+                    .value_parser(value_parser!(usize)).default_value("1000")
+                    .help("number of lines used to guess the layout (0: all lines)"))
+                .arg(Arg::new("output").short('o').long("output")
+                    .help("XML layout file to write, instead of stdout")))
+            .subcommand(Command::new("convert")
+                .about("Converts a layout file into another format, guessed from the output file extension")
+                .arg(Arg::new("layout").required(true).help("layout file to convert"))
+                .arg(Arg::new("output").required(true).help("layout file to write (.xml, .json, .yaml, .yml or .toml)"))))
+}
+
+// runs a subcommand reading a data file
+fn run_data<T>(name: &str, matches: &ArgMatches, opts: &Options) -> Result<i32, String>
+    where Record<T>: ReadMode, T: 'static
+{
+    match name {
+        "count" => commands::count::<T>(opts),
+        "cat" => commands::cat::<T>(opts, None),
+        "head" => commands::cat::<T>(opts, matches.get_one::<usize>("lines").cloned()),
+        "tail" => commands::tail::<T>(opts, *matches.get_one::<usize>("lines").unwrap()),
+        "grep" => commands::grep::<T>(opts, matches.get_one::<String>("query").unwrap(),
+            matches.get_flag("invert-match"), matches.get_flag("line-number"), matches.get_flag("count")),
+        "validate" => commands::validate::<T>(opts, *matches.get_one::<u64>("max-errors").unwrap()),
+        "convert" => commands::convert::<T>(opts,
+            matches.get_one::<String>("to").unwrap(),
+            matches.get_one::<String>("output").map(|s| s.as_str())),
+        "profile" => commands::profile::<T>(opts, *matches.get_one::<usize>("top").unwrap()),
+        _ => unreachable!(),
+    }
+}
+
+fn run(matches: &ArgMatches) -> Result<i32, String> {
+    match matches.subcommand() {
+        Some(("layout", sub)) => match sub.subcommand() {
+            Some(("show", m)) => commands::layout_show(&Options::new(m)?),
+            Some(("check", m)) => commands::layout_check(&Options::new(m)?),
+            Some(("diff", m)) => commands::layout_diff(&Options::new(m)?, m.get_one::<String>("new").unwrap()),
+            Some(("infer", m)) => commands::layout_infer(m.get_one::<String>("data").unwrap(),
+                *m.get_one::<usize>("sample").unwrap(), m.get_one::<String>("output").map(|s| s.as_str())),
+            Some(("convert", m)) => commands::layout_convert(m.get_one::<String>("layout").unwrap(),
+                m.get_one::<String>("output").unwrap()),
+            _ => unreachable!(),
+        },
+        Some((name, m)) => {
+            let opts = Options::new(m)?;
+            match opts.encoding {
+                Encoding::Ascii => run_data::<AsciiMode>(name, m, &opts),
+                Encoding::Utf8 => run_data::<UTF8Mode>(name, m, &opts),
+            }
+        },
+        None => unreachable!(),
+    }
+}
+
+fn main() {
+    // the library reports errors by panicking: only print the message
+    panic::set_hook(Box::new(|info| {
+        let payload = info.payload();
+        let msg = match payload.downcast_ref::<&str>() {
+            Some(s) => s.to_string(),
+            None => payload.downcast_ref::<String>().cloned().unwrap_or_else(|| "unknown error".to_string()),
+        };
+        eprintln!("rbf: {}", msg);
+    }));
+
+    let matches = cli().get_matches();
+
+    let code = match panic::catch_unwind(AssertUnwindSafe(|| run(&matches))) {
+        Ok(Ok(code)) => code,
+        Ok(Err(msg)) => {
+            eprintln!("rbf: {}", msg);
+            EXIT_ERROR
+        },
+        Err(_) => EXIT_ERROR,
+    };
+
+    process::exit(code);
+}
@@ -0,0 +1,216 @@
+//! Options shared by all subcommands, and creation of the layout and reader from them.
+use std::io::{self, Write};
+use std::mem;
+use std::path::Path;
+
+use clap::ArgMatches;
+use regex::Regex;
+
+use rbf::layout::Layout;
+use rbf::reader::{Reader, ReaderLazyness};
+use rbf::mapper::{RecordMapper, SyncRecordHasher};
+use rbf::progress::{Progress, ProgressStep};
+use rbf::util::into_rec_map;
+
+/// Width of the progress bar
+const BAR_WIDTH: usize = 30;
+
+/// Data file encoding
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Ascii,
+    Utf8,
+}
+
+/// Output format
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Text,
+    Csv,
+    Json,
+    Html,
+}
+
+pub struct Options {
+    /// layout file
+    pub layout_file: String,
+    /// record-based file, if any
+    pub data_file: Option<String>,
+    /// data file encoding
+    pub encoding: Encoding,
+    /// mapper pattern overriding the layout one
+    pub mapper: Option<String>,
+    /// whether lazyness is stringent
+    pub stringent: bool,
+    /// records and fields to keep, using the `into_rec_map()` syntax
+    pub select: Option<String>,
+    /// output format
+    pub format: Format,
+    /// whether a progress bar is displayed on stderr
+    pub progress: bool,
+    /// whether the data file is memory-mapped
+    pub mmap: bool,
+}
+
+// displays a progress bar on stderr, ending it with a newline at end of file
+fn show_progress(progress: &Progress) {
+    let percent = progress.percent();
+    let filled = (percent / 100.0 * BAR_WIDTH as f64) as usize;
+    let eta = progress.eta().map_or(0, |d| d.as_secs());
+
+    let mut stderr = io::stderr();
+    let _ = write!(stderr, "\r[{}{}] {:5.1}% {} lines {:.2} MB/s ETA {:02}:{:02}:{:02}",
+        "#".repeat(filled.min(BAR_WIDTH)), ".".repeat(BAR_WIDTH - filled.min(BAR_WIDTH)),
+        percent, progress.nblines_read, progress.mb_per_sec(), eta / 3600, eta / 60 % 60, eta % 60);
+    if progress.done {
+        let _ = writeln!(stderr);
+    }
+}
+
+// checks the mapper pattern, as RecordMapper::from() panics on a bad one
+fn check_mapper(pattern: &str) -> Result<(), String> {
+    let mapper_reg = Regex::new(r"^type:(0\s+map:\s*\w+|1\s+map:\s*\d+\.\.\d+|2\s+map:\s*\d+\.\.\d+\s*,\s*\d+\.\.\d+)\s*$").unwrap();
+
+    if mapper_reg.is_match(pattern) {
+        Ok(())
+    }
+    else {
+        Err(format!("invalid mapper <{}>", pattern))
+    }
+}
+
+// checks the record selection, as into_rec_map() panics on a bad one
+fn check_select(select: &str) -> Result<(), String> {
+    if select.split(';').all(|list| list.contains(':')) {
+        Ok(())
+    }
+    else {
+        Err(format!("invalid record selection <{}>, expected \"REC1:F1,F2; REC2:F3\"", select))
+    }
+}
+
+impl Options {
+    /// Gets options from the subcommand arguments.
+    pub fn new(matches: &ArgMatches) -> Result<Options, String> {
+        let get = |name: &str| matches.get_one::<String>(name).cloned();
+
+        let mapper = get("mapper");
+        if let Some(ref pattern) = mapper {
+            check_mapper(pattern)?;
+        }
+
+        let select = get("select");
+        if let Some(ref list) = select {
+            check_select(list)?;
+        }
+
+        let format = match get("format").as_deref() {
+            Some("csv") => Format::Csv,
+            Some("json") => Format::Json,
+            Some("html") => Format::Html,
+            _ => Format::Text,
+        };
+        if format == Format::Json && !cfg!(feature = "json") {
+            return Err("json format requires rbf to be built with the `json` feature".to_string());
+        }
+
+        let mmap = matches.get_flag("mmap");
+        if mmap && !cfg!(feature = "mmap") {
+            return Err("--mmap requires rbf to be built with the `mmap` feature".to_string());
+        }
+
+        Ok(Options {
+            layout_file: get("layout").unwrap(),
+            data_file: matches.try_get_one::<String>("data").ok().and_then(|d| d.cloned()),
+            encoding: if get("encoding").as_deref() == Some("utf8") { Encoding::Utf8 } else { Encoding::Ascii },
+            mapper,
+            stringent: get("lazyness").as_deref() == Some("stringent"),
+            select,
+            format,
+            progress: matches.get_flag("progress"),
+            mmap,
+        })
+    }
+
+    /// Returns the data file name, checking it exists.
+    pub fn data_file(&self) -> Result<&str, String> {
+        let data_file = self.data_file.as_ref().ok_or("no data file given")?;
+
+        if !Path::new(data_file).is_file() {
+            return Err(format!("data file {} not found", data_file));
+        }
+        Ok(data_file)
+    }
+
+    /// Loads the whole layout.
+    pub fn full_layout<T>(&self) -> Result<Layout<T>, String> {
+        if !Path::new(&self.layout_file).is_file() {
+            return Err(format!("layout file {} not found", self.layout_file));
+        }
+
+        Ok(Layout::<T>::new(&self.layout_file))
+    }
+
+    /// Loads the layout, keeping only selected records and fields.
+    pub fn layout<T>(&self) -> Result<Layout<T>, String> {
+        let mut layout = self.full_layout::<T>()?;
+
+        if let Some(ref list) = self.select {
+            layout.retain(into_rec_map(list));
+        }
+
+        Ok(layout)
+    }
+
+    /// Returns the mapper given on the command line, or takes the one of the layout.
+    pub fn mapper<T>(&self, layout: &mut Layout<T>) -> SyncRecordHasher {
+        match self.mapper {
+            Some(ref pattern) => RecordMapper::from(pattern.as_str()).hasher,
+            None => mem::replace(&mut layout.mapper, RecordMapper::default().hasher),
+        }
+    }
+
+    /// Creates a reader on the data file.
+    pub fn reader<T>(&self) -> Result<Reader<T>, String> {
+        self.reader_with(self.layout::<T>()?)
+    }
+
+    /// Creates a reader on the data file using the given layout.
+    pub fn reader_with<T>(&self, mut layout: Layout<T>) -> Result<Reader<T>, String> {
+        let data_file = self.data_file()?;
+        let mapper = self.mapper(&mut layout);
+
+        let mut reader = Reader::new(data_file, layout, mapper);
+        reader.set_zero_copy(true);
+        if self.stringent {
+            reader.set_lazyness(ReaderLazyness::Stringent);
+        }
+        if self.progress {
+            reader.set_progress(ProgressStep::Bytes(1 << 20), Box::new(show_progress));
+        }
+        if self.mmap {
+            // only set when built with the feature
+            #[cfg(feature = "mmap")]
+            reader.set_mmap().map_err(|e| format!("couldn't map data file {}: {}", data_file, e))?;
+        }
+
+        Ok(reader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_mapper, check_select};
+
+    #[test]
+    fn cli_check_options() {
+        assert!(check_mapper("type:0 map:LL").is_ok());
+        assert!(check_mapper("type:1 map:0..2").is_ok());
+        assert!(check_mapper("type:2 map:0..2, 4..6").is_ok());
+        assert!(check_mapper("type:1 map:0..").is_err());
+        assert!(check_mapper("type:3 map:0..2").is_err());
+
+        assert!(check_select("LL:ID,W1; NB:N1").is_ok());
+        assert!(check_select("LL:ID,W1; NB").is_err());
+    }
+}
@@ -0,0 +1,317 @@
+//! Builds a layout in code, without any layout file. Field types, records and fields are declared
+//! with a fluent API, and the layout is checked when built.
+//!
+//! # Examples
+//! ```rust
+//! use rbf::record::AsciiMode;
+//! use rbf::layout::Layout;
+//! use rbf::builder::LayoutBuilder;
+//!
+//! let layout: Layout<AsciiMode> = LayoutBuilder::new()
+//!     .meta("1.0", "Customers")
+//!     .ignore_line("^#")
+//!     .mapper("type:1 map:0..2")
+//!     .field_type("A", "string")
+//!     .field_type_with("N", "decimal", |t| t.pattern(r"[\d.]+").scale(2))
+//!     .record("CU", |r| r.description("Customer").length(20)
+//!         .field("ID", 2, "A")
+//!         .field("NAME", 10, "A")
+//!         .field_at("AMOUNT", 13, 20, "N"))
+//!     .build()
+//!     .unwrap();
+//!
+//! assert_eq!(layout.get("CU").unwrap().calculated_length, 20);
+//! assert_eq!((layout.mapper)("CU1234"), "CU");
+//!
+//! // fields must refer to declared field types
+//! let result = LayoutBuilder::new().record("CU", |r| r.field("ID", 2, "A")).build::<AsciiMode>();
+//! assert!(result.is_err());
+//! ```
+use diagnostic::LayoutDiagnostic;
+use format::{FieldModel, FieldTypeModel, LayoutModel, RecordModel};
+use layout::Layout;
+use mapper::SyncRecordHasher;
+
+/// Declares a field type.
+pub struct FieldTypeBuilder {
+    model: FieldTypeModel,
+}
+
+impl FieldTypeBuilder {
+    /// Sets the regex describing values.
+    pub fn pattern(mut self, pattern: &str) -> FieldTypeBuilder {
+        self.model.pattern = pattern.to_string();
+        self
+    }
+
+    /// Sets the number of digits after the decimal point.
+    pub fn scale(mut self, scale: usize) -> FieldTypeBuilder {
+        self.model.scale = Some(scale);
+        self
+    }
+
+    /// Sets the date or time format.
+    pub fn format(mut self, format: &str) -> FieldTypeBuilder {
+        self.model.format = format.to_string();
+        self
+    }
+
+    /// Sets the sides stripped of the pad char when reading: none, left, right or both.
+    pub fn trim(mut self, trim: &str) -> FieldTypeBuilder {
+        self.model.trim = trim.to_string();
+        self
+    }
+
+    /// Sets the char filling fields around values.
+    pub fn pad_char(mut self, pad_char: char) -> FieldTypeBuilder {
+        self.model.pad_char = pad_char.to_string();
+        self
+    }
+
+    /// Sets the side values are aligned on when writing: left or right.
+    pub fn justify(mut self, justify: &str) -> FieldTypeBuilder {
+        self.model.justify = justify.to_string();
+        self
+    }
+
+    /// Sets the comma-separated list of values meaning that a field has no value: blanks, zeros, high-values or
+    /// low-values.
+    pub fn null(mut self, null: &str) -> FieldTypeBuilder {
+        self.model.null = null.to_string();
+        self
+    }
+}
+
+/// Declares a record and its fields, in order.
+pub struct RecordBuilder {
+    model: RecordModel,
+}
+
+impl RecordBuilder {
+    /// Sets the record description.
+    pub fn description(mut self, description: &str) -> RecordBuilder {
+        self.model.description = description.to_string();
+        self
+    }
+
+    /// Sets the declared record length, checked against the sum of field lengths when building.
+    pub fn length(mut self, length: usize) -> RecordBuilder {
+        self.model.length = length;
+        self
+    }
+
+    /// Sets the base record, whose fields come first.
+    pub fn extends(mut self, base: &str) -> RecordBuilder {
+        self.model.extends = base.to_string();
+        self
+    }
+
+    /// Adds a field following the previous one.
+    pub fn field(self, name: &str, length: usize, ftype: &str) -> RecordBuilder {
+        self.push(FieldModel {
+            name: name.to_string(),
+            ftype: ftype.to_string(),
+            length: Some(length),
+            ..FieldModel::default()
+        })
+    }
+
+    /// Adds a field by its bounds within the record (starting from 1).
+    pub fn field_at(self, name: &str, start: usize, end: usize, ftype: &str) -> RecordBuilder {
+        self.push(FieldModel {
+            name: name.to_string(),
+            ftype: ftype.to_string(),
+            start: Some(start),
+            end: Some(end),
+            ..FieldModel::default()
+        })
+    }
+
+    /// Adds a field definition, e.g. to set its description.
+    pub fn push(mut self, field: FieldModel) -> RecordBuilder {
+        self.model.fields.push(field);
+        self
+    }
+}
+
+/// Fluent builder for a [Layout](../layout/struct.Layout.html).
+#[derive(Default)]
+pub struct LayoutBuilder {
+    model: LayoutModel,
+    hasher: Option<SyncRecordHasher>,
+}
+
+impl LayoutBuilder {
+    /// Creates an empty layout definition.
+    pub fn new() -> LayoutBuilder {
+        LayoutBuilder::default()
+    }
+
+    /// Starts from an existing layout definition, e.g. read from a layout file.
+    pub fn from_model(model: LayoutModel) -> LayoutBuilder {
+        LayoutBuilder { model, hasher: None }
+    }
+
+    /// Sets the layout version and description.
+    pub fn meta(mut self, version: &str, description: &str) -> LayoutBuilder {
+        self.model.meta.version = version.to_string();
+        self.model.meta.description = description.to_string();
+        self
+    }
+
+    /// Sets the SQL schema name.
+    pub fn schema(mut self, schema: &str) -> LayoutBuilder {
+        self.model.meta.schema = schema.to_string();
+        self
+    }
+
+    /// Sets the length of all records.
+    pub fn rec_length(mut self, length: usize) -> LayoutBuilder {
+        self.model.meta.reclength = length;
+        self
+    }
+
+    /// Sets the regex of lines to ignore when reading.
+    pub fn ignore_line(mut self, ignore_line: &str) -> LayoutBuilder {
+        self.model.meta.ignore_line = ignore_line.to_string();
+        self
+    }
+
+    /// Sets the comma-separated list of fields to skip.
+    pub fn skip_field(mut self, skip_field: &str) -> LayoutBuilder {
+        self.model.meta.skip_field = skip_field.to_string();
+        self
+    }
+
+    /// Sets the mapper pattern (e.g.: "type:1 map:0..2").
+    pub fn mapper(mut self, pattern: &str) -> LayoutBuilder {
+        self.model.meta.mapper = pattern.to_string();
+        self
+    }
+
+    /// Sets a closure mapping each line to a record ID, used instead of any mapper pattern.
+    pub fn mapper_fn(mut self, hasher: SyncRecordHasher) -> LayoutBuilder {
+        self.hasher = Some(hasher);
+        self
+    }
+
+    /// Declares a field type from its name and base type (string, decimal, integer, date or time).
+    pub fn field_type(self, name: &str, base_type: &str) -> LayoutBuilder {
+        self.field_type_with(name, base_type, |t| t)
+    }
+
+    /// Declares a field type, setting its pattern, scale or format in the closure.
+    pub fn field_type_with<F>(mut self, name: &str, base_type: &str, f: F) -> LayoutBuilder
+        where F: FnOnce(FieldTypeBuilder) -> FieldTypeBuilder
+    {
+        let ft = f(FieldTypeBuilder {
+            model: FieldTypeModel { name: name.to_string(), base_type: base_type.to_string(), ..FieldTypeModel::default() },
+        });
+        self.model.field_types.push(ft.model);
+        self
+    }
+
+    /// Declares a record, adding its fields in the closure.
+    pub fn record<F>(mut self, name: &str, f: F) -> LayoutBuilder
+        where F: FnOnce(RecordBuilder) -> RecordBuilder
+    {
+        let rec = f(RecordBuilder {
+            model: RecordModel { name: name.to_string(), ..RecordModel::default() },
+        });
+        self.model.records.push(rec.model);
+        self
+    }
+
+    /// Returns the layout definition built so far.
+    pub fn model(&self) -> &LayoutModel {
+        &self.model
+    }
+
+    /// Checks the layout definition and creates the layout. Errors found by
+    /// [LayoutModel::validate](../format/struct.LayoutModel.html#method.validate) and
+    /// [Layout::validate](../layout/struct.Layout.html#method.validate) are returned, one per line.
+    pub fn build<T>(self) -> Result<Layout<T>, String> {
+        let errors = |diagnostics: Vec<LayoutDiagnostic>| {
+            let errors: Vec<_> = diagnostics.iter().filter(|d| d.is_error()).map(|d| d.to_string()).collect();
+            if errors.is_empty() { Ok(()) } else { Err(errors.join("\n")) }
+        };
+
+        // errors making Layout::from_model() panic first
+        errors(self.model.validate())?;
+
+        let mut layout = Layout::from_model(&self.model, "");
+        errors(layout.validate())?;
+
+        if let Some(hasher) = self.hasher {
+            layout.mapper = hasher;
+        }
+
+        Ok(layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use builder::LayoutBuilder;
+    use layout::Layout;
+    use record::AsciiMode;
+
+    #[test]
+    fn builder_layout() {
+        let layout: Layout<AsciiMode> = LayoutBuilder::new()
+            .meta("2017", "Test file")
+            .ignore_line("^A")
+            .skip_field("W2")
+            .field_type_with("A", "string", |t| t.pattern(r"\w+"))
+            .field_type("N", "decimal")
+            .field_type_with("Z", "integer", |t| t.trim("left").pad_char('0').justify("right").null("blanks, zeros"))
+            .record("LL", |r| r.length(6).field("ID", 2, "A").field("W1", 1, "A").field("W2", 3, "A"))
+            .record("NB", |r| r.field("ID", 2, "A").field_at("N1", 5, 8, "N").field("N2", 4, "Z"))
+            .mapper_fn(Box::new(|x: &str| x[0..2].to_string()))
+            .build()
+            .unwrap();
+
+        assert_eq!(layout.len(), 2);
+        assert_eq!(layout.version, "2017");
+        assert!(layout.ignore_line.is_match("ABC"));
+        assert!(!layout.contains_field("W2"));
+        assert_eq!(layout.get_type("A").unwrap().pattern.as_str(), r"\w+");
+        assert_eq!((layout.mapper)("NB1234"), "NB");
+
+        let rec = layout.get("NB").unwrap();
+        assert_eq!(rec.calculated_length, 12);
+        assert_eq!((rec[1].lower_offset, rec[1].upper_offset), (4, 7));
+        assert_eq!(rec.format_line(vec!["NB", "1.5", "42"]), "NB  1.5 0042");
+
+        // padding is kept in the layout definition, defaults are left out
+        let ft = layout.to_model().field_types.into_iter().find(|ft| ft.name == "Z").unwrap();
+        assert_eq!((ft.trim.as_str(), ft.pad_char.as_str(), ft.justify.as_str()), ("left", "0", "right"));
+        assert_eq!(ft.null, "blanks,zeros");
+        assert!(layout.to_model().field_types.iter().filter(|ft| ft.name != "Z").all(|ft| ft.trim.is_empty()));
+    }
+
+    #[test]
+    fn builder_errors() {
+        let error = |builder: LayoutBuilder| builder.build::<AsciiMode>().err().unwrap();
+
+        assert_eq!(error(LayoutBuilder::new().field_type("A", "char")), "error: field type A: <char> is not allowed as a field type");
+        assert_eq!(error(LayoutBuilder::new().record("LL", |r| r.length(1)).record("LL", |r| r.length(1))),
+            "error: record LL: record is declared more than once, only the last declaration is kept\n\
+             error: record LL: fields add up to 0 instead of the declared length 1");
+        assert_eq!(error(LayoutBuilder::new().field_type("A", "string").record("LL", |r| r.field("ID", 0, "A").field("W1", 1, "B"))),
+            "error: record LL, field ID: zero-width field\nerror: record LL, field W1: field type B is not declared");
+        assert_eq!(error(LayoutBuilder::new().field_type("A", "string").record("LL", |r| r.length(3).field("ID", 2, "A"))),
+            "error: record LL: fields add up to 2 instead of the declared length 3");
+        assert!(error(LayoutBuilder::new().mapper("type:1 map:0..")).starts_with("error: Unknown mapper pattern"));
+        assert!(error(LayoutBuilder::new().ignore_line("(")).starts_with("error: invalid ignoreLine regex"));
+        assert_eq!(error(LayoutBuilder::new().field_type_with("A", "string", |t| t.trim("middle").justify("center"))),
+            "error: field type A: <middle> is not allowed as a trim policy\n\
+             error: field type A: <center> is not allowed as a justification");
+        assert_eq!(error(LayoutBuilder::new().field_type_with("A", "string", |t| t.null("blanks,nothing"))),
+            "error: field type A: <nothing> is not allowed as a null value");
+
+        // a redeclared field type replaces the previous one
+        let layout: Layout<AsciiMode> = LayoutBuilder::new().field_type("A", "string").field_type("A", "decimal").build().unwrap();
+        assert_eq!(layout.get_type("A").unwrap().base_data_type.type_name(), "decimal");
+    }
+}
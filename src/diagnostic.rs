@@ -0,0 +1,83 @@
+//! Problems found when checking a layout, either on its definition
+//! ([LayoutModel::validate](../format/struct.LayoutModel.html#method.validate)) or once loaded
+//! ([Layout::validate](../layout/struct.Layout.html#method.validate)).
+//!
+//! # Examples
+//! ```rust
+//! use rbf::record::AsciiMode;
+//! use rbf::layout::Layout;
+//! use rbf::diagnostic::Severity;
+//!
+//! let layout = Layout::<AsciiMode>::new("./tests/test.xml");
+//! let diagnostics = layout.validate();
+//! assert!(diagnostics.iter().all(|d| d.severity != Severity::Error));
+//! ```
+use std::fmt;
+
+/// How serious a problem is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// the layout can't be used as is to read data
+    Error,
+    /// the layout is usable, but probably not what was meant
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A problem found in a layout, with its location: the record and field it relates to, if any.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutDiagnostic {
+    /// problem severity
+    pub severity: Severity,
+    /// record name, empty if the problem is not related to a record
+    pub record: String,
+    /// field name, empty if the problem is not related to a field
+    pub field: String,
+    /// problem description
+    pub message: String,
+}
+
+impl LayoutDiagnostic {
+    /// Creates an error.
+    pub fn error(record: &str, field: &str, message: String) -> LayoutDiagnostic {
+        LayoutDiagnostic { severity: Severity::Error, record: record.to_string(), field: field.to_string(), message }
+    }
+
+    /// Creates a warning.
+    pub fn warning(record: &str, field: &str, message: String) -> LayoutDiagnostic {
+        LayoutDiagnostic { severity: Severity::Warning, record: record.to_string(), field: field.to_string(), message }
+    }
+
+    /// Tests whether the diagnostic is an error.
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+/// Displays the diagnostic as `error: record LL, field W1: message`.
+impl fmt::Display for LayoutDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut location = Vec::new();
+        if !self.record.is_empty() {
+            location.push(format!("record {}", self.record));
+        }
+        if !self.field.is_empty() {
+            location.push(format!("field {}", self.field));
+        }
+
+        if location.is_empty() {
+            write!(f, "{}: {}", self.severity, self.message)
+        }
+        else {
+            write!(f, "{}: {}: {}", self.severity, location.join(", "), self.message)
+        }
+    }
+}
@@ -0,0 +1,392 @@
+//! Compares two versions of a layout, reporting added and removed records, added, removed and renamed fields,
+//! length and offset shifts, type and description changes. Each change is flagged as breaking when data written
+//! with the old layout can't be read the same way with the new one.
+//!
+//! Fields are first matched by name (the n-th field of a given name in the old record with the n-th one in the
+//! new record). Remaining fields are considered renamed when they have the same position and length, or the same
+//! description.
+//!
+//! # Examples
+//! ```rust
+//! use std::sync::Arc;
+//! use rbf::record::AsciiMode;
+//! use rbf::layout::Layout;
+//! use rbf::field::Field;
+//! use rbf::diff::ChangeKind;
+//!
+//! let old = Layout::<AsciiMode>::new("./tests/test.xml");
+//! let mut new = Layout::<AsciiMode>::new("./tests/test.xml");
+//! new.rec_map.remove("DP");
+//! let ft = Arc::clone(new.get_type("N").unwrap());
+//! new.get_mut("NB").unwrap().push(Field::from_length("N10", "Number 10", &ft, 3));
+//!
+//! let diff = old.diff(&new);
+//! assert_eq!(diff.changes.len(), 3);
+//! assert_eq!(diff.changes[0].kind, ChangeKind::RecordRemoved);
+//! assert_eq!(diff.changes[1].to_string(), "[breaking] record NB: length changed from 47 to 50");
+//! assert_eq!(diff.changes[2].to_string(), "record NB, field N10: field added at positions 48 to 50");
+//! assert!(diff.is_breaking());
+//! ```
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+
+use field::Field;
+use layout::Layout;
+use record::Record;
+
+/// Kind of change between two layouts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChangeKind {
+    /// field type only found in the new layout
+    FieldTypeAdded,
+    /// field type only found in the old layout
+    FieldTypeRemoved,
+    /// base type, pattern, scale or format of a field type
+    FieldTypeChanged,
+    /// record only found in the new layout
+    RecordAdded,
+    /// record only found in the old layout
+    RecordRemoved,
+    /// record length
+    RecordLength,
+    /// record or field description
+    Description,
+    /// field only found in the new record
+    FieldAdded,
+    /// field only found in the old record
+    FieldRemoved,
+    /// field matched by position or description, but with another name
+    FieldRenamed,
+    /// field length
+    FieldLength,
+    /// field start position
+    FieldOffset,
+    /// field type of a field
+    FieldType,
+}
+
+impl ChangeKind {
+    /// Returns the change kind name, as used in JSON output.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ChangeKind::FieldTypeAdded => "fieldtype_added",
+            ChangeKind::FieldTypeRemoved => "fieldtype_removed",
+            ChangeKind::FieldTypeChanged => "fieldtype_changed",
+            ChangeKind::RecordAdded => "record_added",
+            ChangeKind::RecordRemoved => "record_removed",
+            ChangeKind::RecordLength => "record_length",
+            ChangeKind::Description => "description",
+            ChangeKind::FieldAdded => "field_added",
+            ChangeKind::FieldRemoved => "field_removed",
+            ChangeKind::FieldRenamed => "field_renamed",
+            ChangeKind::FieldLength => "field_length",
+            ChangeKind::FieldOffset => "field_offset",
+            ChangeKind::FieldType => "field_type",
+        }
+    }
+}
+
+/// A change between two layouts, located by record and field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutChange {
+    /// change kind
+    pub kind: ChangeKind,
+    /// record name, empty for field type changes
+    pub record: String,
+    /// field name (in the new layout, except for removed fields), empty for record changes
+    pub field: String,
+    /// old value, if any
+    pub old: String,
+    /// new value, if any
+    pub new: String,
+    /// set when data written with the old layout can't be read the same way with the new one
+    pub breaking: bool,
+}
+
+impl LayoutChange {
+    fn new(kind: ChangeKind, record: &str, field: &str, old: String, new: String, breaking: bool) -> LayoutChange {
+        LayoutChange { kind, record: record.to_string(), field: field.to_string(), old, new, breaking }
+    }
+
+    /// Returns a description of the change, without its location.
+    pub fn message(&self) -> String {
+        match self.kind {
+            ChangeKind::FieldTypeAdded => format!("field type {} added", self.new),
+            ChangeKind::FieldTypeRemoved => format!("field type {} removed", self.old),
+            ChangeKind::FieldTypeChanged => format!("field type changed from {} to {}", self.old, self.new),
+            ChangeKind::RecordAdded => "record added".to_string(),
+            ChangeKind::RecordRemoved => "record removed".to_string(),
+            ChangeKind::RecordLength | ChangeKind::FieldLength => format!("length changed from {} to {}", self.old, self.new),
+            ChangeKind::Description => format!("description changed from \"{}\" to \"{}\"", self.old, self.new),
+            ChangeKind::FieldAdded => format!("field added at positions {}", self.new),
+            ChangeKind::FieldRemoved => format!("field removed from positions {}", self.old),
+            ChangeKind::FieldRenamed => format!("field renamed from {} to {}", self.old, self.new),
+            ChangeKind::FieldOffset => format!("moved from position {} to {}", self.old, self.new),
+            ChangeKind::FieldType => format!("type changed from {} to {}", self.old, self.new),
+        }
+    }
+}
+
+/// Displays the change as `[breaking] record NB, field N3: length changed from 3 to 4`.
+impl fmt::Display for LayoutChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.breaking {
+            write!(f, "[breaking] ")?;
+        }
+
+        let mut location = Vec::new();
+        if !self.record.is_empty() {
+            location.push(format!("record {}", self.record));
+        }
+        if !self.field.is_empty() {
+            location.push(format!("field {}", self.field));
+        }
+
+        if location.is_empty() {
+            write!(f, "{}", self.message())
+        }
+        else {
+            write!(f, "{}: {}", location.join(", "), self.message())
+        }
+    }
+}
+
+/// All changes between two layouts: field types first, then records sorted by name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutDiff {
+    /// version of the old layout
+    pub old_version: String,
+    /// version of the new layout
+    pub new_version: String,
+    /// changes found
+    pub changes: Vec<LayoutChange>,
+}
+
+impl LayoutDiff {
+    /// Tests whether at least one change is breaking.
+    pub fn is_breaking(&self) -> bool {
+        self.changes.iter().any(|c| c.breaking)
+    }
+
+    /// Writes the changes, one per line, followed by a summary.
+    pub fn write_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        if self.old_version != self.new_version {
+            writeln!(w, "version changed from {} to {}", self.old_version, self.new_version)?;
+        }
+        for change in &self.changes {
+            writeln!(w, "{}", change)?;
+        }
+
+        let nb_breaking = self.changes.iter().filter(|c| c.breaking).count();
+        writeln!(w, "{} change(s), {} breaking", self.changes.len(), nb_breaking)
+    }
+
+    /// Returns the changes as a JSON object.
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> ::serde_json::Value {
+        use serde_json::json;
+
+        let changes: Vec<_> = self.changes.iter().map(|c| json!({
+            "kind": c.kind.name(),
+            "record": c.record,
+            "field": c.field,
+            "old": c.old,
+            "new": c.new,
+            "breaking": c.breaking,
+            "message": c.message(),
+        })).collect();
+
+        json!({
+            "old_version": self.old_version,
+            "new_version": self.new_version,
+            "breaking": self.is_breaking(),
+            "changes": changes,
+        })
+    }
+}
+
+// positions of a field, as displayed (starting from 1)
+fn positions(f: &Field) -> String {
+    format!("{} to {}", f.lower_offset + 1, f.upper_offset + 1)
+}
+
+// matches fields of two versions of a record: by name first, then by position and length, then by description.
+// Returns indexes of matched fields in the new record, for each field of the old one
+fn match_fields(old: &[Field], new: &[Field]) -> Vec<Option<usize>> {
+    let mut matches: Vec<Option<usize>> = vec![None; old.len()];
+    let mut matched = vec![false; new.len()];
+
+    // n-th occurrence of each name
+    let occurrences = |fields: &[Field]| {
+        let mut count: HashMap<&str, usize> = HashMap::new();
+        fields.iter().map(|f| {
+            let n = count.entry(&f.name).or_insert(0);
+            *n += 1;
+            (f.name.clone(), *n)
+        }).collect::<Vec<_>>()
+    };
+    let old_keys = occurrences(old);
+    let new_keys = occurrences(new);
+
+    for (i, key) in old_keys.iter().enumerate() {
+        if let Some(j) = new_keys.iter().position(|k| k == key) {
+            matches[i] = Some(j);
+            matched[j] = true;
+        }
+    }
+
+    let same_position = |o: &Field, n: &Field| o.lower_offset == n.lower_offset && o.length == n.length;
+    let same_description = |o: &Field, n: &Field| !o.description.is_empty() && o.description == n.description;
+
+    for same in &[&same_position as &dyn Fn(&Field, &Field) -> bool, &same_description] {
+        for i in 0..old.len() {
+            if matches[i].is_some() {
+                continue;
+            }
+            if let Some(j) = (0..new.len()).find(|&j| !matched[j] && same(&old[i], &new[j])) {
+                matches[i] = Some(j);
+                matched[j] = true;
+            }
+        }
+    }
+
+    matches
+}
+
+// compares two versions of a record
+fn diff_records<T>(old: &Record<T>, new: &Record<T>, changes: &mut Vec<LayoutChange>) {
+    let name = &new.name;
+
+    if old.description != new.description {
+        changes.push(LayoutChange::new(ChangeKind::Description, name, "", old.description.clone(), new.description.clone(), false));
+    }
+    if old.calculated_length != new.calculated_length {
+        changes.push(LayoutChange::new(ChangeKind::RecordLength, name, "",
+            old.calculated_length.to_string(), new.calculated_length.to_string(), true));
+    }
+
+    let matches = match_fields(&old.flist, &new.flist);
+
+    // fields of the new record, in order
+    for (j, nf) in new.flist.iter().enumerate() {
+        let of = match matches.iter().position(|m| *m == Some(j)) {
+            Some(i) => &old.flist[i],
+            None => {
+                changes.push(LayoutChange::new(ChangeKind::FieldAdded, name, &nf.name, String::new(), positions(nf), false));
+                continue;
+            },
+        };
+
+        if of.name != nf.name {
+            changes.push(LayoutChange::new(ChangeKind::FieldRenamed, name, &nf.name, of.name.clone(), nf.name.clone(), false));
+        }
+        if of.length != nf.length {
+            changes.push(LayoutChange::new(ChangeKind::FieldLength, name, &nf.name, of.length.to_string(), nf.length.to_string(), true));
+        }
+        if of.lower_offset != nf.lower_offset {
+            changes.push(LayoutChange::new(ChangeKind::FieldOffset, name, &nf.name,
+                (of.lower_offset + 1).to_string(), (nf.lower_offset + 1).to_string(), true));
+        }
+        if of.ftype.id != nf.ftype.id {
+            let breaking = of.ftype.base_data_type != nf.ftype.base_data_type;
+            changes.push(LayoutChange::new(ChangeKind::FieldType, name, &nf.name, of.ftype.id.clone(), nf.ftype.id.clone(), breaking));
+        }
+        if of.description != nf.description {
+            changes.push(LayoutChange::new(ChangeKind::Description, name, &nf.name, of.description.clone(), nf.description.clone(), false));
+        }
+    }
+
+    // fields only found in the old record
+    for (i, of) in old.flist.iter().enumerate() {
+        if matches[i].is_none() {
+            changes.push(LayoutChange::new(ChangeKind::FieldRemoved, name, &of.name, positions(of), String::new(), true));
+        }
+    }
+}
+
+impl<T> Layout<T> {
+    /// Compares this layout (the old version) with another one (the new version).
+    ///
+    /// Changes flagged as breaking are: removed records or fields, record or field length changes, fields moved,
+    /// fields or field types whose base type changed, and date or time formats changes.
+    pub fn diff(&self, other: &Layout<T>) -> LayoutDiff {
+        let mut changes = Vec::new();
+
+        // field types
+        let mut ids: Vec<_> = self.ftypes.keys().chain(other.ftypes.keys()).collect();
+        ids.sort();
+        ids.dedup();
+
+        for id in ids {
+            match (self.ftypes.get(id), other.ftypes.get(id)) {
+                (Some(_), None) => changes.push(LayoutChange::new(ChangeKind::FieldTypeRemoved, "", "", id.clone(), String::new(), false)),
+                (None, Some(_)) => changes.push(LayoutChange::new(ChangeKind::FieldTypeAdded, "", "", String::new(), id.clone(), false)),
+                (Some(old), Some(new)) => {
+                    let describe = |ft: &::fieldtype::FieldDataType| format!(
+                        "{} {:?} pattern=\"{}\" scale={:?} trim={} padChar=\"{}\" justify={} null={:?}", ft.id,
+                        ft.base_data_type, ft.pattern.as_str(), ft.scale, ft.trim_policy().name(), ft.pad_char, ft.justify.name(),
+                        ft.nulls.iter().map(|n| n.name()).collect::<Vec<_>>());
+                    if describe(old) != describe(new) {
+                        let breaking = old.base_data_type != new.base_data_type;
+                        changes.push(LayoutChange::new(ChangeKind::FieldTypeChanged, "", "", describe(old), describe(new), breaking));
+                    }
+                },
+                (None, None) => unreachable!(),
+            }
+        }
+
+        // records
+        let mut names: Vec<_> = self.rec_map.keys().chain(other.rec_map.keys()).collect();
+        names.sort();
+        names.dedup();
+
+        for name in names {
+            match (self.rec_map.get(name), other.rec_map.get(name)) {
+                (Some(_), None) => changes.push(LayoutChange::new(ChangeKind::RecordRemoved, name, "", String::new(), String::new(), true)),
+                (None, Some(_)) => changes.push(LayoutChange::new(ChangeKind::RecordAdded, name, "", String::new(), String::new(), false)),
+                (Some(old), Some(new)) => diff_records(old, new, &mut changes),
+                (None, None) => unreachable!(),
+            }
+        }
+
+        LayoutDiff { old_version: self.version.clone(), new_version: other.version.clone(), changes }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use diff::ChangeKind;
+    use layout::setup::layout_load_layout_ascii;
+
+    #[test]
+    fn diff_fields() {
+        let old = layout_load_layout_ascii();
+        let mut new = layout_load_layout_ascii();
+
+        {
+            let rec = new.get_mut("NB").unwrap();
+
+            // renamed by position
+            rec.flist[1].name = "NUMBER1".to_string();
+            // renamed by description, and moved
+            rec.flist[3].name = "NUMBER3".to_string();
+            rec.flist[3].lower_offset += 1;
+            // new description
+            rec.flist[4].description = "Fourth number".to_string();
+        }
+        new.remove(vec!["N9"]);
+
+        let changes: Vec<_> = old.diff(&new).changes.iter().map(|c| (c.kind, c.field.clone(), c.breaking)).collect();
+        assert_eq!(changes, vec![
+            (ChangeKind::FieldRenamed, "NUMBER1".to_string(), false),
+            (ChangeKind::FieldRenamed, "NUMBER3".to_string(), false),
+            (ChangeKind::FieldOffset, "NUMBER3".to_string(), true),
+            (ChangeKind::Description, "N4".to_string(), false),
+            (ChangeKind::FieldRemoved, "N9".to_string(), true),
+        ]);
+
+        // same layout
+        assert!(old.diff(&old).changes.is_empty());
+    }
+}
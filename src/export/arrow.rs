@@ -0,0 +1,428 @@
+//! Converts records to Apache Arrow record batches, and exports a whole record-based file to Parquet
+//! files, one file per record type (e.g.: `LL.parquet`, `NB.parquet`).
+//!
+//! An Arrow schema is derived for each record of the layout. Each field is mapped to a column named after
+//! the field (duplicated field names are suffixed by their multiplicity, as in CSV export), and typed
+//! according to the base type of its field type:
+//!
+//! | Base type | Arrow type |
+//! |-----------|------------|
+//! | string    | `Utf8` |
+//! | integer   | `Int64` |
+//! | decimal   | `Decimal128(length, scale)` when the field type declares a scale, `Float64` otherwise |
+//! | date      | `Date32` |
+//! | time      | `Time32(Second)` |
+//!
+//! All columns are nullable: null values (see [fieldtype](../../fieldtype/index.html)) are set to null. A value
+//! which can't be converted to its column type is set to null too, but counted as a reject.
+//!
+//! # Examples
+//! ```rust,no_run
+//! use rbf::record::AsciiMode;
+//! use rbf::layout::Layout;
+//! use rbf::reader::Reader;
+//! use rbf::export::arrow::ParquetExporter;
+//!
+//! let layout = Layout::<AsciiMode>::new("./tests/test.xml");
+//! let mapper = Box::new(|x: &str| x[0..2].to_string());
+//! let mut reader = Reader::<AsciiMode>::new("./tests/test_ascii.data", layout, mapper);
+//!
+//! // write /tmp/LL.parquet, /tmp/NB.parquet, ... with row groups of 10000 rows
+//! ParquetExporter::new("/tmp", 10000).export(&mut reader).unwrap();
+//! ```
+use std::fs::File;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::collections::HashMap;
+
+use arrow::array::{ArrayBuilder, ArrayRef, Date32Builder, Decimal128Builder, Float64Builder, Int64Builder,
+    StringBuilder, Time32SecondBuilder};
+use arrow::datatypes::{DataType, Field as ArrowField, Schema, SchemaRef, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+
+use fieldtype::BaseDataType;
+use field::{Field, FieldValue};
+use layout::Layout;
+use record::{ReadMode, Record};
+use reader::Reader;
+use value::TypedValue;
+use export::{ExportCounts, ValueKind, column_names};
+
+/// Maximum precision of an Arrow 128-bit decimal.
+const MAX_DECIMAL_PRECISION: usize = 38;
+
+/// Returns the Arrow type corresponding to a field.
+pub fn data_type(field: &Field) -> DataType {
+    match field.ftype.base_data_type {
+        BaseDataType::String => DataType::Utf8,
+        BaseDataType::Integer => DataType::Int64,
+        BaseDataType::Decimal => match field.ftype.scale {
+            Some(scale) => {
+                let precision = field.length.clamp(scale.max(1), MAX_DECIMAL_PRECISION);
+                DataType::Decimal128(precision as u8, scale.min(precision) as i8)
+            },
+            None => DataType::Float64,
+        },
+        BaseDataType::Date{..} => DataType::Date32,
+        BaseDataType::Time{..} => DataType::Time32(TimeUnit::Second),
+    }
+}
+
+/// Builds the Arrow schema of a record.
+pub fn schema<T>(rec: &Record<T>) -> Schema {
+    let columns: Vec<_> = column_names(rec).into_iter().zip(rec.flist.iter())
+        .map(|(name, f)| ArrowField::new(name, data_type(f), true))
+        .collect();
+    Schema::new(columns)
+}
+
+/// Builds the Arrow schemas of all records of a layout, keyed by record name.
+pub fn layout_schemas<T>(layout: &Layout<T>) -> HashMap<String, SchemaRef> {
+    layout.rec_map.iter().map(|(name, rec)| (name.clone(), Arc::new(schema(rec)))).collect()
+}
+
+/// Converts a decimal string to an integer scaled by 10^`scale`, rounding extra digits.
+fn parse_decimal(value: &str, scale: usize) -> Option<i128> {
+    let (negative, digits) = match value.as_bytes().first() {
+        Some(&b'-') => (true, &value[1..]),
+        Some(&b'+') => (false, &value[1..]),
+        _ => (false, value),
+    };
+
+    let (int_part, frac_part) = match digits.find('.') {
+        Some(i) => (&digits[..i], &digits[i + 1..]),
+        None => (digits, ""),
+    };
+    if int_part.is_empty() && frac_part.is_empty() {
+        return None;
+    }
+    if !int_part.bytes().chain(frac_part.bytes()).all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let mut result: i128 = 0;
+    for b in int_part.bytes().chain(frac_part.bytes().chain(::std::iter::repeat(b'0')).take(scale)) {
+        result = result.checked_mul(10)?.checked_add((b - b'0') as i128)?;
+    }
+
+    // round half away from zero
+    if frac_part.len() > scale && frac_part.as_bytes()[scale] >= b'5' {
+        result = result.checked_add(1)?;
+    }
+
+    Some(if negative { -result } else { result })
+}
+
+// one Arrow array builder per field
+enum ColumnBuilder {
+    Utf8(StringBuilder),
+    Int64(Int64Builder),
+    Float64(Float64Builder),
+    Decimal128(Decimal128Builder, usize),
+    Date32(Date32Builder),
+    Time32(Time32SecondBuilder),
+}
+
+impl ColumnBuilder {
+    fn new(data_type: &DataType) -> ColumnBuilder {
+        match *data_type {
+            DataType::Int64 => ColumnBuilder::Int64(Int64Builder::new()),
+            DataType::Float64 => ColumnBuilder::Float64(Float64Builder::new()),
+            DataType::Decimal128(_, scale) => ColumnBuilder::Decimal128(
+                Decimal128Builder::new().with_data_type(data_type.clone()), scale as usize),
+            DataType::Date32 => ColumnBuilder::Date32(Date32Builder::new()),
+            DataType::Time32(_) => ColumnBuilder::Time32(Time32SecondBuilder::new()),
+            _ => ColumnBuilder::Utf8(StringBuilder::new()),
+        }
+    }
+
+    // appends the field value, or a null if it's null or can't be converted. Returns false in the last case
+    fn append(&mut self, field: &FieldValue, value_kind: ValueKind) -> bool {
+        if field.is_null() {
+            self.append_null();
+            return true;
+        }
+
+        match *self {
+            ColumnBuilder::Utf8(ref mut b) => b.append_value(value_kind.value(field)),
+            ColumnBuilder::Int64(ref mut b) => match field.typed_value() {
+                Ok(Some(TypedValue::Integer(i))) => b.append_value(i),
+                _ => { b.append_null(); return false; },
+            },
+            ColumnBuilder::Float64(ref mut b) => match field.typed_value() {
+                Ok(Some(TypedValue::Decimal(d))) => b.append_value(d),
+                _ => { b.append_null(); return false; },
+            },
+            ColumnBuilder::Decimal128(ref mut b, scale) => match parse_decimal(field.value(), scale) {
+                Some(d) => b.append_value(d),
+                None => { b.append_null(); return false; },
+            },
+            ColumnBuilder::Date32(ref mut b) => match field.typed_value() {
+                Ok(Some(TypedValue::Date(d))) => b.append_value(d.days_since_epoch() as i32),
+                _ => { b.append_null(); return false; },
+            },
+            ColumnBuilder::Time32(ref mut b) => match field.typed_value() {
+                Ok(Some(TypedValue::Time(t))) => b.append_value(t.seconds_since_midnight() as i32),
+                _ => { b.append_null(); return false; },
+            },
+        }
+        true
+    }
+
+    fn append_null(&mut self) {
+        match *self {
+            ColumnBuilder::Utf8(ref mut b) => b.append_null(),
+            ColumnBuilder::Int64(ref mut b) => b.append_null(),
+            ColumnBuilder::Float64(ref mut b) => b.append_null(),
+            ColumnBuilder::Decimal128(ref mut b, _) => b.append_null(),
+            ColumnBuilder::Date32(ref mut b) => b.append_null(),
+            ColumnBuilder::Time32(ref mut b) => b.append_null(),
+        }
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match *self {
+            ColumnBuilder::Utf8(ref mut b) => Arc::new(b.finish()),
+            ColumnBuilder::Int64(ref mut b) => Arc::new(b.finish()),
+            ColumnBuilder::Float64(ref mut b) => Arc::new(b.finish()),
+            ColumnBuilder::Decimal128(ref mut b, _) => Arc::new(b.finish()),
+            ColumnBuilder::Date32(ref mut b) => Arc::new(b.finish()),
+            ColumnBuilder::Time32(ref mut b) => Arc::new(b.finish()),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match *self {
+            ColumnBuilder::Utf8(ref b) => b.len(),
+            ColumnBuilder::Int64(ref b) => b.len(),
+            ColumnBuilder::Float64(ref b) => b.len(),
+            ColumnBuilder::Decimal128(ref b, _) => b.len(),
+            ColumnBuilder::Date32(ref b) => b.len(),
+            ColumnBuilder::Time32(ref b) => b.len(),
+        }
+    }
+}
+
+/// Accumulates records of the same type into an Arrow record batch.
+pub struct BatchBuilder {
+    /// Arrow schema of the record
+    pub schema: SchemaRef,
+    /// which value is used for string columns
+    pub value_kind: ValueKind,
+    /// number of values of each column which couldn't be converted to the column type, since the builder was
+    /// created
+    pub rejects: Vec<u64>,
+    columns: Vec<ColumnBuilder>,
+}
+
+impl BatchBuilder {
+    /// Creates a new builder for the record type of `rec`.
+    pub fn new<T>(rec: &Record<T>, value_kind: ValueKind) -> BatchBuilder {
+        let schema = Arc::new(schema(rec));
+        let columns: Vec<_> = schema.fields().iter().map(|f| ColumnBuilder::new(f.data_type())).collect();
+
+        BatchBuilder {
+            schema,
+            value_kind,
+            rejects: vec![0; columns.len()],
+            columns,
+        }
+    }
+
+    /// Adds a row with all field values of the record. Values which can't be converted are set to null, and
+    /// counted in **rejects**.
+    pub fn append<T>(&mut self, rec: &Record<T>) {
+        for ((c, fv), rejects) in self.columns.iter_mut().zip(rec.field_values()).zip(&mut self.rejects) {
+            if !c.append(&fv, self.value_kind) {
+                *rejects += 1;
+            }
+        }
+    }
+
+    /// Returns the number of rows appended since the last batch was built.
+    pub fn len(&self) -> usize {
+        self.columns.first().map_or(0, |c| c.len())
+    }
+
+    /// Tests whether no row was appended since the last batch was built.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Builds a record batch with all the rows appended so far, and resets the builder.
+    pub fn finish(&mut self) -> RecordBatch {
+        let columns: Vec<_> = self.columns.iter_mut().map(|c| c.finish()).collect();
+        RecordBatch::try_new(self.schema.clone(), columns).unwrap()
+    }
+}
+
+/// Reads all remaining records from `reader` and converts them into record batches of at most `batch_size`
+/// rows. Each batch is handed to `f` along with its record name as soon as it is full. Remaining rows are
+/// flushed when the end of the file is reached. Returns the number of rows per record type, and of rejected
+/// values.
+pub fn record_batches<T, F, E>(reader: &mut Reader<T>, batch_size: usize, value_kind: ValueKind, mut f: F)
+    -> Result<ExportCounts, E>
+    where Record<T>: ReadMode, F: FnMut(&str, RecordBatch) -> Result<(), E>
+{
+    let mut builders: HashMap<String, BatchBuilder> = HashMap::new();
+    let mut counts = ExportCounts::default();
+
+    while let Some(rec) = reader.next() {
+        if !builders.contains_key(&rec.name) {
+            builders.insert(rec.name.clone(), BatchBuilder::new(rec, value_kind));
+        }
+
+        let builder = builders.get_mut(&rec.name).unwrap();
+        builder.append(rec);
+        *counts.rows.entry(rec.name.clone()).or_insert(0) += 1;
+
+        if builder.len() >= batch_size {
+            f(&rec.name, builder.finish())?;
+        }
+    }
+
+    // flush partial batches
+    for (name, mut builder) in builders {
+        if !builder.is_empty() {
+            f(&name, builder.finish())?;
+        }
+
+        for (column, &rejects) in builder.schema.fields().iter().zip(&builder.rejects).filter(|r| *r.1 > 0) {
+            counts.reject(&name, column.name(), rejects);
+        }
+    }
+
+    Ok(counts)
+}
+
+/// Streams a record-based file into one Parquet file per record type.
+pub struct ParquetExporter {
+    /// directory where Parquet files are created
+    pub output_dir: PathBuf,
+    /// maximum number of rows of each record batch (and row group)
+    pub batch_size: usize,
+    /// which value is used for string columns
+    pub value_kind: ValueKind,
+}
+
+impl ParquetExporter {
+    /// Creates a new exporter writing its Parquet files into `output_dir`.
+    pub fn new(output_dir: &str, batch_size: usize) -> ParquetExporter {
+        ParquetExporter {
+            output_dir: PathBuf::from(output_dir),
+            batch_size,
+            value_kind: ValueKind::Str,
+        }
+    }
+
+    /// Returns the path of the Parquet file for a record.
+    pub fn parquet_file(&self, rec_name: &str) -> PathBuf {
+        self.output_dir.join(format!("{}.parquet", rec_name))
+    }
+
+    /// Reads all remaining records from `reader` and writes each one to its Parquet file. Returns the
+    /// number of rows written per record type, and of rejected values.
+    pub fn export<T>(&self, reader: &mut Reader<T>) -> Result<ExportCounts, ParquetError>
+        where Record<T>: ReadMode
+    {
+        let mut writers: HashMap<String, ArrowWriter<File>> = HashMap::new();
+
+        let counts = record_batches(reader, self.batch_size, self.value_kind, |name, batch| {
+            if !writers.contains_key(name) {
+                let file = File::create(self.parquet_file(name))?;
+                let props = WriterProperties::builder().set_max_row_group_size(self.batch_size).build();
+                writers.insert(name.to_string(), ArrowWriter::try_new(file, batch.schema(), Some(props))?);
+            }
+
+            writers.get_mut(name).unwrap().write(&batch)
+        })?;
+
+        for (_, w) in writers {
+            w.close()?;
+        }
+
+        Ok(counts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{Array, Decimal128Array, Int64Array, StringArray};
+    use arrow::datatypes::DataType;
+
+    use fieldtype::{FieldDataType, NullValue};
+    use field::Field;
+    use record::{AsciiMode, ReadMode, Record};
+    use export::ValueKind;
+    use export::arrow::{BatchBuilder, parse_decimal, schema};
+
+    #[test]
+    fn decimal_parse() {
+        assert_eq!(parse_decimal("12.345", 3), Some(12345));
+        assert_eq!(parse_decimal("12.3", 3), Some(12300));
+        assert_eq!(parse_decimal("-12", 2), Some(-1200));
+        assert_eq!(parse_decimal("12.345", 2), Some(1235));
+        assert_eq!(parse_decimal(".5", 1), Some(5));
+        assert_eq!(parse_decimal("1A", 1), None);
+        assert_eq!(parse_decimal("", 1), None);
+    }
+
+    #[test]
+    fn arrow_batch() {
+        let mut ft_s = FieldDataType::new("S", "string");
+        ft_s.set_nulls(vec![NullValue::Blanks]);
+        let ft_s = Arc::new(ft_s);
+        let ft_i = Arc::new(FieldDataType::new("I", "integer"));
+        let mut ft_n = FieldDataType::new("N", "decimal");
+        ft_n.set_scale(2);
+        ft_n.set_nulls(vec![NullValue::Zeros]);
+        let ft_n = Arc::new(ft_n);
+
+        let mut rec = Record::<AsciiMode>::new("R", "Record", 0);
+        rec.push(Field::from_length("NAME", "Name", &ft_s, 5));
+        rec.push(Field::from_length("QTY", "Quantity", &ft_i, 4));
+        rec.push(Field::from_length("PRICE", "Price", &ft_n, 6));
+        rec.push(Field::from_length("QTY", "Quantity", &ft_i, 4));
+
+        let s = schema(&rec);
+        assert_eq!(s.field(0).data_type(), &DataType::Utf8);
+        assert_eq!(s.field(1).name(), "QTY_0");
+        assert_eq!(s.field(1).data_type(), &DataType::Int64);
+        assert_eq!(s.field(2).data_type(), &DataType::Decimal128(6, 2));
+        assert_eq!(s.field(3).name(), "QTY_1");
+
+        let mut builder = BatchBuilder::new(&rec, ValueKind::Str);
+        rec.set_value("AA     12 12.50  XX");
+        builder.append(&rec);
+        rec.set_value("BB      3  1.5    7");
+        builder.append(&rec);
+        rec.set_value("        1000000   1");
+        builder.append(&rec);
+        assert_eq!(builder.len(), 3);
+
+        let batch = builder.finish();
+        assert!(builder.is_empty());
+        assert_eq!(batch.num_rows(), 3);
+
+        let names = batch.column(0).as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(names.value(1), "BB");
+        assert!(names.is_null(2));
+
+        let qty = batch.column(3).as_any().downcast_ref::<Int64Array>().unwrap();
+        assert!(qty.is_null(0));
+        assert_eq!(qty.value(1), 7);
+
+        // "XX" is not an integer, while blank names and zero prices are nulls
+        assert_eq!(builder.rejects, vec![0, 0, 0, 1]);
+
+        let prices = batch.column(2).as_any().downcast_ref::<Decimal128Array>().unwrap();
+        assert_eq!(prices.value(0), 1250);
+        assert_eq!(prices.value(1), 150);
+        assert!(prices.is_null(2));
+    }
+}
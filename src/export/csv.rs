@@ -0,0 +1,263 @@
+//! Exports a record-based file to CSV, writing one CSV file per record type (e.g.: `LL.csv`, `NB.csv`)
+//! in a target directory.
+//!
+//! Each file starts with a header row built from the field names. When a field name appears more than
+//! once in a record, each occurrence is suffixed by its multiplicity (e.g.: `F5_0`, `F5_1`). Columns are
+//! the fields of each record in the layout of the reader: pruning the layout with
+//! [Layout::retain](../../layout/struct.Layout.html#method.retain) restricts the columns.
+//!
+//! # Examples
+//! ```rust,no_run
+//! use rbf::record::AsciiMode;
+//! use rbf::layout::Layout;
+//! use rbf::reader::Reader;
+//! use rbf::export::csv::{CsvOptions, CsvExporter};
+//!
+//! let layout = Layout::<AsciiMode>::new("./tests/test.xml");
+//! let mapper = Box::new(|x: &str| x[0..2].to_string());
+//! let mut reader = Reader::<AsciiMode>::new("./tests/test_ascii.data", layout, mapper);
+//!
+//! // only keep 2 columns for NB records and export into /tmp/NB.csv
+//! let mut exporter = CsvExporter::new("/tmp", CsvOptions::default());
+//! exporter.set_retain("NB:ID,N2");
+//! exporter.export(&mut reader).unwrap();
+//! ```
+use std::io::{self, BufWriter, Write};
+use std::fs::File;
+use std::path::PathBuf;
+use std::collections::HashMap;
+
+use record::{ReadMode, Record};
+use reader::Reader;
+use util::into_rec_map;
+use export::{ValueKind, column_names};
+
+/// Defines when values are enclosed by quotes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuoteStyle {
+    /// always quote values
+    Always,
+    /// only quote values containing the delimiter, the quote char or a line break
+    Necessary,
+    /// never quote values
+    Never,
+}
+
+/// Options driving the CSV output.
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    /// char separating values
+    pub delimiter: char,
+    /// char used to quote values
+    pub quote: char,
+    /// when to quote values
+    pub quote_style: QuoteStyle,
+    /// which field value is exported
+    pub value_kind: ValueKind,
+    /// whether the header row is written
+    pub header: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> CsvOptions {
+        CsvOptions {
+            delimiter: ',',
+            quote: '"',
+            quote_style: QuoteStyle::Necessary,
+            value_kind: ValueKind::Str,
+            header: true,
+        }
+    }
+}
+
+impl CsvOptions {
+    /// Returns the value, quoted according to the options.
+    pub fn quote_value(&self, value: &str) -> String {
+        let needs_quotes = match self.quote_style {
+            QuoteStyle::Always => true,
+            QuoteStyle::Never => false,
+            QuoteStyle::Necessary => value.chars().any(|c| c == self.delimiter || c == self.quote || c == '\n' || c == '\r'),
+        };
+
+        if !needs_quotes {
+            return value.to_string();
+        }
+
+        // quote chars are escaped by doubling them
+        let mut s = String::with_capacity(value.len() + 2);
+        s.push(self.quote);
+        for c in value.chars() {
+            if c == self.quote {
+                s.push(c);
+            }
+            s.push(c);
+        }
+        s.push(self.quote);
+        s
+    }
+
+    /// Writes one row of values followed by a line break.
+    pub fn write_row<W: Write, I, S>(&self, writer: &mut W, values: I) -> io::Result<()>
+        where I: IntoIterator<Item = S>, S: AsRef<str>
+    {
+        let mut delimiter = [0u8; 4];
+        let delimiter = self.delimiter.encode_utf8(&mut delimiter).as_bytes();
+
+        for (i, v) in values.into_iter().enumerate() {
+            if i != 0 {
+                writer.write_all(delimiter)?;
+            }
+            writer.write_all(self.quote_value(v.as_ref()).as_bytes())?;
+        }
+        writer.write_all(b"\n")
+    }
+}
+
+/// Streams a record-based file into one CSV file per record type.
+pub struct CsvExporter {
+    /// directory where CSV files are created
+    pub output_dir: PathBuf,
+    /// options used to write each file
+    pub options: CsvOptions,
+    /// records and fields to keep, using the `into_rec_map()` syntax
+    pub retain: Option<String>,
+}
+
+impl CsvExporter {
+    /// Creates a new exporter writing its CSV files into `output_dir`.
+    pub fn new(output_dir: &str, options: CsvOptions) -> CsvExporter {
+        CsvExporter {
+            output_dir: PathBuf::from(output_dir),
+            options,
+            retain: None,
+        }
+    }
+
+    /// Only exports the records and fields given in the list (e.g.: `"LL:ID,W1; NB:N1"`).
+    pub fn set_retain(&mut self, rec_list: &str) {
+        self.retain = Some(rec_list.to_string());
+    }
+
+    /// Returns the path of the CSV file for a record.
+    pub fn csv_file(&self, rec_name: &str) -> PathBuf {
+        self.output_dir.join(format!("{}.csv", rec_name))
+    }
+
+    /// Reads all remaining records from `reader` and writes each one to its CSV file. Files are created
+    /// when the first record of their type is met. Returns the number of rows written per record type.
+    pub fn export<T>(&self, reader: &mut Reader<T>) -> io::Result<HashMap<String, u64>>
+        where Record<T>: ReadMode
+    {
+        // indices of the fields kept for each record kept, the layout being left as is
+        let kept: Option<HashMap<String, Vec<usize>>> = self.retain.as_ref().map(|rec_list| {
+            into_rec_map(rec_list).into_iter()
+                .filter_map(|(name, fields)| reader.layout.get(name).map(|rec| {
+                    let indices = rec.flist.iter().enumerate()
+                        .filter(|&(_, f)| fields.contains(&f.name.as_str())).map(|(i, _)| i).collect();
+                    (name.to_string(), indices)
+                }))
+                .collect()
+        });
+
+        let mut writers: HashMap<String, BufWriter<File>> = HashMap::new();
+        let mut nb_rows: HashMap<String, u64> = HashMap::new();
+
+        while let Some(rec) = reader.next() {
+            let indices = match kept {
+                Some(ref kept) => match kept.get(&rec.name) {
+                    Some(indices) => Some(indices),
+                    None => continue,
+                },
+                None => None,
+            };
+            let keep = |i: &usize| indices.is_none_or(|indices| indices.contains(i));
+
+            if !writers.contains_key(&rec.name) {
+                let mut w = BufWriter::new(File::create(self.csv_file(&rec.name))?);
+                if self.options.header {
+                    let names = column_names(rec).into_iter().enumerate().filter(|(i, _)| keep(i)).map(|(_, n)| n);
+                    self.options.write_row(&mut w, names)?;
+                }
+                writers.insert(rec.name.clone(), w);
+            }
+
+            let w = writers.get_mut(&rec.name).unwrap();
+            let values = rec.field_values().enumerate().filter(|(i, _)| keep(i)).map(|(_, fv)| self.options.value_kind.value(&fv));
+            self.options.write_row(w, values)?;
+
+            *nb_rows.entry(rec.name.clone()).or_insert(0) += 1;
+        }
+
+        for (_, mut w) in writers {
+            w.flush()?;
+        }
+
+        Ok(nb_rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use record::{AsciiMode, ReadMode};
+    use export::ValueKind;
+    use export::csv::{CsvOptions, QuoteStyle};
+
+    #[test]
+    fn csv_quote() {
+        let mut options = CsvOptions::default();
+        assert_eq!(options.quote_value("AA"), "AA");
+        assert_eq!(options.quote_value("A,A"), "\"A,A\"");
+        assert_eq!(options.quote_value("A\"A"), "\"A\"\"A\"");
+
+        options.delimiter = ';';
+        assert_eq!(options.quote_value("A,A"), "A,A");
+
+        options.quote_style = QuoteStyle::Always;
+        assert_eq!(options.quote_value("AA"), "\"AA\"");
+
+        options.quote_style = QuoteStyle::Never;
+        assert_eq!(options.quote_value("A;A"), "A;A");
+    }
+
+    #[test]
+    fn csv_row() {
+        let mut rec = ::record::setup::set_up_by_length::<AsciiMode>();
+        rec.set_value("AAAAAAAAAA  BBBBBBBBCCCCCCCCCCCCCCCCCCCCDDDDDDDDDD");
+
+        let mut options = CsvOptions { delimiter: '|', ..CsvOptions::default() };
+
+        let mut output: Vec<u8> = Vec::new();
+        options.write_row(&mut output, ::export::column_names(&rec)).unwrap();
+        options.write_row(&mut output, rec.field_values().map(|fv| options.value_kind.value(&fv))).unwrap();
+
+        options.value_kind = ValueKind::Raw;
+        options.write_row(&mut output, rec.field_values().map(|fv| options.value_kind.value(&fv))).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(),
+            "FIELD1|FIELD2_0|FIELD3|FIELD2_1\n\
+             AAAAAAAAAA|BBBBBBBB|CCCCCCCCCCCCCCCCCCCC|DDDDDDDDDD\n\
+             AAAAAAAAAA|  BBBBBBBB|CCCCCCCCCCCCCCCCCCCC|DDDDDDDDDD\n");
+    }
+
+    #[test]
+    fn csv_row_set_value() {
+        let mut rec = ::record::setup::set_up_by_length::<AsciiMode>();
+        rec.set_value("AAAAAAAAAA  BBBBBBBBCCCCCCCCCCCCCCCCCCCCDDDDDDDDDD");
+
+        // a field value set on its own isn't exported
+        rec[0].set_value("ZZZZZZZZZZ");
+        assert_eq!(rec[0].value(), "ZZZZZZZZZZ");
+
+        let options = CsvOptions::default();
+        let mut output: Vec<u8> = Vec::new();
+        options.write_row(&mut output, rec.field_values().map(|fv| options.value_kind.value(&fv))).unwrap();
+
+        // unlike a new record value
+        rec.set_value("ZZZZZZZZZZ  BBBBBBBBCCCCCCCCCCCCCCCCCCCCDDDDDDDDDD");
+        options.write_row(&mut output, rec.field_values().map(|fv| options.value_kind.value(&fv))).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(),
+            "AAAAAAAAAA,BBBBBBBB,CCCCCCCCCCCCCCCCCCCC,DDDDDDDDDD\n\
+             ZZZZZZZZZZ,BBBBBBBB,CCCCCCCCCCCCCCCCCCCC,DDDDDDDDDD\n");
+    }
+}
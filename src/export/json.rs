@@ -0,0 +1,259 @@
+//! Converts records to JSON objects, and exports a whole record-based file as JSON Lines
+//! (one JSON object per line).
+//!
+//! Each record is converted to an object keyed by field name. When a field name appears more than
+//! once in a record, its values are gathered into an array. When the field type allows it and the
+//! conversion succeeds, values are converted to numbers (integers and decimals) or to ISO 8601
+//! strings (dates and times).
+//!
+//! # Examples
+//! ```rust
+//! use rbf::record::{AsciiMode, ReadMode};
+//! use rbf::layout::Layout;
+//!
+//! let mut layout = Layout::<AsciiMode>::new("./tests/test.xml");
+//! let rec = layout.get_mut("DP").unwrap();
+//! rec.set_value("DPAAAAABBBBBCCCCCDDDDD");
+//!
+//! let json = rec.to_json();
+//! assert_eq!(json["ID"], "DP");
+//! assert_eq!(json["F5"][1], "BBBBB");
+//! ```
+use std::io::{self, Write};
+
+use serde_json::{Map, Number, Value};
+
+use field::FieldValue;
+use record::{ReadMode, Record};
+use reader::Reader;
+use value::TypedValue;
+use export::ValueKind;
+
+/// Key holding the record name, when requested.
+pub const RECORD_KEY: &str = "_record";
+/// Key holding the line number, when requested.
+pub const LINE_KEY: &str = "_line";
+
+/// Options driving the JSON conversion.
+#[derive(Debug, Clone)]
+pub struct JsonOptions {
+    /// which field value is exported
+    pub value_kind: ValueKind,
+    /// convert values to numbers or dates when the conversion succeeds
+    pub typed: bool,
+    /// add the record name under the `_record` key
+    pub with_record_name: bool,
+    /// add the line number under the `_line` key
+    pub with_line_number: bool,
+}
+
+impl Default for JsonOptions {
+    fn default() -> JsonOptions {
+        JsonOptions {
+            value_kind: ValueKind::Str,
+            typed: true,
+            with_record_name: false,
+            with_line_number: false,
+        }
+    }
+}
+
+// converts a single field value
+fn field_to_json(field: &FieldValue, options: &JsonOptions) -> Value {
+    if field.is_null() {
+        return Value::Null;
+    }
+
+    if options.typed {
+        match field.typed_value() {
+            Ok(Some(TypedValue::Integer(i))) => return Value::from(i),
+            Ok(Some(TypedValue::Decimal(d))) => if let Some(n) = Number::from_f64(d) {
+                return Value::Number(n);
+            },
+            Ok(Some(TypedValue::Date(d))) => return Value::String(d.to_string()),
+            Ok(Some(TypedValue::Time(t))) => return Value::String(t.to_string()),
+            _ => (),
+        }
+    }
+
+    Value::String(options.value_kind.value(field).to_string())
+}
+
+// builds the object of all fields, gathering duplicated field names into arrays
+fn fields_to_json<T>(rec: &Record<T>, options: &JsonOptions) -> Map<String, Value> {
+    let mut map = Map::new();
+
+    for fv in rec.field_values() {
+        let value = field_to_json(&fv, options);
+        let f = fv.field;
+
+        // fields whose name is still found several times in the record are gathered into an array
+        if rec.indices_of(&f.name).len() > 1 {
+            match map.get_mut(&f.name) {
+                Some(&mut Value::Array(ref mut v)) => v.push(value),
+                _ => { map.insert(f.name.clone(), Value::Array(vec![value])); },
+            }
+            continue;
+        }
+
+        map.insert(f.name.clone(), value);
+    }
+
+    map
+}
+
+// adds optional record name & line number before the fields
+fn with_meta(name: &str, line_number: Option<u64>, fields: Map<String, Value>, options: &JsonOptions) -> Value {
+    if !options.with_record_name && !options.with_line_number {
+        return Value::Object(fields);
+    }
+
+    let mut map = Map::new();
+    if options.with_record_name {
+        map.insert(RECORD_KEY.to_string(), Value::from(name));
+    }
+    if options.with_line_number {
+        map.insert(LINE_KEY.to_string(), line_number.map_or(Value::Null, Value::from));
+    }
+    map.extend(fields);
+
+    Value::Object(map)
+}
+
+impl<T> Record<T> {
+    /// Converts the record to a JSON object keyed by field name, using default options.
+    pub fn to_json(&self) -> Value {
+        self.to_json_with(&JsonOptions::default(), None)
+    }
+
+    /// Converts the record to a JSON object keyed by field name.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - conversion options
+    /// * `line_number`: line number of the record in its file, written if requested by `options`
+    pub fn to_json_with(&self, options: &JsonOptions, line_number: Option<u64>) -> Value {
+        with_meta(&self.name, line_number, fields_to_json(self, options), options)
+    }
+}
+
+/// Streams a record-based file into JSON Lines.
+pub struct JsonLinesExporter {
+    /// options used to convert each record
+    pub options: JsonOptions,
+}
+
+impl JsonLinesExporter {
+    /// Creates a new exporter.
+    pub fn new(options: JsonOptions) -> JsonLinesExporter {
+        JsonLinesExporter { options }
+    }
+
+    /// Reads all remaining records from `reader` and writes them to `writer`, one JSON object
+    /// per line. Returns the number of records written.
+    pub fn export<T, W: Write>(&self, reader: &mut Reader<T>, writer: &mut W) -> io::Result<u64>
+        where Record<T>: ReadMode
+    {
+        let mut nb_records = 0;
+
+        while let Some((name, fields)) = reader.next().map(|rec| (rec.name.clone(), fields_to_json(rec, &self.options))) {
+
+            let value = with_meta(&name, Some(reader.nblines_read), fields, &self.options);
+            writeln!(writer, "{}", value)?;
+
+            nb_records += 1;
+        }
+
+        writer.flush()?;
+        Ok(nb_records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use record::{AsciiMode, ReadMode};
+    use export::ValueKind;
+    use export::json::JsonOptions;
+
+    #[test]
+    fn record_to_json() {
+        let mut rec = ::record::setup::set_up_by_length::<AsciiMode>();
+        rec.set_value("AAAAAAAAAA       123          12.50     DDDDDDDDDD");
+
+        let json = rec.to_json();
+        assert_eq!(json["FIELD1"], "AAAAAAAAAA");
+        assert_eq!(json["FIELD2"][0], 123);
+        assert_eq!(json["FIELD2"][1], "DDDDDDDDDD");
+        assert_eq!(json["FIELD3"], "12.50");
+        assert!(json.get("_record").is_none());
+
+        // untyped raw values with metadata
+        let options = JsonOptions {
+            value_kind: ValueKind::Raw,
+            typed: false,
+            with_record_name: true,
+            with_line_number: true,
+        };
+        let json = rec.to_json_with(&options, Some(12));
+        assert_eq!(json["_record"], "RECORD1");
+        assert_eq!(json["_line"], 12);
+        assert_eq!(json["FIELD2"][0], "       123");
+
+        // metadata comes first
+        let keys: Vec<_> = match json {
+            Value::Object(ref m) => m.keys().cloned().collect(),
+            _ => panic!("not an object"),
+        };
+        assert_eq!(keys, vec!["_record", "_line", "FIELD1", "FIELD2", "FIELD3"]);
+    }
+
+    #[test]
+    fn record_to_json_removed() {
+        use std::sync::Arc;
+        use field::Field;
+        use fieldtype::FieldDataType;
+        use record::Record;
+
+        let ft = Arc::new(FieldDataType::new("S", "string"));
+        let mut rec = Record::<AsciiMode>::new("R", "Record", 0);
+        for _ in 0..3 {
+            rec.push(Field::from_length("F", "Repeated field", &ft, 2));
+        }
+        rec.push(Field::from_length("G", "Single field", &ft, 2));
+
+        // arrays are built from the fields left, whatever their multiplicity
+        rec.remove(|f| f.name == "F" && f.multiplicity == 1);
+        rec.set_value("AABBCCDD");
+        assert_eq!(rec.to_json()["F"], json!(["AA", "CC"]));
+
+        rec.remove(|f| f.name == "F" && f.multiplicity == 0);
+        assert_eq!(rec.to_json()["F"], "CC");
+    }
+
+    #[test]
+    fn record_to_json_nulls() {
+        use std::sync::Arc;
+        use field::Field;
+        use fieldtype::{FieldDataType, NullValue};
+        use record::Record;
+
+        let mut ft = FieldDataType::new("I", "integer");
+        ft.set_nulls(vec![NullValue::Zeros]);
+        let ft = Arc::new(ft);
+
+        let mut rec = Record::<AsciiMode>::new("R", "Record", 0);
+        rec.push(Field::from_length("QTY", "Quantity", &ft, 4));
+        rec.push(Field::from_length("QTY", "Quantity", &ft, 4));
+
+        rec.set_value("00000012");
+        let json = rec.to_json();
+        assert_eq!(json["QTY"][0], Value::Null);
+        assert_eq!(json["QTY"][1], 12);
+
+        // null whether typed or not
+        let options = JsonOptions { typed: false, ..JsonOptions::default() };
+        assert_eq!(rec.to_json_with(&options, None)["QTY"][0], Value::Null);
+    }
+}
@@ -0,0 +1,85 @@
+//! Exports the records read from a record-based file to other formats. Each exporter streams
+//! through a [Reader](../reader/struct.Reader.html), so only the current record is kept in memory.
+//!
+//! Some exporters depend on external crates and are only available when the corresponding cargo
+//! feature is enabled:
+//!
+//!  * `json`: JSON and JSON Lines export
+//!  * `arrow`: Apache Arrow record batches and Parquet export
+//!  * `sqlite`: loading into a SQLite database
+//!
+//! CSV export and SQL DDL generation are always available.
+//!
+//! Exporters only see the data read from the file: field values are borrowed from the line of each
+//! record (see [Record::field_values](../record/struct.Record.html#method.field_values)), so a value
+//! changed with [Field::set_value](../field/struct.Field.html#method.set_value) is not exported. To
+//! export modified data, set the whole record value instead with **ReadMode::set_value()**.
+use std::collections::HashMap;
+
+use field::FieldValue;
+use record::Record;
+
+pub mod csv;
+pub mod sql;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+/// Defines which field value is exported.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ValueKind {
+    /// blank-stripped value
+    #[default]
+    Str,
+    /// value as found in the line
+    Raw,
+}
+
+impl ValueKind {
+    /// Returns the field value corresponding to this kind.
+    pub fn value<'a>(&self, fv: &FieldValue<'a>) -> &'a str {
+        match *self {
+            ValueKind::Str => fv.value(),
+            ValueKind::Raw => fv.raw_value(),
+        }
+    }
+}
+
+/// Numbers of rows exported into a typed format (SQLite, Arrow), and of values rejected because they couldn't be
+/// converted to their field type (null values are not rejected).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExportCounts {
+    /// number of rows exported, per record name
+    pub rows: HashMap<String, u64>,
+    /// number of rejected values, per record and column name
+    pub rejects: HashMap<(String, String), u64>,
+}
+
+impl ExportCounts {
+    /// Returns the total number of rejected values.
+    pub fn nb_rejects(&self) -> u64 {
+        self.rejects.values().sum()
+    }
+
+    // counts values of a column which couldn't be converted
+    #[cfg(any(feature = "sqlite", feature = "arrow"))]
+    fn reject(&mut self, rec_name: &str, column: &str, nb_values: u64) {
+        *self.rejects.entry((rec_name.to_string(), column.to_string())).or_insert(0) += nb_values;
+    }
+}
+
+/// Returns the column names for a record: field names, where duplicated field names are
+/// suffixed by their multiplicity (e.g.: `F5_0`, `F5_1`).
+pub fn column_names<T>(rec: &Record<T>) -> Vec<String> {
+    rec.flist.iter().map(|f| {
+        if rec.indices_of(&f.name).len() > 1 {
+            format!("{}_{}", f.name, f.multiplicity)
+        }
+        else {
+            f.name.clone()
+        }
+    }).collect()
+}
@@ -0,0 +1,153 @@
+//! Generates SQL DDL statements from a layout: each record is mapped to a table, and each field to a column.
+//! Column types are derived from the base type of the field type and from the field length:
+//!
+//! | Base type | Generic | PostgreSQL | SQLite |
+//! |-----------|---------|------------|--------|
+//! | string    | `VARCHAR(length)` | `VARCHAR(length)` | `TEXT` |
+//! | integer   | `BIGINT` | `BIGINT` | `INTEGER` |
+//! | decimal   | `DECIMAL(length, scale)` | `NUMERIC(length, scale)` | `REAL` |
+//! | date      | `DATE` | `DATE` | `TEXT` |
+//! | time      | `TIME` | `TIME` | `TEXT` |
+//!
+//! Table names are prefixed by the layout schema (`<meta schema="..."/>`) if any, except for SQLite which
+//! doesn't support schemas. Duplicated field names are suffixed by their multiplicity, as in CSV export.
+//!
+//! # Examples
+//! ```rust
+//! use rbf::record::AsciiMode;
+//! use rbf::layout::Layout;
+//! use rbf::export::sql::SqlDialect;
+//!
+//! let layout = Layout::<AsciiMode>::new("./tests/test.xml");
+//! let ddl = layout.to_sql_ddl(SqlDialect::Postgres);
+//!
+//! assert!(ddl.contains("CREATE TABLE \"DP\" (\n    \"ID\" VARCHAR(2),\n    \"F5_0\" VARCHAR(5),"));
+//! ```
+use fieldtype::BaseDataType;
+use field::Field;
+use layout::Layout;
+use record::Record;
+use export::column_names;
+
+/// Name of the column holding the line number, when requested.
+pub const LINE_NUMBER_COLUMN: &str = "LINE_NUMBER";
+
+/// SQL flavours supported when generating DDL.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SqlDialect {
+    /// ANSI SQL
+    Generic,
+    /// PostgreSQL
+    Postgres,
+    /// SQLite
+    Sqlite,
+}
+
+/// Quotes an SQL identifier.
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Returns the SQL column type of a field.
+pub fn column_type(field: &Field, dialect: SqlDialect) -> String {
+    match (&field.ftype.base_data_type, dialect) {
+        (&BaseDataType::String, SqlDialect::Sqlite) => "TEXT".to_string(),
+        (&BaseDataType::String, _) => format!("VARCHAR({})", field.length),
+        (&BaseDataType::Integer, SqlDialect::Sqlite) => "INTEGER".to_string(),
+        (&BaseDataType::Integer, _) => "BIGINT".to_string(),
+        (&BaseDataType::Decimal, SqlDialect::Sqlite) => "REAL".to_string(),
+        (&BaseDataType::Decimal, _) => {
+            let name = if dialect == SqlDialect::Postgres { "NUMERIC" } else { "DECIMAL" };
+            match field.ftype.scale {
+                Some(scale) => format!("{}({}, {})", name, field.length.max(scale), scale),
+                None => format!("{}({})", name, field.length),
+            }
+        },
+        (&BaseDataType::Date{..}, SqlDialect::Sqlite) => "TEXT".to_string(),
+        (&BaseDataType::Date{..}, _) => "DATE".to_string(),
+        (&BaseDataType::Time{..}, SqlDialect::Sqlite) => "TEXT".to_string(),
+        (&BaseDataType::Time{..}, _) => "TIME".to_string(),
+    }
+}
+
+/// Returns the quoted table name of a record, prefixed by `schema` if not empty.
+pub fn table_name(rec_name: &str, schema: &str, dialect: SqlDialect) -> String {
+    if schema.is_empty() || dialect == SqlDialect::Sqlite {
+        quote_identifier(rec_name)
+    }
+    else {
+        format!("{}.{}", quote_identifier(schema), quote_identifier(rec_name))
+    }
+}
+
+/// Builds the `CREATE TABLE` statement of a record. If `with_line_number` is set, a first column
+/// named `LINE_NUMBER` is added.
+pub fn create_table<T>(rec: &Record<T>, schema: &str, dialect: SqlDialect, with_line_number: bool) -> String {
+    let mut columns = Vec::new();
+
+    if with_line_number {
+        let line_type = if dialect == SqlDialect::Sqlite { "INTEGER" } else { "BIGINT" };
+        columns.push(format!("    {} {} NOT NULL", quote_identifier(LINE_NUMBER_COLUMN), line_type));
+    }
+
+    for (name, f) in column_names(rec).iter().zip(rec.flist.iter()) {
+        columns.push(format!("    {} {}", quote_identifier(name), column_type(f, dialect)));
+    }
+
+    format!("CREATE TABLE {} (\n{}\n);\n", table_name(&rec.name, schema, dialect), columns.join(",\n"))
+}
+
+impl<T> Layout<T> {
+    /// Generates one `CREATE TABLE` statement per record, sorted by record name.
+    pub fn to_sql_ddl(&self, dialect: SqlDialect) -> String {
+        let mut names: Vec<_> = self.rec_map.keys().collect();
+        names.sort();
+
+        let statements: Vec<_> = names.iter()
+            .map(|name| create_table(&self.rec_map[*name], &self.schema, dialect, false))
+            .collect();
+
+        statements.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use fieldtype::FieldDataType;
+    use field::Field;
+    use record::{AsciiMode, Record};
+    use export::sql::{SqlDialect, create_table};
+
+    #[test]
+    fn sql_create_table() {
+        let ft_s = Arc::new(FieldDataType::new("S", "string"));
+        let mut ft_n = FieldDataType::new("N", "decimal");
+        ft_n.set_scale(2);
+        let ft_n = Arc::new(ft_n);
+        let ft_d = Arc::new(FieldDataType::new("D", "date"));
+
+        let mut rec = Record::<AsciiMode>::new("ORDER", "Orders", 0);
+        rec.push(Field::from_length("NAME", "Name", &ft_s, 5));
+        rec.push(Field::from_length("PRICE", "Price", &ft_n, 8));
+        rec.push(Field::from_length("DATE", "Date", &ft_d, 8));
+
+        assert_eq!(create_table(&rec, "SALES", SqlDialect::Generic, false),
+            "CREATE TABLE \"SALES\".\"ORDER\" (\n    \"NAME\" VARCHAR(5),\n    \"PRICE\" DECIMAL(8, 2),\n    \"DATE\" DATE\n);\n");
+        assert_eq!(create_table(&rec, "SALES", SqlDialect::Postgres, false),
+            "CREATE TABLE \"SALES\".\"ORDER\" (\n    \"NAME\" VARCHAR(5),\n    \"PRICE\" NUMERIC(8, 2),\n    \"DATE\" DATE\n);\n");
+        assert_eq!(create_table(&rec, "SALES", SqlDialect::Sqlite, true),
+            "CREATE TABLE \"ORDER\" (\n    \"LINE_NUMBER\" INTEGER NOT NULL,\n    \"NAME\" TEXT,\n    \"PRICE\" REAL,\n    \"DATE\" TEXT\n);\n");
+    }
+
+    #[test]
+    fn layout_ddl() {
+        let layout = ::layout::setup::layout_load_layout_ascii();
+        let ddl = layout.to_sql_ddl(SqlDialect::Generic);
+
+        assert_eq!(ddl.matches("CREATE TABLE").count(), 4);
+        assert!(ddl.find("\"DP\"").unwrap() < ddl.find("\"NB\"").unwrap());
+        assert!(ddl.contains("\"N9\" DECIMAL(9)\n"));
+    }
+}
@@ -0,0 +1,144 @@
+//! Loads a record-based file into a SQLite database: one table is created per record of the layout
+//! (see [sql](../sql/index.html) for the DDL), with an additional `LINE_NUMBER` column holding the line
+//! number of each record.
+//!
+//! Rows are inserted using prepared statements, and committed by chunks of `commit_size` rows. Integers,
+//! decimals, dates and times are stored as typed values (dates and times as ISO 8601 text), and null values
+//! (see [fieldtype](../../fieldtype/index.html)) as `NULL`. A value which can't be converted to its field type
+//! is stored as text, and counted as a reject.
+//!
+//! # Examples
+//! ```rust,no_run
+//! use rbf::record::AsciiMode;
+//! use rbf::layout::Layout;
+//! use rbf::reader::Reader;
+//! use rbf::export::sqlite::SqliteLoader;
+//!
+//! let layout = Layout::<AsciiMode>::new("./tests/test.xml");
+//! let mapper = Box::new(|x: &str| x[0..2].to_string());
+//! let mut reader = Reader::<AsciiMode>::new("./tests/test_ascii.data", layout, mapper);
+//!
+//! SqliteLoader::new("/tmp/test.db").load(&mut reader).unwrap();
+//! ```
+use std::path::PathBuf;
+use std::collections::HashMap;
+
+use rusqlite::{Connection, Result, params_from_iter};
+use rusqlite::types::Value;
+
+use field::FieldValue;
+use record::{ReadMode, Record};
+use reader::Reader;
+use value::TypedValue;
+use export::{ExportCounts, ValueKind, column_names};
+use export::sql::{SqlDialect, create_table, quote_identifier, table_name};
+
+/// Streams a record-based file into a SQLite database.
+pub struct SqliteLoader {
+    /// SQLite database file, created if it doesn't exist
+    pub db_file: PathBuf,
+    /// number of rows inserted within a single transaction
+    pub commit_size: usize,
+    /// which value is stored for string columns
+    pub value_kind: ValueKind,
+    /// drop tables first if they already exist
+    pub replace: bool,
+}
+
+// converts a field value to an SQLite value, or returns None if it can't be converted to its field type
+fn sql_value(field: &FieldValue, value_kind: ValueKind) -> Option<Value> {
+    match field.typed_value() {
+        Ok(Some(TypedValue::String(_))) => Some(Value::Text(value_kind.value(field).to_string())),
+        Ok(Some(TypedValue::Integer(i))) => Some(Value::Integer(i)),
+        Ok(Some(TypedValue::Decimal(d))) => Some(Value::Real(d)),
+        Ok(Some(TypedValue::Date(d))) => Some(Value::Text(d.to_string())),
+        Ok(Some(TypedValue::Time(t))) => Some(Value::Text(t.to_string())),
+        Ok(None) => Some(Value::Null),
+        Err(_) => None,
+    }
+}
+
+// builds the INSERT statement of a record
+fn insert_statement<T>(rec: &Record<T>) -> String {
+    let placeholders: Vec<_> = (0..rec.count() + 1).map(|i| format!("?{}", i + 1)).collect();
+    format!("INSERT INTO {} VALUES ({})", table_name(&rec.name, "", SqlDialect::Sqlite), placeholders.join(", "))
+}
+
+impl SqliteLoader {
+    /// Creates a new loader for the database file `db_file`.
+    pub fn new(db_file: &str) -> SqliteLoader {
+        SqliteLoader {
+            db_file: PathBuf::from(db_file),
+            commit_size: 10000,
+            value_kind: ValueKind::Str,
+            replace: false,
+        }
+    }
+
+    /// Creates the tables of all records of the reader layout, reads all remaining records from `reader` and
+    /// inserts each one into its table. Returns the number of rows inserted per record type, and of rejected
+    /// values.
+    pub fn load<T>(&self, reader: &mut Reader<T>) -> Result<ExportCounts>
+        where Record<T>: ReadMode
+    {
+        let conn = Connection::open(&self.db_file)?;
+        self.load_into(&conn, reader)
+    }
+
+    /// Same as `load()`, but using an already opened connection.
+    pub fn load_into<T>(&self, conn: &Connection, reader: &mut Reader<T>) -> Result<ExportCounts>
+        where Record<T>: ReadMode
+    {
+        // create all tables
+        for rec in reader.layout.rec_map.values() {
+            if self.replace {
+                conn.execute_batch(&format!("DROP TABLE IF EXISTS {};", quote_identifier(&rec.name)))?;
+            }
+            conn.execute_batch(&create_table(rec, "", SqlDialect::Sqlite, true))?;
+        }
+
+        let statements: HashMap<String, String> = reader.layout.rec_map.values()
+            .map(|rec| (rec.name.clone(), insert_statement(rec)))
+            .collect();
+        let columns: HashMap<String, Vec<String>> = reader.layout.rec_map.values()
+            .map(|rec| (rec.name.clone(), column_names(rec)))
+            .collect();
+
+        let mut counts = ExportCounts::default();
+        let mut pending = 0;
+
+        conn.execute_batch("BEGIN")?;
+
+        while let Some((rec_name, mut values)) = reader.next().map(|rec| {
+            // first value is the line number, only known once the record is read
+            let mut values = Vec::with_capacity(rec.count() + 1);
+            values.push(Value::Null);
+
+            // SQLite columns accept any value: rejected ones are kept as text
+            for (fv, column) in rec.field_values().zip(&columns[&rec.name]) {
+                values.push(sql_value(&fv, self.value_kind).unwrap_or_else(|| {
+                    counts.reject(&rec.name, column, 1);
+                    Value::Text(self.value_kind.value(&fv).to_string())
+                }));
+            }
+            (rec.name.clone(), values)
+        }) {
+            values[0] = Value::Integer(reader.nblines_read as i64);
+
+            let mut stmt = conn.prepare_cached(&statements[&rec_name])?;
+            stmt.execute(params_from_iter(values))?;
+
+            *counts.rows.entry(rec_name).or_insert(0) += 1;
+
+            pending += 1;
+            if pending == self.commit_size {
+                conn.execute_batch("COMMIT; BEGIN")?;
+                pending = 0;
+            }
+        }
+
+        conn.execute_batch("COMMIT")?;
+
+        Ok(counts)
+    }
+}
@@ -4,15 +4,22 @@
 //! be mapped to a line of text within a file, then a field is a substring from
 //! that line, with a fixed length.
 //!
-//! Each field is holding the substring in the **value()** and **raw_value()** properties.
+//! When a record is read, a field only keeps the byte range of its value within the record line: the value
+//! is then borrowed from the record as a [FieldValue](struct.FieldValue.html). Field values can also be copied
+//! into the **value()** and **raw_value()** properties, which is what **set_value()** does.
+//!
+//! Values are stripped of their padding according to the field type (see
+//! [FieldDataType::strip](../fieldtype/struct.FieldDataType.html#method.strip)), and padded the same way
+//! when written with **pad()**. A value matching one of the null values of the field type (e.g. all blanks)
+//! is null: **is_null()** is true and **typed_value()** returns `None`.
 //!
 //! # Examples
 //! ```rust
-//! use std::rc::Rc;
+//! use std::sync::Arc;
 //! use rbf::fieldtype::FieldDataType;
 //! use rbf::field::Field;
 //!
-//! let ft = Rc::new(FieldDataType::new("I", "integer"));
+//! let ft = Arc::new(FieldDataType::new("I", "integer"));
 //! let mut f1 = Field::from_length("F1", "Description for field 1", &ft, 10);
 //! let mut f2 = Field::from_length("F2", "Description for field 2", &ft, 10);        
 //! 
@@ -28,10 +35,12 @@
 //! ```
 
 use std::fmt;
-use std::rc::Rc;
+use std::sync::Arc;
 use std::cmp::max;
+use std::ops::Range;
 
 use fieldtype::FieldDataType;
+use value::TypedValue;
 
 // useful macro print out data enclosed by HTML tag
 #[doc(hidden)]
@@ -62,11 +71,13 @@ pub struct Field {
     /// field length in chars
     pub length: usize,    
     /// field type of this field, in chars (but not in bytes, because of UTF-8 strings)
-    pub ftype: Rc<FieldDataType>,
+    pub ftype: Arc<FieldDataType>,
     /// field value, copied as-is
     pub raw_value: String,
-    /// blank-stripped field value
+    /// field value stripped of its padding
     pub str_value: String,
+    /// byte range of the field value within the line of its parent record
+    pub byte_range: Range<usize>,
     /// offset in chars of this field within its parent record
     pub offset_from_origin: usize,
     /// index of this field within its record
@@ -95,7 +106,7 @@ impl Field {
     ///
     /// # Panics
     /// If `name` is empty or `length` is 0
-    pub fn from_length(name: &str, description: &str, ftype: &Rc<FieldDataType>, length: usize) -> Field {
+    pub fn from_length(name: &str, description: &str, ftype: &Arc<FieldDataType>, length: usize) -> Field {
         // test arguments: non-sense to deal with empty data
         if name.is_empty() {
             panic!("Cannot create Field with an empty name!");
@@ -112,6 +123,7 @@ impl Field {
             ftype: ftype.clone(), 
             raw_value: String::new(),
             str_value: String::new(),
+            byte_range: 0..0,
             offset_from_origin: 0,
             index: 0,
             lower_offset: 0,
@@ -135,7 +147,7 @@ impl Field {
     ///
     /// # Panics
     /// If `name` is empty or `lower_offset > upper_offset`
-    pub fn from_offset(name: &str, description: &str, ftype: &Rc<FieldDataType>, 
+    pub fn from_offset(name: &str, description: &str, ftype: &Arc<FieldDataType>, 
         lower_offset: usize, upper_offset: usize) -> Field {
         // test arguments: non-sense to deal with empty data
         if name.is_empty() {
@@ -156,6 +168,7 @@ impl Field {
             ftype: ftype.clone(), 
             raw_value: String::new(),
             str_value: String::new(),
+            byte_range: 0..0,
             offset_from_origin: 0,
             index: 0,
             lower_offset: lower_offset-1, // internally kept at origin 0
@@ -167,10 +180,14 @@ impl Field {
 
     }
 
-    /// Sets the value which is blank-stripped and also kept asis in the **raw_value** struct field.
+    /// Sets the value which is stripped of its padding and also kept asis in the **raw_value** struct field.
+    /// Already allocated strings are reused. The record line is left unchanged, so field values borrowed
+    /// from the record (see [FieldValue](struct.FieldValue.html)), and thus exporters, still see the value read.
     pub fn set_value(&mut self, val: &str) {
-        self.str_value = String::from(val.trim());
-        self.raw_value = String::from(val);        
+        self.str_value.clear();
+        self.str_value.push_str(self.ftype.strip(val));
+        self.raw_value.clear();
+        self.raw_value.push_str(val);
     }
 
     /// Returns the field value.
@@ -188,6 +205,24 @@ impl Field {
         self.ftype.pattern.is_match(&self.raw_value)
     }
 
+    /// Tests whether the field value is one of the null values of the field type.
+    pub fn is_null(&self) -> bool {
+        self.ftype.is_null(&self.raw_value)
+    }
+
+    /// Converts the stripped field value according to the field type, or returns `None` if the value is null.
+    pub fn typed_value(&self) -> Result<Option<TypedValue>, String> {
+        if self.is_null() {
+            return Ok(None);
+        }
+        self.ftype.convert(&self.str_value).map(Some)
+    }
+
+    /// Pads a value to the field length, to be written in a record line.
+    pub fn pad(&self, value: &str) -> String {
+        self.ftype.pad(value, self.length)
+    }
+
     /// Prints out field data as an HTML table row (useful for debugging).
     pub fn as_html(&self) {
         println!("<tr>");
@@ -212,6 +247,7 @@ impl Clone for Field {
         // copy other fields which can be potentially already set
         cloned.raw_value = self.raw_value.clone();
         cloned.str_value = self.str_value.clone();      
+        cloned.byte_range = self.byte_range.clone();
         cloned.offset_from_origin = self.offset_from_origin;  
         cloned.index = self.index; 
         cloned.lower_offset = self.lower_offset;
@@ -222,9 +258,69 @@ impl Clone for Field {
     }
 }
 
+/// A field and its value borrowed from the line of the record being read. Values are not copied, and
+/// padding is only stripped when calling **value()**.
+///
+/// # Examples
+/// ```rust
+/// use rbf::record::{ReadMode, AsciiMode};
+///
+/// let mut rec = ::rbf::record::setup::set_up_by_length::<AsciiMode>();
+/// rec.parse("  AAAAAA  BBBBBBBBBBCCCCCCCCCCCCCCCCCCCCDDDDDDDDDD");
+///
+/// let fv = rec.field_value(0);
+/// assert_eq!(&fv.field.name, "FIELD1");
+/// assert_eq!(fv.raw_value(), "  AAAAAA  ");
+/// assert_eq!(fv.value(), "AAAAAA");
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct FieldValue<'a> {
+    /// field definition
+    pub field: &'a Field,
+    /// field value, as found in the line
+    pub raw: &'a str,
+}
+
+impl<'a> FieldValue<'a> {
+    /// Returns the field value as found in the line.
+    pub fn raw_value(&self) -> &'a str {
+        self.raw
+    }
+
+    /// Returns the field value stripped of its padding.
+    pub fn value(&self) -> &'a str {
+        self.field.ftype.strip(self.raw)
+    }
+
+    /// Verifies if the field value is matching the field type pattern.
+    pub fn is_match(&self) -> bool {
+        self.field.ftype.pattern.is_match(self.raw)
+    }
+
+    /// Tests whether the field value is one of the null values of the field type.
+    pub fn is_null(&self) -> bool {
+        self.field.ftype.is_null(self.raw)
+    }
+
+    /// Converts the stripped field value according to the field type, or returns `None` if the value is null.
+    pub fn typed_value(&self) -> Result<Option<TypedValue>, String> {
+        if self.is_null() {
+            return Ok(None);
+        }
+        self.field.ftype.convert(self.value()).map(Some)
+    }
+}
+
+/// Just prints out field name and field value, like for a **Field**.
+impl<'a> fmt::Display for FieldValue<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}='{}'", self.field.name, self.value())
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::rc::Rc;
+    use std::sync::Arc;
 
     use fieldtype::FieldDataType;
     use field::Field;
@@ -248,7 +344,7 @@ mod tests {
         let mut ft = FieldDataType::new("I", "integer");
         ft.set_pattern("\\d+");
 
-        let mut f1 = Field::from_offset("F1", "Description for field 1", &Rc::new(ft), 5, 10); 
+        let mut f1 = Field::from_offset("F1", "Description for field 1", &Arc::new(ft), 5, 10); 
         f1.set_value("123");  
         assert!(f1.is_match());
 
@@ -259,7 +355,7 @@ mod tests {
 
     #[test]
     fn field_cons_offset() {
-        let ft = Rc::new(FieldDataType::new("I", "integer"));
+        let ft = Arc::new(FieldDataType::new("I", "integer"));
         let mut f1 = Field::from_offset("F1", "Description for field 1", &ft, 5, 10);     
         
         assert_eq!(&f1.name, "F1");
@@ -275,7 +371,7 @@ mod tests {
 
     #[test]
     fn field_cons_with_length() {
-        let ft = Rc::new(FieldDataType::new("I", "integer"));
+        let ft = Arc::new(FieldDataType::new("I", "integer"));
         let mut f1 = Field::from_length("F1", "Description for field 1", &ft, 10);     
         
         assert_eq!(&f1.name, "F1");
@@ -289,11 +385,51 @@ mod tests {
 
     }
 
+    #[test]
+    fn field_padding() {
+        use fieldtype::{Justify, Trim};
+
+        let mut ft = FieldDataType::new("I", "integer");
+        ft.set_pad_char('0');
+        ft.set_trim(Trim::Left);
+        ft.set_justify(Justify::Right);
+
+        let mut f1 = Field::from_length("F1", "Description for field 1", &Arc::new(ft), 6);
+        f1.set_value("000042");
+        assert_eq!(f1.value(), "42");
+        assert_eq!(f1.raw_value, "000042");
+        assert_eq!(f1.pad("42"), "000042");
+    }
+
+    #[test]
+    fn field_null() {
+        use fieldtype::NullValue;
+        use value::TypedValue;
+
+        let mut ft = FieldDataType::new("I", "integer");
+        ft.set_nulls(vec![NullValue::Blanks, NullValue::Zeros]);
+
+        let mut f1 = Field::from_length("F1", "Description for field 1", &Arc::new(ft), 4);
+        f1.set_value("    ");
+        assert!(f1.is_null());
+        assert_eq!(f1.typed_value(), Ok(None));
+
+        f1.set_value("0000");
+        assert_eq!(f1.typed_value(), Ok(None));
+
+        f1.set_value("0012");
+        assert!(!f1.is_null());
+        assert_eq!(f1.typed_value(), Ok(Some(TypedValue::Integer(12))));
+
+        f1.set_value("12AB");
+        assert!(f1.typed_value().is_err());
+    }
+
     #[test]
     #[should_panic]
     #[allow(unused_variables)]    
     fn field_badcons() {
-        let ft = Rc::new(FieldDataType::new("I", "integer"));
+        let ft = Arc::new(FieldDataType::new("I", "integer"));
 
         let f1 = Field::from_length("F1", "Description for field 1", &ft, 0); 
         let f2 = Field::from_length("", "Description for field 1", &ft, 10);            
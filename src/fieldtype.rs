@@ -18,12 +18,58 @@
 //! assert_eq!(&ft.id, "I");
 //! assert_eq!(ft.base_data_type, BaseDataType::Integer); 
 //! ```
+//!
+//! Field types also tell how values are padded: values read are stripped of their pad char (blanks by default)
+//! on the sides given by the trim policy, and values written are justified and padded with the pad char. Unless
+//! set, the trim policy strips both sides of blanks, but only the side opposite to the justification of any
+//! other pad char.
+//!
+//! ```rust
+//! use rbf::fieldtype::{FieldDataType, Justify, Trim};
+//!
+//! let mut ft = FieldDataType::new("N", "integer");
+//! ft.set_pad_char('0');
+//! ft.set_justify(Justify::Right);
+//!
+//! assert_eq!(ft.trim_policy(), Trim::Left);
+//! assert_eq!(ft.strip("000120"), "120");
+//! assert_eq!(ft.pad("120", 6), "000120");
+//! assert_eq!(ft.pad("-12", 6), "-00012");
+//! ```
+//!
+//! Field types can declare the values meaning that a field has no value (null sentinels): all blanks, all zeros,
+//! all HIGH-VALUES (0xFF bytes) or all LOW-VALUES (0x00 bytes). 0xFF bytes not being valid UTF-8, they are read
+//! as U+FFFD chars (see [util::into_string](../util/fn.into_string.html)), one per byte: fields holding them
+//! should be read in UTF-8 mode, so that offsets are still counted in chars. A `ÿ` (U+00FF) char, found when
+//! 0xFF bytes were decoded as Latin-1, is a HIGH-VALUE too.
+//!
+//! ```rust
+//! use rbf::fieldtype::{FieldDataType, NullValue};
+//!
+//! let mut ft = FieldDataType::new("N", "integer");
+//! ft.set_nulls(vec![NullValue::Blanks, NullValue::Zeros]);
+//!
+//! assert!(ft.is_null("    ") && ft.is_null("0000"));
+//! assert!(!ft.is_null("0010"));
+//! ```
 
 use std::fmt;
+use std::iter;
 use regex::Regex;
 
+use value::{TypedValue, Date, Time};
+
 /// List all possible field types when built from a string
-const POSSIBLE_TYPES: [&str; 5] = ["string", "decimal", "integer", "date", "time"];
+pub const POSSIBLE_TYPES: [&str; 5] = ["string", "decimal", "integer", "date", "time"];
+
+/// Trim policies allowed in layout files.
+pub const TRIM_POLICIES: [&str; 4] = ["none", "left", "right", "both"];
+
+/// Justifications allowed in layout files.
+pub const JUSTIFICATIONS: [&str; 2] = ["left", "right"];
+
+/// Null sentinels allowed in layout files.
+pub const NULL_VALUES: [&str; 4] = ["blanks", "zeros", "high-values", "low-values"];
 
 #[derive(Debug)]
 #[derive(PartialEq)]
@@ -36,20 +82,145 @@ pub enum BaseDataType {
     Time{ time_format: String },
 }
 
-/// Convenient conversion from a string ref.
+/// Convenient conversion from a string ref, whatever the case.
 impl<'a> From<&'a str> for BaseDataType {
     fn from(original: &'a str) -> BaseDataType {
-        match original {
+        match original.to_lowercase().as_str() {
             "string" => BaseDataType::String,
             "decimal" =>  BaseDataType::Decimal,
             "integer" => BaseDataType::Integer,
-            "date" => BaseDataType::Date{ date_format: "%D%m%s".to_string() },
+            "date" => BaseDataType::Date{ date_format: "%Y%m%d".to_string() },
             "time" => BaseDataType::Time{ time_format: "%H%M%S".to_string() },
-            unknown_type @ _ => panic!("<{}> is not allowed as a field type", unknown_type)
+            _ => panic!("<{}> is not allowed as a field type", original)
+        }
+    }
+}
+
+
+impl BaseDataType {
+    /// Returns the type name, as used in layout files.
+    pub fn type_name(&self) -> &'static str {
+        match *self {
+            BaseDataType::String => "string",
+            BaseDataType::Decimal => "decimal",
+            BaseDataType::Integer => "integer",
+            BaseDataType::Date{..} => "date",
+            BaseDataType::Time{..} => "time",
+        }
+    }
+}
+
+/// Sides of a value stripped of padding when reading.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Trim {
+    None,
+    Left,
+    Right,
+    Both,
+}
+
+/// Convenient conversion from a string ref, whatever the case.
+impl<'a> From<&'a str> for Trim {
+    fn from(original: &'a str) -> Trim {
+        match original.to_lowercase().as_str() {
+            "none" => Trim::None,
+            "left" => Trim::Left,
+            "right" => Trim::Right,
+            "both" => Trim::Both,
+            _ => panic!("<{}> is not allowed as a trim policy", original)
+        }
+    }
+}
+
+impl Trim {
+    /// Returns the trim policy name, as used in layout files.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Trim::None => "none",
+            Trim::Left => "left",
+            Trim::Right => "right",
+            Trim::Both => "both",
+        }
+    }
+}
+
+/// Side of a field a value is aligned on when writing, the other side being padded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Justify {
+    Left,
+    Right,
+}
+
+/// Convenient conversion from a string ref, whatever the case.
+impl<'a> From<&'a str> for Justify {
+    fn from(original: &'a str) -> Justify {
+        match original.to_lowercase().as_str() {
+            "left" => Justify::Left,
+            "right" => Justify::Right,
+            _ => panic!("<{}> is not allowed as a justification", original)
+        }
+    }
+}
+
+impl Justify {
+    /// Returns the justification name, as used in layout files.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Justify::Left => "left",
+            Justify::Right => "right",
+        }
+    }
+}
+
+/// Value meaning that a field has no value, the whole field being filled with the same char.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NullValue {
+    /// all blanks, or an empty field
+    Blanks,
+    /// all `0`
+    Zeros,
+    /// all 0xFF bytes (COBOL HIGH-VALUES), read as U+FFFD (or `ÿ`)
+    HighValues,
+    /// all 0x00 bytes (COBOL LOW-VALUES)
+    LowValues,
+}
+
+/// Convenient conversion from a string ref, whatever the case.
+impl<'a> From<&'a str> for NullValue {
+    fn from(original: &'a str) -> NullValue {
+        match original.to_lowercase().as_str() {
+            "blanks" => NullValue::Blanks,
+            "zeros" => NullValue::Zeros,
+            "high-values" => NullValue::HighValues,
+            "low-values" => NullValue::LowValues,
+            _ => panic!("<{}> is not allowed as a null value", original)
         }
     }
 }
 
+impl NullValue {
+    /// Returns the null value name, as used in layout files.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            NullValue::Blanks => "blanks",
+            NullValue::Zeros => "zeros",
+            NullValue::HighValues => "high-values",
+            NullValue::LowValues => "low-values",
+        }
+    }
+
+    /// Tests whether a field value, as found in the line, is this null value.
+    pub fn matches(&self, value: &str) -> bool {
+        let filled_with = |chars: &[char]| !value.is_empty() && value.chars().all(|x| chars.contains(&x));
+
+        match *self {
+            NullValue::Blanks => value.chars().all(char::is_whitespace),
+            NullValue::Zeros => filled_with(&['0']),
+            NullValue::HighValues => filled_with(&[char::REPLACEMENT_CHARACTER, '\u{FF}']),
+            NullValue::LowValues => filled_with(&['\0']),
+        }
+    }
+}
 
 // implement display trait
 impl fmt::Display for BaseDataType {
@@ -73,11 +244,21 @@ pub struct FieldDataType {
     pub base_data_type: BaseDataType,
     /// Optional pattern which describes field format
     pub pattern: Regex,
+    /// Number of digits after the decimal point, when known (only relevant for decimals)
+    pub scale: Option<usize>,
+    /// Sides stripped of the pad char when reading (see **trim_policy()** when not set)
+    pub trim: Option<Trim>,
+    /// Char filling fields around values (blank by default)
+    pub pad_char: char,
+    /// Side values are aligned on when writing (left by default)
+    pub justify: Justify,
+    /// Values meaning that a field has no value (none by default)
+    pub nulls: Vec<NullValue>,
 }
 
 impl FieldDataType {
     /// Creates a new `FieldDataType` with an ID (a kind of nickname to refer to) and
-    /// a type which should in the list: string, decimal, integer, date or time.
+    /// a type which should in the list: string, decimal, integer, date or time (whatever the case).
     ///
     /// # Arguments
     ///
@@ -92,7 +273,7 @@ impl FieldDataType {
         if string_type.is_empty() {
             panic!("Cannot create FieldDataType with an empty string type!");
         }
-        if !POSSIBLE_TYPES.contains(&string_type) {
+        if !POSSIBLE_TYPES.iter().any(|t| t.eq_ignore_ascii_case(string_type)) {
             panic!("<{}> is not allowed as a field type", string_type);
         }
 
@@ -101,6 +282,11 @@ impl FieldDataType {
             id: id.to_string(), 
             base_data_type: BaseDataType::from(string_type),
             pattern: Regex::new("").unwrap(),
+            scale: None,
+            trim: None,
+            pad_char: ' ',
+            justify: Justify::Left,
+            nulls: Vec::new(),
         }
     }
 
@@ -133,6 +319,131 @@ impl FieldDataType {
     pub fn set_pattern(&mut self, pattern: &str) {
         self.pattern = Regex::new(pattern).unwrap();
     }
+
+    /// Sets the number of digits after the decimal point.
+    ///
+    /// # Arguments
+    ///
+    /// * `scale` - number of digits of the fractional part
+    /// 
+    pub fn set_scale(&mut self, scale: usize) {
+        self.scale = Some(scale);
+    }
+
+    /// Sets the sides stripped of the pad char when reading.
+    pub fn set_trim(&mut self, trim: Trim) {
+        self.trim = Some(trim);
+    }
+
+    /// Returns the sides stripped of the pad char when reading. When not set, both sides are stripped of
+    /// blanks, but only the side opposite to the justification is stripped of any other pad char (e.g.
+    /// leading zeros of a right-justified amount).
+    pub fn trim_policy(&self) -> Trim {
+        match (self.trim, self.pad_char, self.justify) {
+            (Some(trim), _, _) => trim,
+            (None, ' ', _) => Trim::Both,
+            (None, _, Justify::Left) => Trim::Right,
+            (None, _, Justify::Right) => Trim::Left,
+        }
+    }
+
+    /// Sets the char filling fields around values.
+    pub fn set_pad_char(&mut self, pad_char: char) {
+        self.pad_char = pad_char;
+    }
+
+    /// Sets the side values are aligned on when writing.
+    pub fn set_justify(&mut self, justify: Justify) {
+        self.justify = justify;
+    }
+
+    /// Sets the values meaning that a field has no value.
+    pub fn set_nulls(&mut self, nulls: Vec<NullValue>) {
+        self.nulls = nulls;
+    }
+
+    /// Tests whether a field value, as found in the line, is one of the null values of the field type.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - field value, as found in the line
+    ///
+    pub fn is_null(&self, value: &str) -> bool {
+        self.nulls.iter().any(|n| n.matches(value))
+    }
+
+    /// Strips a value read from a field of its pad char, on the sides given by the trim policy. With the
+    /// default blank pad char, any whitespace is stripped. A value only made of zeros is stripped to a single
+    /// `0`. Zeros following a sign are kept (e.g. `-00042`), as the value is not copied.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - field value, as found in the line
+    ///
+    pub fn strip<'a>(&self, value: &'a str) -> &'a str {
+        let pad_char = self.pad_char;
+        let is_pad = |c: char| if pad_char == ' ' { c.is_whitespace() } else { c == pad_char };
+
+        let stripped = match self.trim_policy() {
+            Trim::None => value,
+            Trim::Left => value.trim_start_matches(is_pad),
+            Trim::Right => value.trim_end_matches(is_pad),
+            Trim::Both => value.trim_matches(is_pad),
+        };
+
+        // zero is still a number
+        if stripped.is_empty() && pad_char == '0' && !value.is_empty() {
+            return &value[..1];
+        }
+        stripped
+    }
+
+    /// Pads a value to be written in a field of `length` chars, according to the justification. Longer values
+    /// are truncated to their first `length` chars. Zeros padding a right-justified value come after its sign,
+    /// if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - value to write
+    /// * `length` - field length in chars
+    ///
+    pub fn pad(&self, value: &str, length: usize) -> String {
+        let value: String = value.chars().take(length).collect();
+        let padding: String = iter::repeat_n(self.pad_char, length - value.chars().count()).collect();
+
+        match self.justify {
+            Justify::Left => value + &padding,
+            Justify::Right if self.pad_char == '0' && value.starts_with(['-', '+']) => {
+                format!("{}{}{}", &value[..1], padding, &value[1..])
+            },
+            Justify::Right => padding + &value,
+        }
+    }
+
+    /// Converts a string value to a typed value, according to the base type. Dates and times
+    /// are decoded using the date or time format.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - string value to convert (usually a blank-stripped field value)
+    ///
+    pub fn convert(&self, value: &str) -> Result<TypedValue, String> {
+        match self.base_data_type {
+            BaseDataType::String => Ok(TypedValue::String(value.to_string())),
+            BaseDataType::Integer => value.parse::<i64>()
+                .map(TypedValue::Integer)
+                .map_err(|_| format!("<{}> is not a valid integer", value)),
+            BaseDataType::Decimal => value.parse::<f64>()
+                .map(TypedValue::Decimal)
+                .map_err(|_| format!("<{}> is not a valid decimal", value)),
+            BaseDataType::Date{ ref date_format } => Date::parse(value, date_format)
+                .map(TypedValue::Date)
+                .ok_or(format!("<{}> is not a valid date for format <{}>", value, date_format)),
+            BaseDataType::Time{ ref time_format } => Time::parse(value, time_format)
+                .map(TypedValue::Time)
+                .ok_or(format!("<{}> is not a valid time for format <{}>", value, time_format)),
+        }
+    }
 }
 
 
@@ -147,7 +458,7 @@ impl fmt::Display for FieldDataType {
 #[cfg(test)]
 mod tests {
 
-    use fieldtype::{BaseDataType, FieldDataType};
+    use fieldtype::{BaseDataType, FieldDataType, Justify, NullValue, Trim};
 
     #[test]
     #[should_panic]
@@ -161,6 +472,9 @@ mod tests {
         let ft = FieldDataType::new("I", "integer");
         assert_eq!(&ft.id, "I");
         assert_eq!(ft.base_data_type, BaseDataType::Integer);    
+
+        // base type names are case-insensitive
+        assert_eq!(FieldDataType::new("R", "Decimal").base_data_type, BaseDataType::Decimal);
     }
 
     #[test]
@@ -179,10 +493,96 @@ mod tests {
 
         let ft = FieldDataType::new("D", "date");
         assert_eq!(&ft.id, "D");
-        assert_eq!(ft.base_data_type, BaseDataType::Date{ date_format: "%D%m%s".to_string() });  
+        assert_eq!(ft.base_data_type, BaseDataType::Date{ date_format: "%Y%m%d".to_string() });  
 
         let ft = FieldDataType::new("T", "time");
         assert_eq!(&ft.id, "T");
         assert_eq!(ft.base_data_type, BaseDataType::Time{ time_format: "%H%M%S".to_string() });                                      
     }    
-}  
\ No newline at end of file
+
+    #[test]
+    fn fieldtype_convert() {
+        use value::{TypedValue, Date};
+
+        assert_eq!(FieldDataType::new("S", "string").convert("AB").unwrap(), TypedValue::String("AB".to_string()));
+        assert_eq!(FieldDataType::new("I", "integer").convert("-12").unwrap(), TypedValue::Integer(-12));
+        assert!(FieldDataType::new("I", "integer").convert("1.5").is_err());
+        assert_eq!(FieldDataType::new("N", "decimal").convert("29.5").unwrap(), TypedValue::Decimal(29.5));
+
+        let mut ft = FieldDataType::new("D", "date");
+        ft.set_date_format("%d%m%Y");
+        assert_eq!(ft.convert("25082017").unwrap(), TypedValue::Date(Date { year: 2017, month: 8, day: 25 }));
+        assert!(ft.convert("").is_err());
+    }
+
+    #[test]
+    fn fieldtype_padding() {
+        let mut ft = FieldDataType::new("S", "string");
+        assert_eq!(ft.strip(" \tAB  "), "AB");
+        assert_eq!(ft.pad("AB", 4), "AB  ");
+        assert_eq!(ft.pad("ABCDE", 4), "ABCD");
+
+        ft.set_trim(Trim::Right);
+        assert_eq!(ft.strip("  AB  "), "  AB");
+        ft.set_trim(Trim::None);
+        assert_eq!(ft.strip("  AB  "), "  AB  ");
+
+        ft.set_pad_char('*');
+        ft.set_trim(Trim::Both);
+        ft.set_justify(Justify::Right);
+        assert_eq!(ft.strip("**A B *"), "A B ");
+        assert_eq!(ft.pad("αβ", 4), "**αβ");
+
+        assert_eq!(Trim::from("Left"), Trim::Left);
+        assert_eq!(Justify::from("right").name(), "right");
+    }
+
+    #[test]
+    fn fieldtype_zero_padding() {
+        use value::TypedValue;
+
+        let mut ft = FieldDataType::new("N", "integer");
+        ft.set_pad_char('0');
+        ft.set_justify(Justify::Right);
+
+        // only leading zeros are stripped
+        assert_eq!(ft.trim_policy(), Trim::Left);
+        assert_eq!(ft.strip("000120"), "120");
+        assert_eq!(ft.strip("001000"), "1000");
+        assert_eq!(ft.strip("000000"), "0");
+        assert_eq!(ft.strip("-00042"), "-00042");
+        assert_eq!(ft.convert(ft.strip("-00042")).unwrap(), TypedValue::Integer(-42));
+
+        // sign comes first
+        assert_eq!(ft.pad("0", 6), "000000");
+        assert_eq!(ft.pad("1000", 6), "001000");
+        assert_eq!(ft.pad("-42", 6), "-00042");
+        assert_eq!(ft.pad("+42", 6), "+00042");
+
+        // trailing zeros when left-justified
+        ft.set_justify(Justify::Left);
+        assert_eq!(ft.trim_policy(), Trim::Right);
+        assert_eq!(ft.strip("120000"), "12");
+
+        ft.set_trim(Trim::Both);
+        assert_eq!(ft.strip("001000"), "1");
+        assert_eq!(ft.strip("000000"), "0");
+    }
+
+    #[test]
+    fn fieldtype_nulls() {
+        let mut ft = FieldDataType::new("S", "string");
+        assert!(!ft.is_null(""));
+
+        ft.set_nulls(vec![NullValue::from("HIGH-VALUES"), NullValue::LowValues]);
+        assert!(ft.is_null("\u{FFFD}\u{FFFD}\u{FFFD}") && ft.is_null("ÿÿÿ"));
+        assert!(ft.is_null("\0\0\0"));
+        assert!(!ft.is_null("\u{FFFD}\0\0") && !ft.is_null("A\0\0"));
+        assert!(!ft.is_null("   "));
+
+        ft.set_nulls(vec![NullValue::Blanks, NullValue::Zeros]);
+        assert!(ft.is_null("") && ft.is_null("   ") && ft.is_null("000"));
+        assert!(!ft.is_null(" 0 "));
+        assert_eq!(NullValue::HighValues.name(), "high-values");
+    }
+}
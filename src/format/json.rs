@@ -0,0 +1,17 @@
+//! JSON layout front end.
+use std::io::{Read, Write};
+
+use serde_json;
+
+use format::LayoutModel;
+
+/// Reads a JSON layout.
+pub fn read<R: Read>(reader: R) -> Result<LayoutModel, String> {
+    serde_json::from_reader(reader).map_err(|e| e.to_string())
+}
+
+/// Writes a layout as JSON.
+pub fn write<W: Write>(model: &LayoutModel, writer: &mut W) -> Result<(), String> {
+    serde_json::to_writer_pretty(&mut *writer, model).map_err(|e| e.to_string())?;
+    writeln!(writer).map_err(|e| e.to_string())
+}
@@ -0,0 +1,528 @@
+//! Layout file formats. A layout file is first read into a format-neutral model ([LayoutModel](struct.LayoutModel.html)),
+//! which is then turned into a [Layout](../layout/struct.Layout.html). Each format is a front end reading and
+//! writing this model, so layouts can be converted from one format to another.
+//!
+//! All formats share the same semantics and attribute names as the XML format: `meta` (with `version`,
+//! `description`, `schema`, `reclength`, `ignoreLine`, `skipField` and `mapper`), `fieldtypes`, and
+//! `records` holding their `fields`, given either by `length` or by `start` and `end` (starting from 1).
+//! A record can `extends` another record, its fields then following those of the base record.
+//!
+//! XML is always available, other formats depend on cargo features:
+//!
+//!  * `json`: JSON layouts (`.json`)
+//!  * `yaml`: YAML layouts (`.yaml` or `.yml`)
+//!  * `toml`: TOML layouts (`.toml`)
+//!
+//! The format is chosen from the file extension (XML for any other extension), or given explicitly.
+//!
+//! A layout can include other layout files (`<include href="common.xml"/>` in XML, `includes` list in other
+//! formats), to share field types and records. Included files can be of any format.
+//!
+//! # Examples
+//! ```rust
+//! use rbf::format::{LayoutFormat, LayoutModel};
+//!
+//! let model = LayoutModel::from_file("./tests/test.xml").unwrap();
+//! assert_eq!(model.meta.version, "2017");
+//! assert_eq!(model.records[0].name, "LL");
+//! assert_eq!(model.records[0].fields[1].length, Some(1));
+//!
+//! assert_eq!(LayoutFormat::from_path("layout.yml"), LayoutFormat::Yaml);
+//! ```
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use regex::Regex;
+
+use diagnostic::LayoutDiagnostic;
+use fieldtype::{JUSTIFICATIONS, NULL_VALUES, POSSIBLE_TYPES, TRIM_POLICIES};
+use mapper::RecordMapper;
+
+pub mod xml;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "yaml")]
+pub mod yaml;
+#[cfg(feature = "toml")]
+pub mod toml;
+
+/// Layout file formats.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LayoutFormat {
+    Xml,
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl LayoutFormat {
+    /// Guesses the format from the file extension. Unknown extensions are XML.
+    pub fn from_path(path: &str) -> LayoutFormat {
+        let extension = Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+
+        match extension.as_str() {
+            "json" => LayoutFormat::Json,
+            "yaml" | "yml" => LayoutFormat::Yaml,
+            "toml" => LayoutFormat::Toml,
+            _ => LayoutFormat::Xml,
+        }
+    }
+
+    /// Returns the usual file extension of the format.
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            LayoutFormat::Xml => "xml",
+            LayoutFormat::Json => "json",
+            LayoutFormat::Yaml => "yaml",
+            LayoutFormat::Toml => "toml",
+        }
+    }
+
+    // error returned when the format feature is not enabled
+    #[allow(dead_code)]
+    fn not_enabled(&self) -> String {
+        format!("{} layouts require rbf to be built with the `{}` feature", self.extension(), self.extension())
+    }
+}
+
+/// Layout metadata, found in the `meta` element of XML layouts.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MetaModel {
+    /// layout version
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "String::is_empty"))]
+    pub version: String,
+    /// layout description
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "String::is_empty"))]
+    pub description: String,
+    /// SQL schema name
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "String::is_empty"))]
+    pub schema: String,
+    /// length of all records, or 0
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_zero"))]
+    pub reclength: usize,
+    /// regex of lines to ignore
+    #[cfg_attr(feature = "serde", serde(default, rename = "ignoreLine", skip_serializing_if = "String::is_empty"))]
+    pub ignore_line: String,
+    /// comma-separated list of fields to skip
+    #[cfg_attr(feature = "serde", serde(default, rename = "skipField", skip_serializing_if = "String::is_empty"))]
+    pub skip_field: String,
+    /// mapper pattern (e.g.: "type:1 map:0..2")
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "String::is_empty"))]
+    pub mapper: String,
+}
+
+/// Field type definition.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FieldTypeModel {
+    /// field type name, referenced by fields
+    pub name: String,
+    /// base type: string, decimal, integer, date or time
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
+    pub base_type: String,
+    /// regex describing values
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "String::is_empty"))]
+    pub pattern: String,
+    /// number of digits after the decimal point
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub scale: Option<usize>,
+    /// date or time format
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "String::is_empty"))]
+    pub format: String,
+    /// sides stripped of the pad char when reading: none, left, right or both (the default)
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "String::is_empty"))]
+    pub trim: String,
+    /// char filling fields around values, blank if empty
+    #[cfg_attr(feature = "serde", serde(default, rename = "padChar", skip_serializing_if = "String::is_empty"))]
+    pub pad_char: String,
+    /// side values are aligned on when writing: left (the default) or right
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "String::is_empty"))]
+    pub justify: String,
+    /// comma-separated list of values meaning that a field has no value: blanks, zeros, high-values or low-values
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "String::is_empty"))]
+    pub null: String,
+}
+
+/// Field definition, either by length or by bounds.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FieldModel {
+    /// field name
+    pub name: String,
+    /// field description
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub description: String,
+    /// field type name
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
+    pub ftype: String,
+    /// field length
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub length: Option<usize>,
+    /// first position of the field within its record (starting from 1)
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub start: Option<usize>,
+    /// last position of the field within its record (starting from 1)
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub end: Option<usize>,
+}
+
+/// Record definition.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RecordModel {
+    /// record name, i.e. record ID
+    pub name: String,
+    /// record description
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub description: String,
+    /// declared record length (including fields of the base record), or 0
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_zero"))]
+    pub length: usize,
+    /// name of the base record whose fields come first, or empty
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "String::is_empty"))]
+    pub extends: String,
+    /// fields, in order
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub fields: Vec<FieldModel>,
+}
+
+/// Format-neutral layout definition.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LayoutModel {
+    /// layout files whose field types and records are included, relative to this one. Empty once resolved
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Vec::is_empty"))]
+    pub includes: Vec<String>,
+    /// layout metadata
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub meta: MetaModel,
+    /// field types, in order
+    #[cfg_attr(feature = "serde", serde(default, rename = "fieldtypes"))]
+    pub field_types: Vec<FieldTypeModel>,
+    /// records, in order
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub records: Vec<RecordModel>,
+}
+
+#[cfg(feature = "serde")]
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+// adds `others` to `items`, each one replacing the first item with the same name, unless already replaced: names
+// declared more than once among `others` (i.e. in the same file) are all kept, so that they're still reported
+fn merge_by_name<T, F>(items: &mut Vec<T>, others: Vec<T>, name: F)
+    where F: Fn(&T) -> &str
+{
+    let nb_items = items.len();
+    let mut replaced = vec![false; nb_items];
+
+    for other in others {
+        match (0..nb_items).find(|&i| !replaced[i] && name(&items[i]) == name(&other)) {
+            Some(i) => {
+                items[i] = other;
+                replaced[i] = true;
+            },
+            None => items.push(other),
+        }
+    }
+}
+
+impl LayoutModel {
+    /// Reads a layout file, guessing its format from the file extension.
+    pub fn from_file(path: &str) -> Result<LayoutModel, String> {
+        LayoutModel::from_file_with(path, LayoutFormat::from_path(path))
+    }
+
+    /// Reads a layout file of the given format.
+    pub fn from_file_with(path: &str, format: LayoutFormat) -> Result<LayoutModel, String> {
+        let file = File::open(path).map_err(|e| format!("couldn't open {}: {}", path, e))?;
+        LayoutModel::read(BufReader::new(file), format).map_err(|e| format!("invalid layout file {}: {}", path, e))
+    }
+
+    /// Reads a layout of the given format.
+    pub fn read<R: Read>(reader: R, format: LayoutFormat) -> Result<LayoutModel, String> {
+        match format {
+            LayoutFormat::Xml => xml::read(reader),
+            #[cfg(feature = "json")]
+            LayoutFormat::Json => json::read(reader),
+            #[cfg(feature = "yaml")]
+            LayoutFormat::Yaml => yaml::read(reader),
+            #[cfg(feature = "toml")]
+            LayoutFormat::Toml => toml::read(reader),
+            #[allow(unreachable_patterns)]
+            _ => Err(format.not_enabled()),
+        }
+    }
+
+    /// Replaces includes by the field types and records of the included files, which are themselves resolved.
+    /// `path` is the file the model was read from: included files are relative to its directory.
+    ///
+    /// Included definitions come first, in the order of includes. A field type or record declared again, either in
+    /// a later include or in the including file, replaces the previous declaration. Those declared more than once in
+    /// the same file are all kept, so that they're still reported when the layout is checked. Metadata of included
+    /// files is ignored.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rbf::format::LayoutModel;
+    ///
+    /// let mut model = LayoutModel::from_file("./tests/include/orders.xml").unwrap();
+    /// assert_eq!(model.includes, vec!["common.xml"]);
+    ///
+    /// model.resolve_includes("./tests/include/orders.xml").unwrap();
+    /// assert!(model.includes.is_empty());
+    /// assert!(model.records.iter().any(|rec| rec.name == "HD"));
+    /// ```
+    pub fn resolve_includes(&mut self, path: &str) -> Result<(), String> {
+        self.resolve(path, &mut Vec::new())
+    }
+
+    // resolves includes, `stack` holding the files being resolved to detect cycles
+    fn resolve(&mut self, path: &str, stack: &mut Vec<(PathBuf, String)>) -> Result<(), String> {
+        if self.includes.is_empty() {
+            return Ok(());
+        }
+
+        let canonical = Path::new(path).canonicalize().map_err(|e| format!("couldn't open {}: {}", path, e))?;
+        if let Some(i) = stack.iter().position(|(p, _)| *p == canonical) {
+            let mut cycle: Vec<_> = stack[i..].iter().map(|(_, name)| name.as_str()).collect();
+            cycle.push(path);
+            return Err(format!("include cycle: {}", cycle.join(" -> ")));
+        }
+        stack.push((canonical, path.to_string()));
+
+        let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+        let mut resolved = LayoutModel::default();
+
+        for href in &self.includes {
+            let include_path = dir.join(href);
+            let include_path = include_path.to_str().ok_or(format!("invalid include path {}", href))?;
+
+            let mut included = LayoutModel::from_file(include_path)?;
+            included.resolve(include_path, stack)?;
+            resolved.merge(included);
+        }
+        stack.pop();
+
+        // own declarations come last, overriding included ones
+        self.includes.clear();
+        let own = mem::take(self);
+        resolved.meta = own.meta.clone();
+        resolved.merge(own);
+        *self = resolved;
+
+        Ok(())
+    }
+
+    // adds field types and records of another model, replacing those with the same name
+    fn merge(&mut self, other: LayoutModel) {
+        merge_by_name(&mut self.field_types, other.field_types, |ft| &ft.name);
+        merge_by_name(&mut self.records, other.records, |rec| &rec.name);
+    }
+
+    /// Returns the fields a record inherits from its base record (given by `extends`), and from the base of its base,
+    /// in order. When a record name is declared more than once, the last declaration is the base record.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rbf::format::{LayoutModel, RecordModel};
+    ///
+    /// let mut model = LayoutModel::default();
+    /// model.records.push(RecordModel { name: "A".to_string(), extends: "B".to_string(), ..RecordModel::default() });
+    /// model.records.push(RecordModel { name: "B".to_string(), extends: "A".to_string(), ..RecordModel::default() });
+    ///
+    /// assert_eq!(model.base_fields(&model.records[0]), Err("extends cycle: A -> B -> A".to_string()));
+    /// ```
+    pub fn base_fields(&self, rec: &RecordModel) -> Result<Vec<&FieldModel>, String> {
+        let mut chain = vec![rec.name.as_str()];
+        let mut bases = Vec::new();
+        let mut base_name = rec.extends.as_str();
+
+        while !base_name.is_empty() {
+            let base = self.records.iter().rev().find(|r| r.name == base_name)
+                .ok_or(format!("base record {} of record {} is not declared", base_name, chain[chain.len() - 1]))?;
+
+            if chain.contains(&base_name) {
+                chain.push(base_name);
+                return Err(format!("extends cycle: {}", chain.join(" -> ")));
+            }
+            chain.push(base_name);
+
+            bases.push(base);
+            base_name = base.extends.as_str();
+        }
+
+        // fields of the root base first
+        Ok(bases.iter().rev().flat_map(|base| base.fields.iter()).collect())
+    }
+
+    /// Returns the field type referred to by a field. Besides a declared name, a field can refer to a field type
+    /// case-insensitively (`IDCode` for `IDcode`), by a base type name (`Integer`), or followed by a width and an
+    /// optional scale (`String(40)`, `Real(8.3)`). The width is only informative, while the scale sets the number
+    /// of digits after the decimal point. Field types found this way are named after the reference.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rbf::format::{FieldTypeModel, LayoutModel};
+    ///
+    /// let mut model = LayoutModel::default();
+    /// model.field_types.push(FieldTypeModel { name: "Real".to_string(), base_type: "decimal".to_string(), ..FieldTypeModel::default() });
+    ///
+    /// let ft = model.field_type("real(8.3)").unwrap();
+    /// assert_eq!((ft.name.as_str(), ft.base_type.as_str(), ft.scale), ("real(8.3)", "decimal", Some(3)));
+    /// assert_eq!(model.field_type("Integer").unwrap().base_type, "integer");
+    /// assert!(model.field_type("Complex").is_none());
+    /// ```
+    pub fn field_type(&self, name: &str) -> Option<FieldTypeModel> {
+        // the last declaration is kept
+        if let Some(ft) = self.field_types.iter().rev().find(|ft| ft.name == name) {
+            return Some(ft.clone());
+        }
+
+        let found = self.field_types.iter().rev().find(|ft| ft.name.eq_ignore_ascii_case(name)).cloned()
+            .or_else(|| POSSIBLE_TYPES.iter().find(|t| t.eq_ignore_ascii_case(name))
+                .map(|t| FieldTypeModel { base_type: t.to_string(), ..FieldTypeModel::default() }));
+        if found.is_some() {
+            return found.map(|ft| FieldTypeModel { name: name.to_string(), ..ft });
+        }
+
+        // e.g.: Real(8.3)
+        let sized = Regex::new(r"^(?P<base>.+?)\s*\(\s*\d+\s*(\.\s*(?P<scale>\d+)\s*)?\)$").unwrap();
+        let caps = sized.captures(name)?;
+        let ft = self.field_type(&caps["base"])?;
+
+        Some(FieldTypeModel {
+            name: name.to_string(),
+            scale: caps.name("scale").map(|s| s.as_str().parse().unwrap()).or(ft.scale),
+            ..ft
+        })
+    }
+
+    /// Checks the definition for errors preventing the layout from being loaded: invalid regexes or mapper
+    /// pattern, empty names, unknown base types, trim policies, justifications or null values, unknown base records or `extends` cycles, fields using an
+    /// undeclared field type, and fields with neither a length nor valid bounds. Other problems are found by
+    /// [Layout::validate](../layout/struct.Layout.html#method.validate) once the layout is loaded.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rbf::format::{LayoutFormat, LayoutModel};
+    ///
+    /// let xml = r#"<rbfile><fieldtype name="A" type="string"/>
+    ///     <record name="LL" description="">
+    ///         <field name="ID" description="" type="A" length="2"/><field name="W1" description="" type="B" length="2"/>
+    ///     </record></rbfile>"#;
+    /// let model = LayoutModel::read(xml.as_bytes(), LayoutFormat::Xml).unwrap();
+    ///
+    /// let diagnostics = model.validate();
+    /// assert_eq!(diagnostics.len(), 1);
+    /// assert_eq!(diagnostics[0].to_string(), "error: record LL, field W1: field type B is not declared");
+    /// ```
+    pub fn validate(&self) -> Vec<LayoutDiagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut error = |record: &str, field: &str, message: String| diagnostics.push(LayoutDiagnostic::error(record, field, message));
+
+        if let Err(e) = Regex::new(&self.meta.ignore_line) {
+            error("", "", format!("invalid ignoreLine regex: {}", e));
+        }
+        if !self.meta.mapper.is_empty() {
+            if let Err(e) = RecordMapper::parse(&self.meta.mapper) {
+                error("", "", e);
+            }
+        }
+
+        for ft in &self.field_types {
+            if ft.name.is_empty() {
+                error("", "", "field type with an empty name".to_string());
+            }
+            if !POSSIBLE_TYPES.iter().any(|t| t.eq_ignore_ascii_case(&ft.base_type)) {
+                error("", "", format!("field type {}: <{}> is not allowed as a field type", ft.name, ft.base_type));
+            }
+            if let Err(e) = Regex::new(&ft.pattern) {
+                error("", "", format!("field type {}: invalid pattern: {}", ft.name, e));
+            }
+            if !ft.trim.is_empty() && !TRIM_POLICIES.iter().any(|t| t.eq_ignore_ascii_case(&ft.trim)) {
+                error("", "", format!("field type {}: <{}> is not allowed as a trim policy", ft.name, ft.trim));
+            }
+            if ft.pad_char.chars().count() > 1 {
+                error("", "", format!("field type {}: pad char <{}> is not a single char", ft.name, ft.pad_char));
+            }
+            if !ft.justify.is_empty() && !JUSTIFICATIONS.iter().any(|j| j.eq_ignore_ascii_case(&ft.justify)) {
+                error("", "", format!("field type {}: <{}> is not allowed as a justification", ft.name, ft.justify));
+            }
+            for null in ft.null.split(',').map(|n| n.trim()).filter(|n| !n.is_empty()) {
+                if !NULL_VALUES.iter().any(|n| n.eq_ignore_ascii_case(null)) {
+                    error("", "", format!("field type {}: <{}> is not allowed as a null value", ft.name, null));
+                }
+            }
+        }
+
+        for rec in &self.records {
+            if rec.name.is_empty() {
+                error("", "", "record with an empty name".to_string());
+            }
+            if let Err(e) = self.base_fields(rec) {
+                error(&rec.name, "", e);
+            }
+
+            for f in &rec.fields {
+                if f.name.is_empty() {
+                    error(&rec.name, "", "field with an empty name".to_string());
+                }
+                if self.field_type(&f.ftype).is_none() {
+                    error(&rec.name, &f.name, format!("field type {} is not declared", f.ftype));
+                }
+                match (f.length, f.start, f.end) {
+                    (Some(length), _, _) if length > 0 => (),
+                    (_, Some(start), Some(end)) if start > 0 && start <= end => (),
+                    (_, Some(start), Some(end)) => error(&rec.name, &f.name, format!("invalid bounds {} to {}", start, end)),
+                    (Some(_), _, _) => error(&rec.name, &f.name, "zero-width field".to_string()),
+                    _ => error(&rec.name, &f.name, "neither a length nor bounds".to_string()),
+                }
+            }
+        }
+
+        diagnostics
+    }
+
+    /// Writes the layout in the given format.
+    pub fn write<W: Write>(&self, writer: &mut W, format: LayoutFormat) -> Result<(), String> {
+        match format {
+            LayoutFormat::Xml => xml::write(self, writer).map_err(|e| e.to_string()),
+            #[cfg(feature = "json")]
+            LayoutFormat::Json => json::write(self, writer),
+            #[cfg(feature = "yaml")]
+            LayoutFormat::Yaml => yaml::write(self, writer),
+            #[cfg(feature = "toml")]
+            LayoutFormat::Toml => toml::write(self, writer),
+            #[allow(unreachable_patterns)]
+            _ => Err(format.not_enabled()),
+        }
+    }
+
+    /// Writes the layout into a file, guessing its format from the file extension.
+    pub fn to_file(&self, path: &str) -> Result<(), String> {
+        let file = File::create(path).map_err(|e| format!("couldn't create {}: {}", path, e))?;
+        let mut writer = BufWriter::new(file);
+
+        self.write(&mut writer, LayoutFormat::from_path(path))
+            .and_then(|_| writer.flush().map_err(|e| e.to_string()))
+            .map_err(|e| format!("error when writing {}: {}", path, e))
+    }
+}
+
+/// Converts a layout file into another format, formats being guessed from file extensions.
+///
+/// # Example
+/// ```rust,no_run
+/// // needs the `yaml` feature
+/// rbf::format::convert("./tests/test.xml", "/tmp/test.yaml").unwrap();
+/// ```
+pub fn convert(input: &str, output: &str) -> Result<(), String> {
+    LayoutModel::from_file(input)?.to_file(output)
+}
@@ -0,0 +1,21 @@
+//! TOML layout front end. Field types and records are arrays of tables (`[[fieldtypes]]`, `[[records]]`
+//! and `[[records.fields]]`).
+use std::io::{Read, Write};
+
+use ::toml as toml_rs;
+
+use format::LayoutModel;
+
+/// Reads a TOML layout.
+pub fn read<R: Read>(mut reader: R) -> Result<LayoutModel, String> {
+    let mut s = String::new();
+    reader.read_to_string(&mut s).map_err(|e| e.to_string())?;
+
+    toml_rs::from_str(&s).map_err(|e| e.to_string())
+}
+
+/// Writes a layout as TOML.
+pub fn write<W: Write>(model: &LayoutModel, writer: &mut W) -> Result<(), String> {
+    let s = toml_rs::to_string(model).map_err(|e| e.to_string())?;
+    writer.write_all(s.as_bytes()).map_err(|e| e.to_string())
+}
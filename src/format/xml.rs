@@ -0,0 +1,164 @@
+//! XML layout front end: reads `<meta>`, `<include>`, `<fieldtype>`, `<record>` and `<field>` elements into a layout
+//! model, and writes a layout model back as XML.
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+
+use xml::attribute::OwnedAttribute;
+use xml::escape::escape_str_attribute;
+use xml::reader::{EventReader, XmlEvent};
+
+use format::{FieldModel, FieldTypeModel, LayoutModel, MetaModel, RecordModel};
+
+fn as_hash(attributes: &[OwnedAttribute]) -> HashMap<&str, &str>
+{
+    // loop through attributes to create a hash. Not present in xml_rs (?!)
+    let mut h: HashMap<&str, &str> = HashMap::new();
+
+    for own_attr in attributes {
+        h.insert(&own_attr.name.local_name, &own_attr.value);
+    }
+    h
+}
+
+// gets a mandatory attribute
+fn mandatory<'a>(attr: &HashMap<&str, &'a str>, tag: &str, name: &str) -> Result<&'a str, String> {
+    attr.get(name).cloned().ok_or(format!("missing attribute {} in <{}>", name, tag))
+}
+
+// gets an optional attribute, empty if not found
+fn optional(attr: &HashMap<&str, &str>, name: &str) -> String {
+    attr.get(name).map_or(String::new(), |v| v.to_string())
+}
+
+// gets an optional number attribute
+fn number(attr: &HashMap<&str, &str>, tag: &str, name: &str) -> Result<Option<usize>, String> {
+    match attr.get(name) {
+        Some(v) => v.parse::<usize>().map(Some).map_err(|_| format!("invalid number {}=\"{}\" in <{}>", name, v, tag)),
+        None => Ok(None),
+    }
+}
+
+/// Reads an XML layout.
+pub fn read<R: Read>(reader: R) -> Result<LayoutModel, String> {
+    let mut model = LayoutModel::default();
+
+    // loop through elements
+    for e in EventReader::new(reader) {
+        match e {
+            Ok(XmlEvent::StartElement { name, attributes, .. }) => {
+                // fetch attributes as a hash
+                let attr = as_hash(&attributes);
+                let tag = name.local_name.as_str();
+
+                // now depending on XML tag
+                match tag {
+                    "meta" => {
+                        model.meta = MetaModel {
+                            version: optional(&attr, "version"),
+                            description: optional(&attr, "description"),
+                            schema: optional(&attr, "schema"),
+                            reclength: number(&attr, tag, "reclength")?.unwrap_or(0),
+                            ignore_line: optional(&attr, "ignoreLine"),
+                            skip_field: optional(&attr, "skipField"),
+                            mapper: optional(&attr, "mapper"),
+                        };
+                    },
+                    "include" => model.includes.push(mandatory(&attr, tag, "href")?.to_string()),
+                    "fieldtype" => {
+                        model.field_types.push(FieldTypeModel {
+                            name: mandatory(&attr, tag, "name")?.to_string(),
+                            base_type: mandatory(&attr, tag, "type")?.to_string(),
+                            pattern: optional(&attr, "pattern"),
+                            scale: number(&attr, tag, "scale")?,
+                            format: optional(&attr, "format"),
+                            trim: optional(&attr, "trim"),
+                            pad_char: optional(&attr, "padChar"),
+                            justify: optional(&attr, "justify"),
+                            null: optional(&attr, "null"),
+                        });
+                    },
+                    "record" => {
+                        model.records.push(RecordModel {
+                            name: mandatory(&attr, tag, "name")?.to_string(),
+                            description: mandatory(&attr, tag, "description")?.to_string(),
+                            length: number(&attr, tag, "length")?.unwrap_or(0),
+                            extends: optional(&attr, "extends"),
+                            fields: Vec::new(),
+                        });
+                    },
+                    "field" => {
+                        let field = FieldModel {
+                            name: mandatory(&attr, tag, "name")?.to_string(),
+                            description: mandatory(&attr, tag, "description")?.to_string(),
+                            ftype: mandatory(&attr, tag, "type")?.to_string(),
+                            length: number(&attr, tag, "length")?,
+                            start: number(&attr, tag, "start")?,
+                            end: number(&attr, tag, "end")?,
+                        };
+
+                        // fields belong to the last record met
+                        match model.records.last_mut() {
+                            Some(rec) => rec.fields.push(field),
+                            None => return Err(format!("field {} is not within a record", field.name)),
+                        }
+                    },
+                    _ => (),
+                }
+            },
+            Err(e) => return Err(e.to_string()),
+            _ => {},
+        }
+    }
+
+    Ok(model)
+}
+
+/// Writes a layout as XML.
+pub fn write<W: Write>(model: &LayoutModel, writer: &mut W) -> io::Result<()> {
+    // attributes are only written when set
+    let attribute = |name: &str, value: &str| if value.is_empty() {
+        String::new()
+    }
+    else {
+        format!(" {}=\"{}\"", name, escape_str_attribute(value))
+    };
+    let number = |name: &str, value: Option<usize>| value.map_or(String::new(), |n| format!(" {}=\"{}\"", name, n));
+    let length = |name: &str, value: usize| number(name, if value == 0 { None } else { Some(value) });
+
+    let meta = &model.meta;
+    writeln!(writer, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(writer, "<rbfile>")?;
+    writeln!(writer, "    <meta{}{}{}{}{}{}{}/>",
+        attribute("version", &meta.version), attribute("description", &meta.description),
+        attribute("schema", &meta.schema), length("reclength", meta.reclength),
+        attribute("ignoreLine", &meta.ignore_line), attribute("skipField", &meta.skip_field),
+        attribute("mapper", &meta.mapper))?;
+    writeln!(writer)?;
+
+    for href in &model.includes {
+        writeln!(writer, "    <include href=\"{}\"/>", escape_str_attribute(href))?;
+    }
+
+    for ft in &model.field_types {
+        writeln!(writer, "    <fieldtype{}{}{}{}{}{}{}{}{}/>", attribute("name", &ft.name), attribute("type", &ft.base_type),
+            attribute("pattern", &ft.pattern), number("scale", ft.scale), attribute("format", &ft.format),
+            attribute("trim", &ft.trim), attribute("padChar", &ft.pad_char), attribute("justify", &ft.justify),
+            attribute("null", &ft.null))?;
+    }
+
+    for rec in &model.records {
+        writeln!(writer)?;
+        writeln!(writer, "    <record{} description=\"{}\"{}{}>", attribute("name", &rec.name),
+            escape_str_attribute(&rec.description), length("length", rec.length), attribute("extends", &rec.extends))?;
+
+        for f in &rec.fields {
+            writeln!(writer, "        <field{} description=\"{}\"{}{}{}{}/>", attribute("name", &f.name),
+                escape_str_attribute(&f.description), number("length", f.length), number("start", f.start),
+                number("end", f.end), attribute("type", &f.ftype))?;
+        }
+
+        writeln!(writer, "    </record>")?;
+    }
+
+    writeln!(writer, "</rbfile>")
+}
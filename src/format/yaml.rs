@@ -0,0 +1,16 @@
+//! YAML layout front end.
+use std::io::{Read, Write};
+
+use serde_yaml;
+
+use format::LayoutModel;
+
+/// Reads a YAML layout.
+pub fn read<R: Read>(reader: R) -> Result<LayoutModel, String> {
+    serde_yaml::from_reader(reader).map_err(|e| e.to_string())
+}
+
+/// Writes a layout as YAML.
+pub fn write<W: Write>(model: &LayoutModel, writer: &mut W) -> Result<(), String> {
+    serde_yaml::to_writer(writer, model).map_err(|e| e.to_string())
+}
@@ -0,0 +1,243 @@
+//! Index of a record-based file, allowing random access into huge files. The file is scanned once to
+//! store the byte offset of every Nth line, and the line number and byte offset of each record, by record
+//! ID. The index is saved into a sidecar file next to the data file (`<data file>.rbfidx`).
+//!
+//! The data file size and modification time are stored in the index, which is considered stale as soon
+//! as they change.
+//!
+//! # Examples
+//! ```rust
+//! use rbf::index::LineIndex;
+//! use rbf::mapper::RecordHasher;
+//!
+//! let mapper: RecordHasher = Box::new(|x: &str| x[0..2].to_string());
+//! let index = LineIndex::build("./tests/test_ascii.data", &mapper, 100).unwrap();
+//!
+//! assert_eq!(index.nb_lines, 743);
+//! assert_eq!(index.line_offsets.len(), 8);
+//! assert_eq!(index.records["LL"].len(), 257);
+//! assert!(index.is_valid());
+//!
+//! // nearest indexed line before line 250
+//! assert_eq!(index.locate_line(250).unwrap().0, 201);
+//! ```
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::time::UNIX_EPOCH;
+
+use mapper::RecordHasher;
+use util::read_line;
+
+/// Default number of lines between two indexed lines
+pub const DEFAULT_STEP: u64 = 1000;
+
+/// Magic bytes and version at the beginning of an index file
+const MAGIC: &[u8] = b"RBFIDX\x01";
+
+pub struct LineIndex {
+    /// data file which is indexed
+    pub data_file: String,
+    /// data file size when indexed
+    pub file_size: u64,
+    /// data file modification time when indexed, in nanoseconds since the Unix epoch
+    pub mtime: u64,
+    /// number of lines between two indexed lines
+    pub step: u64,
+    /// number of lines of the data file
+    pub nb_lines: u64,
+    /// byte offsets of lines 1, step+1, 2*step+1, ...
+    pub line_offsets: Vec<u64>,
+    /// line number and byte offset of each record, by record ID
+    pub records: HashMap<String, Vec<(u64, u64)>>,
+}
+
+// size and modification time of a file
+fn file_stamp(data_file: &str) -> io::Result<(u64, u64)> {
+    let metadata = fs::metadata(data_file)?;
+    let mtime = metadata.modified()?.duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    Ok((metadata.len(), mtime))
+}
+
+// index files store integers as LEB128 varints
+fn write_varint<W: Write>(w: &mut W, mut n: u64) -> io::Result<()> {
+    loop {
+        let byte = (n & 0x7F) as u8;
+        n >>= 7;
+        if n == 0 {
+            return w.write_all(&[byte]);
+        }
+        w.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut n = 0u64;
+    let mut byte = [0u8];
+
+    for shift in (0..64).step_by(7) {
+        r.read_exact(&mut byte)?;
+        n |= ((byte[0] & 0x7F) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::InvalidData, "invalid varint in index file"))
+}
+
+// offsets and line numbers are increasing, so only differences are stored
+fn write_deltas<W: Write>(w: &mut W, values: &[(u64, u64)]) -> io::Result<()> {
+    write_varint(w, values.len() as u64)?;
+    let mut previous = (0, 0);
+    for &(line, offset) in values {
+        write_varint(w, line - previous.0)?;
+        write_varint(w, offset - previous.1)?;
+        previous = (line, offset);
+    }
+    Ok(())
+}
+
+fn read_deltas<R: Read>(r: &mut R) -> io::Result<Vec<(u64, u64)>> {
+    let length = read_varint(r)?;
+    let mut values = Vec::with_capacity(length.min(1 << 20) as usize);
+    let mut previous = (0, 0);
+    for _ in 0..length {
+        previous = (previous.0 + read_varint(r)?, previous.1 + read_varint(r)?);
+        values.push(previous);
+    }
+    Ok(values)
+}
+
+impl LineIndex {
+    /// Returns the name of the index file of a data file.
+    pub fn index_file(data_file: &str) -> String {
+        format!("{}.rbfidx", data_file)
+    }
+
+    /// Scans the data file once to build its index.
+    ///
+    /// # Arguments
+    ///
+    /// * `data_file` - name and path of the record-based file to index
+    /// * `mapper` function to map each line to a record name
+    /// * `step`: number of lines between two indexed lines
+    pub fn build(data_file: &str, mapper: &RecordHasher, step: u64) -> io::Result<LineIndex> {
+        let (file_size, mtime) = file_stamp(data_file)?;
+        let step = step.max(1);
+
+        let mut bufreader = BufReader::new(File::open(data_file)?);
+        let mut line = String::new();
+        let mut offset = 0;
+        let mut nb_lines = 0;
+        let mut line_offsets = Vec::new();
+        let mut records: HashMap<String, Vec<(u64, u64)>> = HashMap::new();
+
+        loop {
+            let length = read_line(&mut bufreader, &mut line)? as u64;
+            if length == 0 {
+                break;
+            }
+
+            if nb_lines % step == 0 {
+                line_offsets.push(offset);
+            }
+            nb_lines += 1;
+
+            records.entry(mapper(&line)).or_default().push((nb_lines, offset));
+            offset += length;
+        }
+
+        Ok(LineIndex {
+            data_file: data_file.to_string(),
+            file_size,
+            mtime,
+            step,
+            nb_lines,
+            line_offsets,
+            records,
+        })
+    }
+
+    /// Saves the index into its sidecar file.
+    pub fn save(&self) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(LineIndex::index_file(&self.data_file))?);
+
+        w.write_all(MAGIC)?;
+        for &n in &[self.file_size, self.mtime, self.step, self.nb_lines] {
+            write_varint(&mut w, n)?;
+        }
+
+        // line offsets are stored like record positions, with line numbers
+        let lines: Vec<_> = self.line_offsets.iter().enumerate()
+            .map(|(i, &offset)| (i as u64 * self.step + 1, offset))
+            .collect();
+        write_deltas(&mut w, &lines)?;
+
+        write_varint(&mut w, self.records.len() as u64)?;
+        for (name, positions) in &self.records {
+            write_varint(&mut w, name.len() as u64)?;
+            w.write_all(name.as_bytes())?;
+            write_deltas(&mut w, positions)?;
+        }
+
+        w.flush()
+    }
+
+    /// Loads the index of a data file from its sidecar file. The index might be stale.
+    pub fn load(data_file: &str) -> io::Result<LineIndex> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+        let mut r = BufReader::new(File::open(LineIndex::index_file(data_file))?);
+
+        let mut magic = [0u8; 7];
+        r.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(invalid("not an index file, or unsupported version"));
+        }
+
+        let file_size = read_varint(&mut r)?;
+        let mtime = read_varint(&mut r)?;
+        let step = read_varint(&mut r)?;
+        let nb_lines = read_varint(&mut r)?;
+        let line_offsets = read_deltas(&mut r)?.into_iter().map(|(_, offset)| offset).collect();
+
+        let mut records = HashMap::new();
+        for _ in 0..read_varint(&mut r)? {
+            let mut name = vec![0u8; read_varint(&mut r)? as usize];
+            r.read_exact(&mut name)?;
+            let name = String::from_utf8(name).map_err(|_| invalid("invalid record ID in index file"))?;
+            records.insert(name, read_deltas(&mut r)?);
+        }
+
+        Ok(LineIndex { data_file: data_file.to_string(), file_size, mtime, step, nb_lines, line_offsets, records })
+    }
+
+    /// Loads the index of a data file if it's up to date, otherwise builds and saves it.
+    pub fn open(data_file: &str, mapper: &RecordHasher, step: u64) -> io::Result<LineIndex> {
+        if let Ok(index) = LineIndex::load(data_file) {
+            if index.is_valid() {
+                return Ok(index);
+            }
+        }
+
+        let index = LineIndex::build(data_file, mapper, step)?;
+        index.save()?;
+        Ok(index)
+    }
+
+    /// Tests whether the data file is unchanged since it was indexed (same size and modification time).
+    pub fn is_valid(&self) -> bool {
+        file_stamp(&self.data_file).is_ok_and(|stamp| stamp == (self.file_size, self.mtime))
+    }
+
+    /// Returns the nearest indexed line before or at line `n` (starting from 1), as a line number and
+    /// its byte offset. Returns **None** if the file has less than `n` lines.
+    pub fn locate_line(&self, n: u64) -> Option<(u64, u64)> {
+        if n == 0 || n > self.nb_lines {
+            return None;
+        }
+
+        let i = (n - 1) / self.step;
+        self.line_offsets.get(i as usize).map(|&offset| (i * self.step + 1, offset))
+    }
+}
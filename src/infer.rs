@@ -0,0 +1,549 @@
+//! Guesses a layout from a sample of an undocumented record-based file. The result is only a starting
+//! point, written as an XML layout which can be loaded by **Layout::new()**, and annotated with comments
+//! giving the confidence of each guess.
+//!
+//! Guessing is done in 3 steps:
+//!
+//!  * the record ID prefix length is found from the number of distinct line prefixes of each length:
+//!    record IDs give few groups of lines (record types are few compared to the number of lines), while
+//!    adding the following chars, which are data, gives far more groups
+//!  * for each group of lines (i.e. each record type), field boundaries are put where the prevailing
+//!    character class of a column (digits, letters, blanks or other chars) changes. Blank columns are
+//!    merged with the neighbouring field they pad, and numbers with a single `.`, `,`, `-`, `/` or `:`
+//!    separator (decimals, dates) are kept as one field
+//!  * the type of each field is guessed from its blank-stripped values: `date` (for usual date
+//!    formats), `integer`, `decimal` or `string`
+//!
+//! # Examples
+//! ```rust
+//! use rbf::infer::{GuessedType, LayoutGuess};
+//!
+//! let lines = vec![
+//!     "01JOHN      20170825   12.50",
+//!     "01MARY      20180101  150.00",
+//!     "01ROBERT    20161231    3.75",
+//!     "01ALICE     20150704 1020.00",
+//!     "01KATE      20190315    0.99",
+//!     "02000123PARIS     ",
+//!     "02004567LONDON    ",
+//!     "02120000ROME      ",
+//!     "99000008",
+//! ];
+//! let guess = LayoutGuess::from_lines(&lines);
+//!
+//! assert_eq!(guess.prefix_length, 2);
+//! assert_eq!(guess.records.len(), 3);
+//!
+//! let rec = &guess.records[0];
+//! assert_eq!(rec.name, "01");
+//! assert_eq!(rec.length, 28);
+//!
+//! // ID, name, date and amount
+//! let types: Vec<_> = rec.fields.iter().map(|f| f.guessed_type.clone()).collect();
+//! assert_eq!(types, vec![GuessedType::String, GuessedType::String, GuessedType::Date("%Y%m%d"), GuessedType::Decimal]);
+//! assert_eq!(rec.fields[1].length, 10);
+//! ```
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufReader, Write};
+
+use xml::escape::escape_str_attribute;
+
+use value::Date;
+use util::read_line;
+
+/// Maximum length of the record ID prefix
+const MAX_PREFIX_LENGTH: usize = 8;
+
+/// Minimum ratio of values which should convert to a type for this type to be chosen
+const MIN_TYPE_RATIO: f64 = 0.95;
+
+/// Date formats tried when guessing dates, with the field type name used for each one
+const DATE_FORMATS: [(&str, &str); 4] = [
+    ("%Y%m%d", "YYYYMMDD"),
+    ("%Y-%m-%d", "YYYY-MM-DD"),
+    ("%d/%m/%Y", "DD/MM/YYYY"),
+    ("%d%m%Y", "DDMMYYYY"),
+];
+
+/// Type guessed for a field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GuessedType {
+    String,
+    Integer,
+    Decimal,
+    /// date, with its format
+    Date(&'static str),
+}
+
+impl GuessedType {
+    // field type name and base type used in the XML layout
+    fn field_type(&self) -> (&'static str, &'static str) {
+        match *self {
+            GuessedType::String => ("A", "string"),
+            GuessedType::Integer => ("I", "integer"),
+            GuessedType::Decimal => ("N", "decimal"),
+            GuessedType::Date(format) => {
+                let name = DATE_FORMATS.iter().find(|d| d.0 == format).unwrap().1;
+                (name, "date")
+            },
+        }
+    }
+}
+
+/// Field guessed within a record.
+#[derive(Debug, Clone)]
+pub struct FieldGuess {
+    /// field name: ID for the record ID, FILLER for blank columns, otherwise F1, F2, ...
+    pub name: String,
+    /// offset of the first char of the field (starting from 0)
+    pub start: usize,
+    /// field length in chars
+    pub length: usize,
+    /// guessed type
+    pub guessed_type: GuessedType,
+    /// ratio of non-blank values converted to the guessed type (1 for strings)
+    pub type_confidence: f64,
+    /// ratio of non-blank chars of the field columns sharing the prevailing class of their column
+    pub boundary_confidence: f64,
+}
+
+/// Record guessed from a group of lines sharing the same record ID.
+#[derive(Debug, Clone)]
+pub struct RecordGuess {
+    /// record ID, used as the record name
+    pub name: String,
+    /// number of lines of the sample with this record ID
+    pub nb_lines: usize,
+    /// most frequent line length in chars, used as the record length
+    pub length: usize,
+    /// number of lines with a different length
+    pub nb_other_lengths: usize,
+    /// fields, in order
+    pub fields: Vec<FieldGuess>,
+}
+
+/// Layout guessed from a sample.
+#[derive(Debug, Clone)]
+pub struct LayoutGuess {
+    /// number of non-empty lines of the sample
+    pub nb_lines: usize,
+    /// length of the record ID prefix (0 if the sample is empty)
+    pub prefix_length: usize,
+    /// how sharply the prefix separates record IDs from data, between 0 and 1
+    pub prefix_confidence: f64,
+    /// guessed records, sorted by record ID
+    pub records: Vec<RecordGuess>,
+}
+
+/// Character classes of columns
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CharClass {
+    Blank,
+    Digit,
+    Letter,
+    Other,
+}
+
+impl CharClass {
+    fn of(c: char) -> CharClass {
+        if c.is_whitespace() { CharClass::Blank }
+        else if c.is_ascii_digit() { CharClass::Digit }
+        else if c.is_alphabetic() { CharClass::Letter }
+        else { CharClass::Other }
+    }
+}
+
+// statistics of a single column of a group of lines
+struct Column {
+    /// prevailing class of non-blank chars, Blank if the column is always blank
+    class: CharClass,
+    /// ratio of non-blank chars of the prevailing class
+    purity: f64,
+    /// whether the column is blank in some lines
+    has_blanks: bool,
+    /// the only non-blank char found in the column, if any
+    constant: Option<char>,
+}
+
+impl Column {
+    fn new(chars: &[Vec<char>], i: usize) -> Column {
+        // number of digits, letters and other chars
+        let classes = [CharClass::Digit, CharClass::Letter, CharClass::Other];
+        let mut counts = [0usize; 3];
+        let mut distinct = HashSet::new();
+        let mut has_blanks = false;
+
+        for line in chars {
+            // missing chars of short lines are blanks
+            let c = line.get(i).cloned().unwrap_or(' ');
+            match classes.iter().position(|&class| class == CharClass::of(c)) {
+                Some(k) => counts[k] += 1,
+                None => { has_blanks = true; continue; },
+            }
+            distinct.insert(c);
+        }
+
+        let nonblank: usize = counts.iter().sum();
+        let k = (0..3).max_by_key(|&k| (counts[k], 3 - k)).unwrap();
+        let (class, purity) = if nonblank == 0 {
+            (CharClass::Blank, 1.0)
+        }
+        else {
+            (classes[k], counts[k] as f64 / nonblank as f64)
+        };
+        let constant = if distinct.len() == 1 { distinct.into_iter().next() } else { None };
+
+        Column { class, purity, has_blanks, constant }
+    }
+}
+
+// a run of columns sharing the same class: columns start..end
+#[derive(Debug, Clone)]
+struct Run {
+    start: usize,
+    end: usize,
+    class: CharClass,
+}
+
+impl LayoutGuess {
+    /// Guesses a layout from the first `max_lines` lines of a data file (all lines if 0).
+    pub fn from_file(data_file: &str, max_lines: usize) -> io::Result<LayoutGuess> {
+        let mut bufreader = BufReader::new(File::open(data_file)?);
+        let mut lines = Vec::new();
+        let mut line = String::new();
+
+        while read_line(&mut bufreader, &mut line)? != 0 {
+            lines.push(line.clone());
+            if lines.len() == max_lines {
+                break;
+            }
+        }
+
+        Ok(LayoutGuess::from_lines(&lines))
+    }
+
+    /// Guesses a layout from sample lines. Trailing newlines and empty lines are ignored.
+    pub fn from_lines<S: AsRef<str>>(lines: &[S]) -> LayoutGuess {
+        let lines: Vec<&str> = lines.iter()
+            .map(|l| l.as_ref().trim_end_matches(['\n', '\r']))
+            .filter(|l| !l.is_empty())
+            .collect();
+
+        let (prefix_length, prefix_confidence) = guess_prefix_length(&lines);
+
+        // group lines by record ID, sorted by record ID
+        let mut groups: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+        for line in &lines {
+            let id: String = line.chars().take(prefix_length).collect();
+            groups.entry(id).or_default().push(line);
+        }
+
+        let records = groups.into_iter()
+            .map(|(name, group)| guess_record(name, &group, prefix_length))
+            .collect();
+
+        LayoutGuess { nb_lines: lines.len(), prefix_length, prefix_confidence, records }
+    }
+
+    /// Writes the guessed layout as an XML layout file, with comments giving the confidence of guesses.
+    ///
+    /// # Arguments
+    ///
+    /// * `w` - where to write the layout
+    /// * `description` - layout description, e.g. the sample file name
+    pub fn write_xml<W: Write>(&self, w: &mut W, description: &str) -> io::Result<()> {
+        writeln!(w, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(w, "<!-- layout guessed from {} lines: check it before using it -->", self.nb_lines)?;
+        writeln!(w, "<rbfile>")?;
+
+        if self.prefix_length > 0 {
+            writeln!(w, "    <meta version=\"1.0\" description=\"{}\" mapper=\"type:1 map:0..{}\"/>",
+                escape_str_attribute(description), self.prefix_length)?;
+        }
+        else {
+            writeln!(w, "    <meta version=\"1.0\" description=\"{}\"/>", escape_str_attribute(description))?;
+        }
+        writeln!(w, "    <!-- record ID prefix length {}: {} record types, confidence {:.2} -->",
+            self.prefix_length, self.records.len(), self.prefix_confidence)?;
+        writeln!(w)?;
+
+        // only declare field types which are used
+        let ftypes = self.records.iter().flat_map(|rec| rec.fields.iter().map(|f| f.guessed_type.clone()));
+        let mut declared = HashSet::new();
+        for ft in ftypes {
+            let (name, base_type) = ft.field_type();
+            if !declared.insert(name) {
+                continue;
+            }
+            match ft {
+                GuessedType::Date(format) =>
+                    writeln!(w, "    <fieldtype name=\"{}\" type=\"{}\" format=\"{}\"/>", name, base_type, format)?,
+                _ => writeln!(w, "    <fieldtype name=\"{}\" type=\"{}\"/>", name, base_type)?,
+            }
+        }
+
+        for rec in &self.records {
+            writeln!(w)?;
+            if rec.nb_lines == 1 {
+                writeln!(w, "    <!-- 1 line, {} chars long -->", rec.length)?;
+            }
+            else if rec.nb_other_lengths == 0 {
+                writeln!(w, "    <!-- {} lines, all {} chars long -->", rec.nb_lines, rec.length)?;
+            }
+            else {
+                writeln!(w, "    <!-- {} lines, {} of them not {} chars long -->", rec.nb_lines, rec.nb_other_lengths, rec.length)?;
+            }
+            writeln!(w, "    <record name=\"{}\" description=\"Record {}\" length=\"{}\">",
+                escape_str_attribute(&rec.name), escape_str_attribute(&rec.name), rec.length)?;
+
+            for f in &rec.fields {
+                let description = match f.name.as_str() {
+                    "ID" => "Record ID".to_string(),
+                    "FILLER" => "Blanks".to_string(),
+                    _ => format!("Columns {} to {}", f.start + 1, f.start + f.length),
+                };
+                writeln!(w, "        <field name=\"{}\" description=\"{}\" length=\"{}\" type=\"{}\"/> \
+                    <!-- type confidence {:.2}, boundary confidence {:.2} -->",
+                    f.name, description, f.length, f.guessed_type.field_type().0, f.type_confidence, f.boundary_confidence)?;
+            }
+
+            writeln!(w, "    </record>")?;
+        }
+
+        writeln!(w, "</rbfile>")
+    }
+
+    /// Returns the guessed layout as an XML string.
+    pub fn to_xml(&self, description: &str) -> String {
+        let mut buf = Vec::new();
+        self.write_xml(&mut buf, description).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+}
+
+// number of distinct prefixes of a given length in chars
+fn distinct_prefixes(lines: &[&str], length: usize) -> usize {
+    lines.iter()
+        .map(|line| line.char_indices().nth(length).map_or(*line, |(i, _)| &line[..i]))
+        .collect::<HashSet<_>>()
+        .len()
+}
+
+// guesses the record ID prefix length, and how sharply it separates IDs from data
+fn guess_prefix_length(lines: &[&str]) -> (usize, f64) {
+    if lines.is_empty() {
+        return (0, 0.0);
+    }
+
+    // record types are expected to be far fewer than lines
+    let max_groups = ((lines.len() as f64).sqrt().ceil() as usize).clamp(2, 64);
+
+    // prefixes are only considered if they're ASCII (the mapper slices bytes) and present in all lines
+    let max_length = lines.iter()
+        .map(|line| line.chars().take_while(|c| c.is_ascii() && !c.is_whitespace()).count())
+        .min().unwrap_or(0)
+        .min(MAX_PREFIX_LENGTH);
+
+    // number of distinct prefixes of each length
+    let counts: Vec<usize> = (0..max_length + 2).map(|length| distinct_prefixes(lines, length)).collect();
+
+    // a record ID prefix gives few groups, and is followed by data giving far more groups: look for the
+    // largest increase of groups relative to the number of groups. Only the shortest prefix giving a
+    // number of groups is a candidate
+    let mut best: Option<(f64, usize)> = None;
+    for length in 1..=max_length {
+        let n = counts[length];
+        if n > max_groups || (length > 1 && n == counts[length - 1]) {
+            continue;
+        }
+
+        let next = (length..=max_length + 1).map(|l| counts[l]).find(|&m| m != n).unwrap_or(n);
+        let score = next as f64 / (n * n) as f64;
+        if best.is_none_or(|(s, _)| score > s) {
+            best = Some((score, length));
+        }
+    }
+    let mut length = match best {
+        Some((_, length)) => length,
+        None => return (0, 0.0),
+    };
+
+    // IDs like "HDR" or "LL" don't split lines more than their first char: extend the prefix while the next
+    // char gives the same groups and is of the same class as the previous one in every line
+    let same_class = |length: usize| lines.iter().all(|line| {
+        let mut chars = line.chars().skip(length - 1);
+        CharClass::of(chars.next().unwrap()) == chars.next().map_or(CharClass::Blank, CharClass::of)
+    });
+    while length < max_length && same_class(length) && counts[length + 1] == counts[length] {
+        length += 1;
+    }
+
+    (length, 1.0 - counts[length] as f64 / counts[length + 1] as f64)
+}
+
+// guesses fields of a group of lines sharing the same record ID
+fn guess_record(name: String, group: &[&str], prefix_length: usize) -> RecordGuess {
+    let chars: Vec<Vec<char>> = group.iter().map(|line| line.chars().collect()).collect();
+
+    // most frequent length, the longest one if several
+    let mut lengths: HashMap<usize, usize> = HashMap::new();
+    for line in &chars {
+        *lengths.entry(line.len()).or_insert(0) += 1;
+    }
+    let (length, n) = lengths.into_iter().max_by_key(|&(l, n)| (n, l)).unwrap();
+
+    let columns: Vec<Column> = (0..length).map(|i| Column::new(&chars, i)).collect();
+
+    let mut fields = Vec::new();
+    if prefix_length > 0 {
+        fields.push(guess_field("ID".to_string(), 0, prefix_length, &chars, &columns));
+    }
+
+    let mut nb_fields = 0;
+    for run in split_columns(&columns, prefix_length) {
+        let name = if run.class == CharClass::Blank {
+            "FILLER".to_string()
+        }
+        else {
+            nb_fields += 1;
+            format!("F{}", nb_fields)
+        };
+        fields.push(guess_field(name, run.start, run.end - run.start, &chars, &columns));
+    }
+
+    RecordGuess { name, nb_lines: group.len(), length, nb_other_lengths: group.len() - n, fields }
+}
+
+// splits columns from `start` into runs of columns sharing the same class
+fn split_columns(columns: &[Column], start: usize) -> Vec<Run> {
+    let mut runs: Vec<Run> = Vec::new();
+    for (i, col) in columns.iter().enumerate().skip(start) {
+        match runs.last_mut() {
+            Some(run) if run.class == col.class => run.end = i + 1,
+            _ => runs.push(Run { start: i, end: i + 1, class: col.class }),
+        }
+    }
+
+    // a single separator char between numbers is part of a decimal or a date: merge the 3 runs
+    let mut i = 1;
+    while i + 1 < runs.len() {
+        let separator = &columns[runs[i].start];
+        if runs[i].class == CharClass::Other && runs[i].end - runs[i].start == 1
+            && runs[i - 1].class == CharClass::Digit && runs[i + 1].class == CharClass::Digit
+            && separator.constant.is_some_and(|c| ".,-/:".contains(c)) {
+            runs[i - 1].end = runs[i + 1].end;
+            runs.drain(i..i + 2);
+        }
+        else {
+            i += 1;
+        }
+    }
+
+    // blank runs pad the previous field (left-justified values) or the next one (right-justified values)
+    let mut merged: Vec<Run> = Vec::new();
+    let mut pending: Option<Run> = None;
+    for run in runs {
+        if run.class == CharClass::Blank {
+            match merged.last_mut() {
+                Some(last) if columns[last.end - 1].has_blanks => last.end = run.end,
+                _ => pending = Some(run),
+            }
+            continue;
+        }
+
+        let mut run = run;
+        if let Some(blank) = pending.take() {
+            if columns[run.start].has_blanks {
+                run.start = blank.start;
+            }
+            else {
+                merged.push(blank);
+            }
+        }
+        merged.push(run);
+    }
+    merged.extend(pending);
+
+    merged
+}
+
+// guesses the type of a field from its values
+fn guess_field(name: String, start: usize, length: usize, chars: &[Vec<char>], columns: &[Column]) -> FieldGuess {
+    let values: Vec<String> = chars.iter()
+        .map(|line| line.iter().skip(start).take(length).collect::<String>().trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect();
+
+    // ratio of values accepted by a test
+    let ratio = |test: &dyn Fn(&str) -> bool| {
+        values.iter().filter(|v| test(v)).count() as f64 / values.len() as f64
+    };
+
+    let mut guessed_type = (GuessedType::String, 1.0);
+    if !values.is_empty() && name != "ID" {
+        let is_integer = |v: &str| {
+            let digits = v.strip_prefix(['-', '+']).unwrap_or(v);
+            !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+        };
+        let is_decimal = |v: &str| v.contains('.') && v.parse::<f64>().is_ok();
+
+        let mut candidates: Vec<_> = DATE_FORMATS.iter()
+            .map(|&(format, _)| (GuessedType::Date(format), ratio(&|v: &str| Date::parse(v, format).is_some())))
+            .collect();
+        candidates.push((GuessedType::Integer, ratio(&is_integer)));
+        candidates.push((GuessedType::Decimal, ratio(&|v: &str| is_integer(v) || is_decimal(v))));
+
+        if let Some(candidate) = candidates.into_iter().find(|c| c.1 >= MIN_TYPE_RATIO) {
+            guessed_type = candidate;
+        }
+    }
+
+    let nonblank_columns: Vec<_> = columns[start..start + length].iter().filter(|c| c.class != CharClass::Blank).collect();
+    let boundary_confidence = if nonblank_columns.is_empty() {
+        1.0
+    }
+    else {
+        nonblank_columns.iter().map(|c| c.purity).sum::<f64>() / nonblank_columns.len() as f64
+    };
+
+    FieldGuess {
+        name,
+        start,
+        length,
+        guessed_type: guessed_type.0,
+        type_confidence: guessed_type.1,
+        boundary_confidence,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use infer::{guess_prefix_length, LayoutGuess};
+
+    #[test]
+    fn infer_prefix_length() {
+        // 1-char IDs followed by varying data
+        let lines: Vec<String> = (0..100).map(|i| format!("{}{:05}", ["H", "D", "T"][i % 3], i * 7)).collect();
+        let lines: Vec<_> = lines.iter().map(|l| l.as_str()).collect();
+        assert_eq!(guess_prefix_length(&lines).0, 1);
+
+        // no prefix without lines
+        assert_eq!(guess_prefix_length(&[]), (0, 0.0));
+    }
+
+    #[test]
+    fn infer_blank_padding() {
+        // left-justified name padded with blanks, right-justified number
+        let lines = vec!["AAJOHN        42", "AAROBERT    1234", "AAAL           7", "BB12   XY", "BB34   ZW"];
+        let guess = LayoutGuess::from_lines(&lines);
+        assert_eq!(guess.prefix_length, 2);
+
+        let fields: Vec<_> = guess.records[0].fields.iter().map(|f| (f.name.as_str(), f.start, f.length)).collect();
+        assert_eq!(fields, vec![("ID", 0, 2), ("F1", 2, 10), ("F2", 12, 4)]);
+
+        // blanks between values never blank
+        let fields: Vec<_> = guess.records[1].fields.iter().map(|f| (f.name.as_str(), f.start, f.length)).collect();
+        assert_eq!(fields, vec![("ID", 0, 2), ("F1", 2, 2), ("FILLER", 4, 3), ("F2", 7, 2)]);
+    }
+}
@@ -29,19 +29,18 @@
 //!  }
 //! ```
 use std::env;
-use std::fs::File;
-use std::error::Error;
-use std::io::BufReader;
-use std::rc::Rc;
+use std::io::{self, Write};
+use std::sync::Arc;
 use std::collections::HashMap;
 
-use xml::reader::{EventReader, XmlEvent};
 use regex::Regex;
 
-use fieldtype::FieldDataType;
-use field::Field;
+use fieldtype::{BaseDataType, FieldDataType, Justify, NullValue, Trim};
+use field::{Field, FieldCreationType};
 use record::Record;
-use mapper::{RecordHasher, RecordMapper};
+use format::{self, FieldModel, FieldTypeModel, LayoutFormat, LayoutModel, MetaModel, RecordModel};
+use mapper::{RecordMapper, SyncRecordHasher};
+use diagnostic::LayoutDiagnostic;
 use util::into_field_list;
 
 // useful macro to get value from attribute name
@@ -54,6 +53,40 @@ macro_rules! get_value {
 }
 
 
+// creates a field type from its definition
+fn field_type(ftm: &FieldTypeModel) -> FieldDataType {
+    let mut ft = FieldDataType::new(&ftm.name, &ftm.base_type);
+
+    // optional attributes
+    if !ftm.pattern.is_empty() {
+        ft.set_pattern(&ftm.pattern);
+    }
+    if let Some(scale) = ftm.scale {
+        ft.set_scale(scale);
+    }
+    if !ftm.format.is_empty() {
+        match ft.base_data_type {
+            BaseDataType::Date{..} => ft.set_date_format(&ftm.format),
+            BaseDataType::Time{..} => ft.set_time_format(&ftm.format),
+            _ => (),
+        }
+    }
+    if !ftm.trim.is_empty() {
+        ft.set_trim(Trim::from(ftm.trim.as_str()));
+    }
+    if let Some(pad_char) = ftm.pad_char.chars().next() {
+        ft.set_pad_char(pad_char);
+    }
+    if !ftm.justify.is_empty() {
+        ft.set_justify(Justify::from(ftm.justify.as_str()));
+    }
+    if !ftm.null.is_empty() {
+        ft.set_nulls(ftm.null.split(',').map(|n| n.trim()).filter(|n| !n.is_empty()).map(NullValue::from).collect());
+    }
+
+    ft
+}
+
 //#[derive(Debug)]
 pub struct Layout<T> {
     /// XML layout file name
@@ -64,7 +97,7 @@ pub struct Layout<T> {
     pub version: String,
      /// Layout file description   
     pub description: String,
-    /// SQL schema name (future use)
+    /// SQL schema name, used to prefix table names when generating SQL DDL
     pub schema: String,
     /// Regex for lines to exclude from reading
     pub ignore_line: Regex,
@@ -73,226 +106,123 @@ pub struct Layout<T> {
     /// Hash map of all read records from file
     pub rec_map: HashMap<String, Record<T>>,
     /// Hash map of all field types found when reading
-    pub ftypes: HashMap<String, Rc<FieldDataType>>,
+    pub ftypes: HashMap<String, Arc<FieldDataType>>,
     // closure which maps each line to a record ID
-    pub mapper: RecordHasher,
-}
-
-use xml::attribute::OwnedAttribute;
-fn as_hash(attributes: &Vec<OwnedAttribute>) -> HashMap<&str, &str>
-{
-    // loop through attributes to create a hash. Not present in xml_rs (?!)
-    let mut h: HashMap<&str, &str> = HashMap::new();
-
-    for own_attr in attributes {
-        h.insert(&own_attr.name.local_name, &own_attr.value);
-    }
-    h
+    pub mapper: SyncRecordHasher,
+    /// Mapper pattern found in the layout file (empty if none). Not updated if **mapper** is replaced
+    pub mapper_pattern: String,
+    /// Problems found when loading the layout (e.g.: duplicated records), reported by **validate()**
+    pub diagnostics: Vec<LayoutDiagnostic>,
 }
 
 impl<T> Layout<T> {
-    /// Reads the XML layout file to create record and field structs.
+    /// Reads the layout file to create record and field structs. The layout format is guessed from the
+    /// file extension: see the [format](../format/index.html) module. Included layout files are read too.
     ///
     /// # Arguments
     ///
-    /// * `xml_file` - full file name and path of the XML layout file
+    /// * `xml_file` - full file name and path of the layout file
     /// 
     ///
     /// # Panics
     /// If `xml_file` could not be read   
     pub fn new(xml_file: &str) -> Layout<T> {
-        // try to open xml_file
-        let file = match File::open(&xml_file) {
-            // The `description` method of `io::Error` returns a string that
-            // describes the error
-            Err(why) => panic!("couldn't open {}: {}, current directory is: {}", 
-                            xml_file, why.description(), env::current_dir().unwrap().display()),
-            Ok(file) => BufReader::new(file),
-        };
+        Layout::with_format(xml_file, LayoutFormat::from_path(xml_file))
+    }
+
+    /// Reads a layout file of the given format, and the layout files it includes.
+    ///
+    /// # Panics
+    /// If `file` could not be read   
+    pub fn with_format(file: &str, format: LayoutFormat) -> Layout<T> {
+        let model = LayoutModel::from_file_with(file, format)
+            .and_then(|mut model| model.resolve_includes(file).map(|_| model));
+
+        match model {
+            Ok(model) => Layout::from_model(&model, file),
+            Err(why) => panic!("{}, current directory is: {}", why, env::current_dir().unwrap().display()),
+        }
+    }
 
+    /// Creates record and field structs from a layout model. `file` is the file name the model was read from,
+    /// if any.
+    ///
+    /// # Panics
+    /// If a field refers to an unknown field type, or has neither a length nor bounds, or if a record extends an
+    /// unknown record
+    pub fn from_model(model: &LayoutModel, file: &str) -> Layout<T> {
         // define hash to hold fieldtypes
-        let mut ftypes: HashMap<String, Rc<FieldDataType>> = HashMap::new();
+        let mut ftypes: HashMap<String, Arc<FieldDataType>> = HashMap::new();
+        let mut diagnostics = Vec::new();
+
+        for ftm in &model.field_types {
+            if ftypes.insert(ftm.name.clone(), Arc::new(field_type(ftm))).is_some() {
+                diagnostics.push(LayoutDiagnostic::warning("", "",
+                    format!("field type {} is declared more than once, the last declaration is kept", ftm.name)));
+            }
+        }
 
-        //let mut rec_list: Vec<Record> = Vec::new();
         let mut rec_map: HashMap<String, Record<T>> = HashMap::new();
 
-        let mut last_rec_name = String::new();
-
-        // temp variable to get meta values
-        let mut rec_length: usize = 0;
-        let mut version = String::new();
-        let mut description = String::new();
-        let mut schema = String::new();
-        let mut ignore_line = Regex::new("").unwrap();
-        let mut skip_field = String::new();  
-        let mut mapper_pattern = String::new();    
-
-        // loop through elements
-        let parser = EventReader::new(file);
-        for e in parser {
-            match e {
-                Ok(XmlEvent::StartElement { name, attributes, .. }) => {
-                    // fetch attributes as a hash
-                    let attr = as_hash(&attributes);
-
-                    // now depending on XML tag
-                    match name.local_name.as_ref() {
-                        "meta" => {
-                            rec_length = match attr.get("reclength") {
-                                Some(v) => v.parse::<usize>().unwrap(),
-                                None => 0,
-                            };  
-                            version = match attr.get("version") {
-                                Some(v) => v.to_string(),
-                                None => String::from(""),
-                            }; 
-                            description = match attr.get("description") {
-                                Some(v) => v.to_string(),
-                                None => String::from(""),
-                            }; 
-                            schema = match attr.get("schema") {
-                                Some(v) => v.to_string(),
-                                None => String::from(""),
-                            };                                                                                   
-                            ignore_line = match attr.get("ignoreLine") {
-                                Some(v) => Regex::new(&v.to_string()).unwrap(),
-                                None => Regex::new("").unwrap(),
-                            };
-                            skip_field = match attr.get("skipField") {
-                                Some(v) => v.to_string(),
-                                None => String::from(""),
-                            }; 
-                            mapper_pattern = match attr.get("mapper") {
-                                Some(v) => v.to_string(),
-                                None => String::from(""),
-                            };                                                       
-                        }
-                        "fieldtype" => {
-                            // mandatory XML attributes
-                            let ft_name = attr.get("name").unwrap();
-                            let ft_type = attr.get("type").unwrap();   
-
-                            let mut ft =  FieldDataType::new(ft_name, ft_type);                       
-
-                            // optional XML attributes
-                            match attr.get("pattern") {
-                                Some(v) => ft.set_pattern(&v),
-                                None => (),
-                            }; 
-
-                            // finally insert field type
-                            ftypes.insert(
-                                ft_name.to_string(), 
-                                Rc::new(ft)
-                            );                                                       
-                        }                     
-                        "record" => {
-                            let rec_name = attr.get("name").unwrap();                            
-                            let rec_desc = attr.get("description").unwrap();
-                            let rec_length: usize;
-
-                            // save last met Record name to be able to add fields whenever we meet
-                            // a <field> tag
-                            last_rec_name = rec_name.to_string();
-
-                            // length could be present or Not
-                            rec_length = match attr.get("length") {
-                                Some(length) => length.parse::<usize>().unwrap(),
-                                None => 0,
-                            };
-
-                            // add new record
-                            //rec_list.push(Record::new(last_rec_name, rec_type, rec_length))
-                            rec_map.insert(
-                                rec_name.to_string(),
-                                Record::<T>::new(rec_name, rec_desc, rec_length)
-                            );                            
-                        }  
-                        "field" => {
-                            // name and description are mandatory
-                            let f_name = attr.get("name").unwrap();
-                            let f_desc = attr.get("description").unwrap();
-
-                            // so is the field type
-                            let f_type = attr.get("type").unwrap().to_string(); 
-
-                            // try to get already insert field type                                                      
-                            let ft = match ftypes.get(&f_type) {
-                                Some(ft) => ft,
-                                None => panic!("No field type {} found!", f_type),
-                            };
-
-                            // length could be present or Not
-                            let f_length = match attr.get("length") {
-                                Some(length) => length.parse::<usize>().unwrap(),
-                                None => 0,
-                            };
-
-                            // if length is not present, then lower and upper bounds for this field should
-                            // be present
-                            if f_length == 0 {
-                                // get lower offset
-                                let f_lower_offset = match attr.get("start") {
-                                    Some(n) => n.parse::<usize>().unwrap(),
-                                    None => 0,
-                                };  
-                                    
-                                // get upper offset
-                                let f_upper_offset = match attr.get("end") {
-                                    Some(n) => n.parse::<usize>().unwrap(),
-                                    None => 0,
-                                };
-
-                                // add Field into the last created record
-                                rec_map.get_mut(&last_rec_name).unwrap().push(
-                                    Field::from_offset(
-                                        f_name, f_desc, &ft, f_lower_offset, f_upper_offset
-                                    )                               
-                                );                                                  
-                            }
-                            // here, length is not null
-                            else {
-                                // add Field into the last created record
-                                rec_map.get_mut(&last_rec_name).unwrap().push(
-                                    Field::from_length(
-                                        f_name, f_desc, &ft, f_length
-                                    )                               
-                                );
-                            }
-                        }                      
-                        _ => ()
-                    }
-                    //println!("{} {:?}", name, attributes);
-                }
-                Err(e) => {
-                    println!("Error: {}", e);
-                    break;
+        for recm in &model.records {
+            let mut rec = Record::<T>::new(&recm.name, &recm.description, recm.length);
+
+            // fields of the base record come first
+            let base_fields = match model.base_fields(recm) {
+                Ok(fields) => fields,
+                Err(e) => panic!("{}", e),
+            };
+
+            for fm in base_fields.into_iter().chain(&recm.fields) {
+                // field types not declared as such (e.g.: Real(8.3)) are added once found
+                if !ftypes.contains_key(&fm.ftype) {
+                    match model.field_type(&fm.ftype) {
+                        Some(ftm) => ftypes.insert(fm.ftype.clone(), Arc::new(field_type(&ftm))),
+                        None => panic!("No field type {} found!", fm.ftype),
+                    };
                 }
-                _ => {}
+                let ft = &ftypes[&fm.ftype];
+
+                // if length is not present, then lower and upper bounds for this field should be present
+                let field = match (fm.length, fm.start, fm.end) {
+                    (Some(length), _, _) if length > 0 => Field::from_length(&fm.name, &fm.description, ft, length),
+                    (_, Some(start), Some(end)) => Field::from_offset(&fm.name, &fm.description, ft, start, end),
+                    _ => panic!("field {} of record {} has neither a length nor bounds!", fm.name, recm.name),
+                };
+                rec.push(field);
             }
-        }      
 
+            if rec_map.insert(recm.name.clone(), rec).is_some() {
+                diagnostics.push(LayoutDiagnostic::error(&recm.name, "",
+                    "record is declared more than once, only the last declaration is kept".to_string()));
+            }
+        }
+
+        let meta = &model.meta;
         let mut layout = Layout {
-            xml_file: xml_file.to_string(),
-            rec_length: rec_length,    
-            version: version,
-            description: description,
-            schema: schema,
-            ignore_line: ignore_line,
+            xml_file: file.to_string(),
+            rec_length: meta.reclength,
+            version: meta.version.clone(),
+            description: meta.description.clone(),
+            schema: meta.schema.clone(),
+            ignore_line: Regex::new(&meta.ignore_line).unwrap(),
             skip_field: String::new(),
             rec_map: rec_map,
             ftypes: ftypes,
             mapper: RecordMapper::default().hasher,
+            mapper_pattern: String::new(),
+            diagnostics,
         };
 
         // set skip field if any
-        if skip_field != "" {
-            layout.set_skip_field(&skip_field);
+        if !meta.skip_field.is_empty() {
+            layout.set_skip_field(&meta.skip_field);
         }
 
         // set mapper closure if any
-        if mapper_pattern != "" {
-            layout.mapper = RecordMapper::from(mapper_pattern.as_ref()).hasher;
+        if !meta.mapper.is_empty() {
+            layout.mapper = RecordMapper::from(meta.mapper.as_ref()).hasher;
+            layout.mapper_pattern = meta.mapper.clone();
         }                  
 
         layout
@@ -323,8 +253,8 @@ impl<T> Layout<T> {
         self.rec_map.get_mut(rec_name)
     }
 
-    /// Gets a field type Rc.
-    pub fn get_type(&self, ftype_name: &str) -> Option<&Rc<FieldDataType>> {
+    /// Gets a field type Arc.
+    pub fn get_type(&self, ftype_name: &str) -> Option<&Arc<FieldDataType>> {
         self.ftypes.get(ftype_name)
     }  
 
@@ -351,25 +281,115 @@ impl<T> Layout<T> {
 
     }  
 
-    /// Checks whether layout is valid: if `rec_length` is not 0, all records have the same length
-    /// the sum of length all fields (i.e. record length) should match the `rec_length` value.
-    /// If not, each record length should match the declared length
-    pub fn is_valid(&self) -> (bool,&str,usize,usize) {
-        if self.rec_length != 0 {
-            for (_, rec) in &self.rec_map {
-                if self.rec_length != rec.calculated_length {
-                    return (false, "", self.rec_length, rec.calculated_length)
+    /// Checks the layout, and returns all problems found, sorted by record name:
+    ///
+    /// * record lengths: if `rec_length` is not 0, all records should have this length, otherwise each
+    ///   record length should match its declared length
+    /// * overlapping fields, and positions not covered by any field
+    /// * zero-width fields
+    /// * records declared more than once, of which only the last one is kept
+    /// * fields using a field type not declared in the layout
+    /// * mapper ranges outside of records
+    ///
+    /// # Example
+    /// ```rust
+    /// use std::sync::Arc;
+    /// use rbf::record::AsciiMode;
+    /// use rbf::layout::Layout;
+    /// use rbf::field::Field;
+    ///
+    /// let mut layout = Layout::<AsciiMode>::new("./tests/test.xml");
+    /// assert!(layout.validate().is_empty());
+    ///
+    /// let ft = Arc::clone(layout.get_type("N").unwrap());
+    /// layout.get_mut("NB").unwrap().push(Field::from_offset("X", "Overlapping field", &ft, 45, 50));
+    ///
+    /// let diagnostics = layout.validate();
+    /// assert_eq!(diagnostics[0].to_string(), "error: record NB: fields add up to 50 instead of the declared length 47");
+    /// assert_eq!(diagnostics[1].to_string(), "error: record NB, field X: overlaps field N9 (positions 39 to 47)");
+    /// ```
+    pub fn validate(&self) -> Vec<LayoutDiagnostic> {
+        let mut diagnostics = self.diagnostics.clone();
+        let ranges = RecordMapper::ranges(&self.mapper_pattern);
+
+        let mut names: Vec<_> = self.rec_map.keys().collect();
+        names.sort();
+
+        for name in names {
+            let rec = &self.rec_map[name];
+
+            // record length
+            if self.rec_length != 0 {
+                if rec.calculated_length != self.rec_length {
+                    diagnostics.push(LayoutDiagnostic::error(name, "",
+                        format!("fields add up to {} instead of the layout record length {}", rec.calculated_length, self.rec_length)));
                 }
             }
-        }
-        else {
-            for (_, rec) in &self.rec_map {
-                if rec.declared_length != rec.calculated_length {
-                    return (false, &rec.name, rec.declared_length, rec.calculated_length)
+            else if rec.declared_length == 0 {
+                diagnostics.push(LayoutDiagnostic::warning(name, "",
+                    format!("no declared length, fields add up to {}", rec.calculated_length)));
+            }
+            else if rec.calculated_length != rec.declared_length {
+                diagnostics.push(LayoutDiagnostic::error(name, "",
+                    format!("fields add up to {} instead of the declared length {}", rec.calculated_length, rec.declared_length)));
+            }
+
+            // fields, by position
+            let mut fields: Vec<_> = rec.flist.iter().collect();
+            fields.sort_by_key(|f| f.lower_offset);
+
+            let mut position = 0;
+            let mut last: Option<&Field> = None;
+            for f in fields {
+                if !self.ftypes.contains_key(&f.ftype.id) {
+                    diagnostics.push(LayoutDiagnostic::error(name, &f.name, format!("field type {} is not declared", f.ftype.id)));
+                }
+                if f.length == 0 {
+                    diagnostics.push(LayoutDiagnostic::error(name, &f.name, "zero-width field".to_string()));
+                    continue;
+                }
+
+                match last {
+                    Some(other) if f.lower_offset < position => diagnostics.push(LayoutDiagnostic::error(name, &f.name,
+                        format!("overlaps field {} (positions {} to {})", other.name, other.lower_offset + 1, other.upper_offset + 1))),
+                    _ if f.lower_offset > position => diagnostics.push(LayoutDiagnostic::warning(name, &f.name,
+                        format!("positions {} to {} before this field are not covered by any field", position + 1, f.lower_offset))),
+                    _ => (),
+                }
+
+                if f.upper_offset + 1 > position {
+                    position = f.upper_offset + 1;
+                    last = Some(f);
+                }
+            }
+
+            // record ID is built from those ranges
+            for range in &ranges {
+                if range.end > rec.calculated_length {
+                    diagnostics.push(LayoutDiagnostic::error(name, "",
+                        format!("mapper range {}..{} is outside of the record (length {})", range.start, range.end, rec.calculated_length)));
                 }
-            }            
+            }
+        }
+
+        diagnostics
+    }
+
+    /// Checks whether layout is valid, i.e. **validate()** finds no error. When not valid, returns the record
+    /// name, the expected length and the calculated length of the first record in error (the record name
+    /// is empty if `rec_length` is not 0).
+    #[deprecated(note = "use validate(), which reports all problems")]
+    pub fn is_valid(&self) -> (bool, &str, usize, usize) {
+        let diagnostic = match self.validate().into_iter().find(|d| d.is_error()) {
+            Some(diagnostic) => diagnostic,
+            None => return (true, "", 0, 0),
+        };
+
+        match self.rec_map.get(&diagnostic.record) {
+            Some(rec) if self.rec_length != 0 => (false, "", self.rec_length, rec.calculated_length),
+            Some(rec) => (false, &rec.name, rec.declared_length, rec.calculated_length),
+            None => (false, "", 0, 0),
         }
-        (true, "", 0, 0)
     }
 
     /// Sets skip field.
@@ -380,6 +400,102 @@ impl<T> Layout<T> {
         // remove field names
         self.remove(into_field_list(skip_field));
     }
+
+    /// Writes the layout as an XML layout file, which can be loaded back with **Layout::new()**. Field types
+    /// and records are sorted by name. Fields are written with their length or their bounds, depending on
+    /// how they were created, except fields following removed ones which are always written with their
+    /// bounds.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rbf::record::AsciiMode;
+    /// use rbf::layout::Layout;
+    ///
+    /// let mut layout = Layout::<AsciiMode>::new("./tests/test.xml");
+    /// layout.remove(vec!["W26", "N9", "G24"]);
+    ///
+    /// let mut xml = Vec::new();
+    /// layout.to_xml(&mut xml).unwrap();
+    ///
+    /// let xml = String::from_utf8(xml).unwrap();
+    /// assert!(xml.contains("<record name=\"NB\" description=\"Second record: numbers\" length=\"47\">"));
+    /// assert!(!xml.contains("\"N9\""));
+    /// ```
+    pub fn to_xml<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        format::xml::write(&self.to_model(), writer)
+    }
+
+    /// Converts the layout into a format-neutral model, which can be written in any layout format. Field types
+    /// and records are sorted by name, and fields are given as for **to_xml()**. Records extending another record
+    /// are given with all their fields.
+    pub fn to_model(&self) -> LayoutModel {
+        let meta = MetaModel {
+            version: self.version.clone(),
+            description: self.description.clone(),
+            schema: self.schema.clone(),
+            reclength: self.rec_length,
+            ignore_line: self.ignore_line.as_str().to_string(),
+            skip_field: self.skip_field.clone(),
+            mapper: self.mapper_pattern.clone(),
+        };
+
+        let mut ftypes: Vec<_> = self.ftypes.values().collect();
+        ftypes.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let field_types = ftypes.iter().map(|ft| FieldTypeModel {
+            name: ft.id.clone(),
+            base_type: ft.base_data_type.type_name().to_string(),
+            pattern: ft.pattern.as_str().to_string(),
+            scale: ft.scale,
+            format: match ft.base_data_type {
+                BaseDataType::Date{ ref date_format } => date_format.clone(),
+                BaseDataType::Time{ ref time_format } => time_format.clone(),
+                _ => String::new(),
+            },
+            // defaults are left out
+            trim: ft.trim.map_or(String::new(), |trim| trim.name().to_string()),
+            pad_char: if ft.pad_char == ' ' { String::new() } else { ft.pad_char.to_string() },
+            justify: if ft.justify == Justify::Left { String::new() } else { ft.justify.name().to_string() },
+            null: ft.nulls.iter().map(|n| n.name()).collect::<Vec<_>>().join(","),
+        }).collect();
+
+        let mut names: Vec<_> = self.rec_map.keys().collect();
+        names.sort();
+
+        let records = names.iter().map(|name| {
+            let rec = &self.rec_map[*name];
+
+            // fields removed from a record leave a gap: the next field is then given by its bounds to keep
+            // its position
+            let mut position = 0;
+            let fields = rec.into_iter().map(|f| {
+                let by_length = match f.creation_type {
+                    FieldCreationType::ByLength => f.lower_offset == position,
+                    _ => false,
+                };
+                position = f.upper_offset + 1;
+
+                FieldModel {
+                    name: f.name.clone(),
+                    description: f.description.clone(),
+                    ftype: f.ftype.id.clone(),
+                    length: if by_length { Some(f.length) } else { None },
+                    start: if by_length { None } else { Some(f.lower_offset + 1) },
+                    end: if by_length { None } else { Some(f.upper_offset + 1) },
+                }
+            }).collect();
+
+            RecordModel {
+                name: rec.name.clone(),
+                description: rec.description.clone(),
+                length: rec.declared_length,
+                extends: String::new(),
+                fields,
+            }
+        }).collect();
+
+        LayoutModel { includes: Vec::new(), meta, field_types, records }
+    }
            
 
 }
@@ -417,7 +533,7 @@ mod tests {
         let layout = ::layout::setup::layout_load_layout_ascii();
 
         // is it a valid XML layout ?
-        assert!(layout.is_valid().0);
+        assert!(layout.validate().is_empty());
 
         // check layout methods
         assert_eq!(layout.contains_record("LL"), true);
@@ -450,6 +566,20 @@ mod tests {
         }        
     }
 
+    #[test]
+    #[allow(deprecated)]
+    fn layout_is_valid() {
+        use std::sync::Arc;
+        use field::Field;
+
+        let mut layout = ::layout::setup::layout_load_layout_ascii();
+        assert_eq!(layout.is_valid(), (true, "", 0, 0));
+
+        let ft = Arc::clone(layout.get_type("N").unwrap());
+        layout.get_mut("NB").unwrap().push(Field::from_length("X", "Extra field", &ft, 3));
+        assert_eq!(layout.is_valid(), (false, "NB", 47, 50));
+    }
+
     #[test]
     fn layout_remove() {
         // load our layout
@@ -499,6 +629,85 @@ mod tests {
         assert_eq!(layout.get("NB").unwrap().count(), 8);
         assert_eq!(layout.get("GL").unwrap().count(), 23);           
     } 
+
+    #[test]
+    fn layout_to_xml() {
+        use std::fs;
+        use std::sync::Arc;
+        use field::Field;
+        use layout::Layout;
+        use record::AsciiMode;
+
+        // change the layout before saving it
+        let mut layout = ::layout::setup::layout_load_layout_ascii();
+        layout.remove(vec!("W10", "N5"));
+        layout.mapper_pattern = "type:1 map:0..2".to_string();
+        let ft = Arc::clone(layout.get_type("N").unwrap());
+        layout.get_mut("DP").unwrap().push(Field::from_offset("X", "Offset field", &ft, 23, 25));
+
+        let xml_file = ::util::setup::TempPath::new("layout_to_xml.xml");
+        let mut xml = Vec::new();
+        layout.to_xml(&mut xml).unwrap();
+        fs::write(xml_file.path(), &xml).unwrap();
+
+        let other = Layout::<AsciiMode>::new(xml_file.as_str());
+
+        assert_eq!(other.version, layout.version);
+        assert_eq!(other.description, layout.description);
+        assert_eq!(other.ignore_line.as_str(), "^A");
+        assert_eq!(other.mapper_pattern, "type:1 map:0..2");
+        assert_eq!((other.mapper)("NB123"), "NB");
+
+        assert_eq!(other.ftypes.len(), layout.ftypes.len());
+        for (id, ft) in &layout.ftypes {
+            let other_ft = other.get_type(id).unwrap();
+            assert_eq!(other_ft.base_data_type, ft.base_data_type);
+            assert_eq!(other_ft.pattern.as_str(), ft.pattern.as_str());
+            assert_eq!(other_ft.scale, ft.scale);
+        }
+
+        assert_eq!(other.len(), layout.len());
+        for (name, rec) in &layout {
+            let other_rec = other.get(name).unwrap();
+            assert_eq!(other_rec.description, rec.description);
+            assert_eq!(other_rec.declared_length, rec.declared_length);
+            assert_eq!(other_rec.calculated_length, rec.calculated_length);
+            assert_eq!(other_rec.count(), rec.count());
+
+            for (f, other_f) in rec.flist.iter().zip(&other_rec.flist) {
+                assert_eq!((&other_f.name, &other_f.description, &other_f.ftype.id), (&f.name, &f.description, &f.ftype.id));
+                assert_eq!((other_f.length, other_f.lower_offset, other_f.upper_offset), (f.length, f.lower_offset, f.upper_offset));
+                assert_eq!(other_f.multiplicity, f.multiplicity);
+            }
+        }
+
+        // fields created by offset, or following a removed field, are written with their bounds
+        let xml = String::from_utf8(xml).unwrap();
+        assert!(xml.contains("<field name=\"W11\" description=\"Word 11\" start=\"58\" end=\"68\" type=\"A\"/>"));
+        assert!(xml.contains("<field name=\"W12\" description=\"Word 12\" length=\"12\" type=\"A\"/>"));
+        assert!(xml.contains("<field name=\"X\" description=\"Offset field\" start=\"23\" end=\"25\" type=\"N\"/>"));
+    }
+
+    #[test]
+    fn layout_send_sync() {
+        use std::thread;
+        use layout::Layout;
+        use record::{AsciiMode, UTF8Mode};
+
+        fn send_sync<T: Send + Sync>() {}
+        send_sync::<Layout<AsciiMode>>();
+        send_sync::<Layout<UTF8Mode>>();
+
+        // the layout mapper (the identity here) can be called from other threads
+        let layout = ::layout::setup::layout_load_layout_ascii();
+        let mapper = &layout.mapper;
+        let ids: Vec<_> = thread::scope(|s| {
+            let handles: Vec<_> = ["LL01", "NB02"].iter().map(|line| s.spawn(move || mapper(line))).collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(ids, vec!["LL01", "NB02"]);
+    }
+
 /*
     #[test]
     fn layout_retain() {
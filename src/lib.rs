@@ -132,12 +132,42 @@
 //! ```
 extern crate xml;
 extern crate regex;
+#[cfg(feature = "json")]
+extern crate serde_json;
+#[cfg(feature = "arrow")]
+extern crate arrow;
+#[cfg(feature = "arrow")]
+extern crate parquet;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
+#[cfg(feature = "mmap")]
+extern crate memmap2;
+#[cfg(feature = "mmap")]
+extern crate memchr;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "yaml")]
+extern crate serde_yaml;
+#[cfg(feature = "toml")]
+extern crate toml;
 
 pub mod fieldtype;
 pub mod field;
 #[macro_use]
 pub mod record;
 pub mod layout;
+pub mod format;
+pub mod builder;
+pub mod diagnostic;
+pub mod diff;
 pub mod reader;
+pub mod parallel;
+pub mod index;
+pub mod progress;
+pub mod profile;
+pub mod infer;
 pub mod util;
-pub mod mapper;
\ No newline at end of file
+pub mod mapper;
+pub mod value;
+pub mod query;
+pub mod export;
\ No newline at end of file
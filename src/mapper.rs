@@ -1,10 +1,16 @@
+use std::ops::Range;
+
 use regex::Regex; 
 
 /// Convenient conversion from a string ref.
-pub type RecordHasher = Box<Fn(&str) -> String>;
+pub type RecordHasher = Box<dyn Fn(&str) -> String>;
+
+/// Same as **RecordHasher**, but which can be shared between threads. Used by **Layout**, so that a layout
+/// can be read by several threads (see **ParallelReader**).
+pub type SyncRecordHasher = Box<dyn Fn(&str) -> String + Send + Sync>;
 
 pub struct RecordMapper {
-    pub hasher: RecordHasher,
+    pub hasher: SyncRecordHasher,
 }
 
 /// Default closure is the identity function
@@ -34,36 +40,56 @@ impl Default for RecordMapper {
 /// let m3 = RecordMapper::from("type:2 map:0..2,4..6");
 /// assert_eq!((m3.hasher)(s), "0102");
 /// ```
-#[allow(unused_variables)]
 impl<'a> From<&'a str> for RecordMapper {
     fn from(original: &'a str) -> RecordMapper {
+        match RecordMapper::parse(original) {
+            Ok(mapper) => mapper,
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+impl RecordMapper {
+    /// Builds the closure from a mapper pattern, returning an error if the pattern is not valid.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rbf::mapper::RecordMapper;
+    ///
+    /// assert!(RecordMapper::parse("type:1 map:0..2").is_ok());
+    /// assert!(RecordMapper::parse("type:1 map:0..").is_err());
+    /// ```
+    #[allow(unused_variables)]
+    pub fn parse(original: &str) -> Result<RecordMapper, String> {
+        let unknown = || format!("Unknown mapper pattern {}", original);
+
         let mapper_reg = Regex::new(r"^type:(?P<h_type>\d)\s+map:\s*(?P<h_value>[\w\.,]+)\s*$").unwrap();
-        let caps = mapper_reg.captures(original).unwrap();
+        let caps = mapper_reg.captures(original).ok_or_else(unknown)?;
 
         match caps["h_type"].parse::<usize>().unwrap() {
             0 => {
                 // in this case, closure is just returning a constant string
                 let constant = caps["h_value"].to_string();
 
-                RecordMapper{ 
+                Ok(RecordMapper{ 
                     hasher: Box::new(move |x: &str| constant.clone()),
-                }
+                })
             },
             1 => {
                 let range_reg = Regex::new(r"(?P<r_inf>\d+)\.\.(?P<r_sup>\d+)").unwrap();
-                let caps_range = range_reg.captures(&caps["h_value"]).unwrap();
+                let caps_range = range_reg.captures(&caps["h_value"]).ok_or_else(unknown)?;
                 let range = (
                     caps_range["r_inf"].parse::<usize>().unwrap(),
                     caps_range["r_sup"].parse::<usize>().unwrap(),
                 );
 
-                RecordMapper{ 
+                Ok(RecordMapper{ 
                     hasher: Box::new(move |x: &str| x[range.0 .. range.1].to_string()),
-                }                
+                })
             },
             2 => {
                 let dual_range_reg = Regex::new(r"(?P<r1_inf>\d+)\.\.(?P<r1_sup>\d+)\s*,\s*(?P<r2_inf>\d+)\.\.(?P<r2_sup>\d+)").unwrap();
-                let caps_dual_range = dual_range_reg.captures(&caps["h_value"]).unwrap();
+                let caps_dual_range = dual_range_reg.captures(&caps["h_value"]).ok_or_else(unknown)?;
                 let dual_range = (
                     caps_dual_range["r1_inf"].parse::<usize>().unwrap(),
                     caps_dual_range["r1_sup"].parse::<usize>().unwrap(),
@@ -71,7 +97,7 @@ impl<'a> From<&'a str> for RecordMapper {
                     caps_dual_range["r2_sup"].parse::<usize>().unwrap(),
                 );
 
-                RecordMapper{ 
+                Ok(RecordMapper{ 
                     hasher: Box::new(move |x: &str|
                         { 
                             let mut s = String::with_capacity(20);
@@ -80,10 +106,31 @@ impl<'a> From<&'a str> for RecordMapper {
                             s
                         }
                     )
-                }                
+                })
             }
-            _ => panic!("Unknown mapper pattern {}", original),
+            _ => Err(unknown()),
+        }
+    }
+
+    /// Returns the ranges of a line used to build the record ID with a mapper pattern: none for a constant
+    /// record ID (type 0) or an invalid pattern.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rbf::mapper::RecordMapper;
+    ///
+    /// assert_eq!(RecordMapper::ranges("type:2 map:0..2,4..6"), vec![0..2, 4..6]);
+    /// assert!(RecordMapper::ranges("type:0 map:ID").is_empty());
+    /// ```
+    pub fn ranges(pattern: &str) -> Vec<Range<usize>> {
+        if RecordMapper::parse(pattern).is_err() || pattern.starts_with("type:0") {
+            return Vec::new();
         }
+
+        let range_reg = Regex::new(r"(?P<r_inf>\d+)\.\.(?P<r_sup>\d+)").unwrap();
+        range_reg.captures_iter(pattern)
+            .map(|caps| caps["r_inf"].parse::<usize>().unwrap()..caps["r_sup"].parse::<usize>().unwrap())
+            .collect()
     }
 }
 
@@ -109,6 +156,22 @@ mod tests {
         assert_eq!((m3.hasher)(s), "0102");
     }  
 
+    #[test]
+    fn mapper_with_state() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+        use mapper::RecordHasher;
+
+        // a hasher can keep state which can't be shared between threads
+        let nb_lines = Rc::new(RefCell::new(0));
+        let counter = nb_lines.clone();
+        let hasher: RecordHasher = Box::new(move |x: &str| { *counter.borrow_mut() += 1; x[0..2].to_string() });
+
+        assert_eq!(hasher("01XX"), "01");
+        assert_eq!(hasher("02XX"), "02");
+        assert_eq!(*nb_lines.borrow(), 2);
+    }
+
     #[test]
     #[should_panic]
     #[allow(unused_variables)]    
@@ -0,0 +1,336 @@
+//! Reads a record-based file using several threads. The file is split into chunks of whole lines, which
+//! are parsed in parallel. Each worker thread owns a copy of the layout records, so the layout and its mapper
+//! are only shared read-only between threads.
+//!
+//! Results are either handed to a closure called for each chunk (in no particular order), or collected
+//! and given back in the original line order. In both cases, line numbers are absolute line numbers
+//! within the file.
+//!
+//! # Examples
+//! ```rust
+//! use rbf::record::AsciiMode;
+//! use rbf::layout::Layout;
+//! use rbf::parallel::ParallelReader;
+//!
+//! let mut layout = Layout::<AsciiMode>::new("./tests/test.xml");
+//! layout.mapper = Box::new(|x: &str| x[0..2].to_string());
+//!
+//! let mut reader = ParallelReader::<AsciiMode>::new("./tests/test_ascii.data", layout);
+//! reader.set_chunk_size(4096);
+//!
+//! // N3 values of NB records, in line order
+//! let mut values = Vec::new();
+//! reader.map_ordered(
+//!     |_, rec| if rec.name == "NB" { Some(rec.view().get_value("N3").to_string()) } else { None },
+//!     |line_number, value| values.push((line_number, value))
+//! );
+//!
+//! assert_eq!(values.len(), 247);
+//! assert!(values.windows(2).all(|w| w[0].0 < w[1].0));
+//! ```
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::thread;
+
+use record::{ReadMode, Record};
+use layout::Layout;
+use mapper::SyncRecordHasher;
+use reader::ReaderLazyness;
+use util::into_string;
+
+/// Default chunk size in bytes
+const DEFAULT_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+// a chunk of whole lines read from the file, sent to worker threads
+struct RawChunk {
+    index: usize,
+    first_line: u64,
+    offset: u64,
+    data: String,
+}
+
+/// A chunk of the file being parsed by a worker thread.
+pub struct Chunk<'a, T: 'a> {
+    /// chunk number, starting from 0
+    pub index: usize,
+    /// absolute number of the first line of the chunk (starting from 1)
+    pub first_line: u64,
+    /// byte offset of the chunk within the file
+    pub offset: u64,
+    /// lines of the chunk
+    data: &'a str,
+    /// byte position of the next line to read within the chunk
+    pos: usize,
+    /// absolute number of the last line read
+    line_number: u64,
+    /// records of the worker thread, set for each line read
+    records: &'a mut HashMap<String, Record<T>>,
+    mapper: &'a SyncRecordHasher,
+    lazyness: &'a ReaderLazyness,
+}
+
+impl<'a, T> Chunk<'a, T> {
+    /// Returns the next record of the chunk together with its absolute line number, or **None** at the
+    /// end of the chunk. Field values are not copied (see **Record::parse()**), and should be accessed with
+    /// **Record::view()** or **Record::field_values()**.
+    ///
+    /// # Panics
+    /// If the record ID is not found in the layout and lazyness is stringent.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<(u64, &Record<T>)>
+        where Record<T>: ReadMode
+    {
+        let rec_id = loop {
+            if self.pos >= self.data.len() {
+                return None;
+            }
+
+            // lines are kept with their newline, as with Reader
+            let end = match self.data[self.pos..].find('\n') {
+                Some(i) => self.pos + i + 1,
+                None => self.data.len(),
+            };
+            let line = &self.data[self.pos..end];
+            self.pos = end;
+            self.line_number += 1;
+
+            let rec_id = (self.mapper)(line);
+            match self.records.get_mut(&rec_id) {
+                Some(rec) => rec.parse(line),
+                None => if *self.lazyness == ReaderLazyness::Stringent {
+                    panic!("couldn't find record ID {} at line {}", rec_id, self.line_number);
+                }
+                else {
+                    continue;
+                },
+            }
+            break rec_id;
+        };
+
+        Some((self.line_number, &self.records[&rec_id]))
+    }
+}
+
+pub struct ParallelReader<T> {
+    /// record-based file to read
+    pub rbf_file: String,
+    /// layout struct describing the file to read, shared by all threads. Its mapper maps each line to a
+    /// record name
+    pub layout: Layout<T>,
+    /// lazyness when reading
+    pub lazyness: ReaderLazyness,
+    /// number of worker threads
+    pub nb_threads: usize,
+    /// approximate size in bytes of the chunks sent to worker threads
+    pub chunk_size: usize,
+}
+
+impl<T> ParallelReader<T>
+    where Record<T>: ReadMode, T: Sync
+{
+    /// Creates a new parallel reader, using as many threads as available CPUs.
+    ///
+    /// # Arguments
+    ///
+    /// * `rbf_file` - name and path of the record-based file to read
+    /// * `layout`: Layout struct previously created from the XML layout file describing the data file. Its
+    ///   mapper is used to map each line to a record name
+    pub fn new(rbf_file: &str, layout: Layout<T>) -> ParallelReader<T> {
+        ParallelReader {
+            rbf_file: rbf_file.to_string(),
+            layout,
+            lazyness: ReaderLazyness::Lazy,
+            nb_threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+
+    /// Sets reader lazyness
+    pub fn set_lazyness(&mut self, lazyness: ReaderLazyness) {
+        self.lazyness = lazyness;
+    }
+
+    /// Sets the number of worker threads (at least 1).
+    pub fn set_threads(&mut self, nb_threads: usize) {
+        self.nb_threads = nb_threads.max(1);
+    }
+
+    /// Sets the approximate size in bytes of the chunks. Chunks are extended to end on a line boundary.
+    pub fn set_chunk_size(&mut self, chunk_size: usize) {
+        self.chunk_size = chunk_size.max(1);
+    }
+
+    /// Calls `f` for each chunk of the file on a worker thread. Chunks are processed concurrently, so in
+    /// no particular order.
+    ///
+    /// # Example
+    /// ```rust
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use rbf::record::AsciiMode;
+    /// use rbf::layout::Layout;
+    /// use rbf::parallel::ParallelReader;
+    ///
+    /// let mut layout = Layout::<AsciiMode>::new("./tests/test.xml");
+    /// layout.mapper = Box::new(|x: &str| x[0..2].to_string());
+    /// let reader = ParallelReader::<AsciiMode>::new("./tests/test_ascii.data", layout);
+    ///
+    /// let nb_records = AtomicUsize::new(0);
+    /// reader.for_each_chunk(|chunk| {
+    ///     while let Some((_, rec)) = chunk.next() {
+    ///         if rec.name == "LL" {
+    ///             nb_records.fetch_add(1, Ordering::Relaxed);
+    ///         }
+    ///     }
+    /// });
+    /// assert_eq!(nb_records.into_inner(), 257);
+    /// ```
+    ///
+    /// # Panics
+    /// If `rbf_file` could not be read, or if `f` panics.
+    pub fn for_each_chunk<F>(&self, f: F)
+        where F: Fn(&mut Chunk<T>) + Sync
+    {
+        self.run(|chunk, _: &Sender<()>| f(chunk), |_| ());
+    }
+
+    /// Calls `map` for each record on worker threads, and then `consume` on the calling thread for each
+    /// value returned by `map`, following the line order of the file. Both closures are given the
+    /// absolute line number of the record.
+    ///
+    /// # Panics
+    /// If `rbf_file` could not be read, or if one of the closures panics.
+    pub fn map_ordered<R, M, C>(&self, map: M, mut consume: C)
+        where M: Fn(u64, &Record<T>) -> Option<R> + Sync, R: Send, C: FnMut(u64, R)
+    {
+        let work = |chunk: &mut Chunk<T>, tx: &Sender<(usize, Vec<(u64, R)>)>| {
+            let mut values = Vec::new();
+            while let Some((line_number, rec)) = chunk.next() {
+                if let Some(value) = map(line_number, rec) {
+                    values.push((line_number, value));
+                }
+            }
+            let _ = tx.send((chunk.index, values));
+        };
+
+        self.run(work, |rx| {
+            // chunks might be received in any order: keep them until their turn comes
+            let mut pending = BTreeMap::new();
+            let mut next_index = 0;
+
+            for (index, values) in rx {
+                pending.insert(index, values);
+                while let Some(values) = pending.remove(&next_index) {
+                    for (line_number, value) in values {
+                        consume(line_number, value);
+                    }
+                    next_index += 1;
+                }
+            }
+        });
+    }
+
+    // reads the file in one thread, parses chunks in worker threads which send results of type M,
+    // received by `consume` on the calling thread
+    fn run<W, M, C>(&self, work: W, consume: C)
+        where W: Fn(&mut Chunk<T>, &Sender<M>) + Sync, M: Send, C: FnOnce(Receiver<M>)
+    {
+        let file = match File::open(&self.rbf_file) {
+            Ok(f) => f,
+            Err(why) => panic!("couldn't open {}: {}", self.rbf_file, why),
+        };
+
+        // a few chunks are read ahead for each worker
+        let (chunk_tx, chunk_rx) = mpsc::sync_channel(2 * self.nb_threads);
+        let chunk_rx = Arc::new(Mutex::new(chunk_rx));
+        let (result_tx, result_rx) = mpsc::channel();
+        let work = &work;
+
+        let (rbf_file, chunk_size) = (self.rbf_file.as_str(), self.chunk_size);
+        let (layout, lazyness) = (&self.layout, &self.lazyness);
+
+        thread::scope(|s| {
+            s.spawn(move || split(rbf_file, chunk_size, file, chunk_tx));
+
+            for _ in 0..self.nb_threads {
+                let chunk_rx = chunk_rx.clone();
+                let result_tx = result_tx.clone();
+
+                s.spawn(move || {
+                    let mut records = layout.rec_map.clone();
+
+                    loop {
+                        let raw = match chunk_rx.lock().unwrap().recv() {
+                            Ok(raw) => raw,
+                            Err(_) => break,
+                        };
+
+                        let mut chunk = Chunk {
+                            index: raw.index,
+                            first_line: raw.first_line,
+                            offset: raw.offset,
+                            data: &raw.data,
+                            pos: 0,
+                            line_number: raw.first_line - 1,
+                            records: &mut records,
+                            mapper: &layout.mapper,
+                            lazyness,
+                        };
+                        work(&mut chunk, &result_tx);
+                    }
+                });
+            }
+
+            // only worker threads hold the channels now: when they all end, reading stops and results
+            // are all received
+            drop(chunk_rx);
+            drop(result_tx);
+            consume(result_rx);
+        });
+    }
+}
+
+// splits the file into chunks of whole lines, numbering lines on the fly
+fn split(rbf_file: &str, chunk_size: usize, file: File, tx: SyncSender<RawChunk>) {
+    let mut bufreader = BufReader::new(file);
+    let mut index = 0;
+    let mut first_line = 1;
+    let mut offset = 0;
+
+    loop {
+        let mut buf = Vec::with_capacity(chunk_size + 1024);
+        let read = (&mut bufreader).take(chunk_size as u64).read_to_end(&mut buf)
+            .and_then(|_| if buf.last().is_some_and(|&b| b != b'\n') {
+                bufreader.read_until(b'\n', &mut buf)
+            } else {
+                Ok(0)
+            });
+        if let Err(why) = read {
+            panic!("error {} when reading file {}", why, rbf_file);
+        }
+        if buf.is_empty() {
+            break;
+        }
+
+        // last line might have no newline
+        let mut nb_lines = buf.iter().filter(|&&b| b == b'\n').count() as u64;
+        if buf.last() != Some(&b'\n') {
+            nb_lines += 1;
+        }
+
+        // invalid UTF-8 (e.g. HIGH-VALUES) is replaced, as with Reader
+        let length = buf.len() as u64;
+        let data = into_string(buf);
+
+        // no more worker thread
+        if tx.send(RawChunk { index, first_line, offset, data }).is_err() {
+            break;
+        }
+
+        index += 1;
+        first_line += nb_lines;
+        offset += length;
+    }
+}
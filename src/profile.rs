@@ -0,0 +1,380 @@
+//! Data profiling of a record-based file: for each record type, the number of records read, and for each
+//! field its fill rate, an estimate of its number of distinct values, the minimum and maximum length of
+//! its blank-stripped values, its most frequent values, and how many values match the field type pattern
+//! or can be converted according to the field type.
+//!
+//! A profile is built by adding records one at a time, usually from a [Reader](../reader/struct.Reader.html),
+//! and is then rendered as a text table, as HTML (using the same Bootstrap classes as
+//! **Record::to_html()**) or as JSON (with the `json` feature).
+//!
+//! # Examples
+//! ```rust
+//! use rbf::record::AsciiMode;
+//! use rbf::layout::Layout;
+//! use rbf::reader::Reader;
+//! use rbf::profile::Profile;
+//!
+//! let layout = Layout::<AsciiMode>::new("./tests/test.xml");
+//! let mapper = Box::new(|x: &str| x[0..2].to_string());
+//! let mut reader = Reader::<AsciiMode>::new("./tests/test_ascii.data", layout, mapper);
+//!
+//! let profile = Profile::from_reader(&mut reader, 5);
+//! let nb = &profile.records["NB"];
+//! assert_eq!(nb.count, 247);
+//!
+//! // N3 is always 333
+//! let n3 = nb.fields.iter().find(|f| f.name == "N3").unwrap();
+//! assert_eq!(n3.fill_rate(), 1.0);
+//! assert_eq!(n3.distinct(), 1);
+//! assert_eq!(n3.top_values(), vec![("333", 247)]);
+//! ```
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+
+use record::{ReadMode, Record};
+use reader::Reader;
+
+/// Number of hashes kept to estimate the number of distinct values. Counts are exact below this number.
+const DISTINCT_SKETCH_SIZE: usize = 1024;
+
+/// Maximum number of different values counted per field to get the most frequent ones. Once reached, only
+/// values already seen are counted.
+const MAX_TRACKED_VALUES: usize = 10_000;
+
+/// Profile of a single field of a record.
+#[derive(Debug, Clone)]
+pub struct FieldProfile {
+    /// field index within the record
+    pub index: usize,
+    /// field name
+    pub name: String,
+    /// field type ID
+    pub type_id: String,
+    /// number of values
+    pub count: u64,
+    /// number of non-blank values
+    pub filled: u64,
+    /// minimum length in chars of non-blank values (blank-stripped)
+    pub min_length: Option<usize>,
+    /// maximum length in chars of non-blank values (blank-stripped)
+    pub max_length: Option<usize>,
+    /// number of values matching the field type pattern
+    pub matched: u64,
+    /// number of non-blank values converted according to the field type
+    pub converted: u64,
+    /// smallest hashes of values, to estimate the number of distinct values
+    hashes: BTreeSet<u64>,
+    /// occurrences of each value, up to MAX_TRACKED_VALUES values
+    values: HashMap<String, u64>,
+    /// number of most frequent values kept
+    top_n: usize,
+}
+
+impl FieldProfile {
+    fn new(index: usize, name: &str, type_id: &str, top_n: usize) -> FieldProfile {
+        FieldProfile {
+            index,
+            name: name.to_string(),
+            type_id: type_id.to_string(),
+            count: 0,
+            filled: 0,
+            min_length: None,
+            max_length: None,
+            matched: 0,
+            converted: 0,
+            hashes: BTreeSet::new(),
+            values: HashMap::new(),
+            top_n,
+        }
+    }
+
+    // adds a blank-stripped value, telling whether its raw value matches the pattern and whether it's
+    // converted according to the field type
+    fn add(&mut self, value: &str, is_match: bool, is_converted: bool) {
+        self.count += 1;
+        if is_match {
+            self.matched += 1;
+        }
+
+        if value.is_empty() {
+            return;
+        }
+        self.filled += 1;
+        if is_converted {
+            self.converted += 1;
+        }
+
+        let length = value.chars().count();
+        self.min_length = Some(self.min_length.map_or(length, |l| l.min(length)));
+        self.max_length = Some(self.max_length.map_or(length, |l| l.max(length)));
+
+        // K minimum values sketch: only keep the smallest hashes
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+        if self.hashes.len() < DISTINCT_SKETCH_SIZE {
+            self.hashes.insert(hash);
+        }
+        else if hash < *self.hashes.iter().next_back().unwrap() && self.hashes.insert(hash) {
+            self.hashes.pop_last();
+        }
+
+        match self.values.get_mut(value) {
+            Some(n) => *n += 1,
+            None => if self.values.len() < MAX_TRACKED_VALUES {
+                self.values.insert(value.to_string(), 1);
+            },
+        }
+    }
+
+    /// Returns the ratio of non-blank values (between 0 and 1).
+    pub fn fill_rate(&self) -> f64 {
+        ratio(self.filled, self.count)
+    }
+
+    /// Returns the ratio of values matching the field type pattern (between 0 and 1).
+    pub fn match_rate(&self) -> f64 {
+        ratio(self.matched, self.count)
+    }
+
+    /// Returns the ratio of non-blank values successfully converted according to the field type (between
+    /// 0 and 1).
+    pub fn conversion_rate(&self) -> f64 {
+        ratio(self.converted, self.filled)
+    }
+
+    /// Returns the number of distinct non-blank values. It's exact up to 1024 distinct values, and
+    /// estimated beyond.
+    pub fn distinct(&self) -> u64 {
+        if self.hashes.len() < DISTINCT_SKETCH_SIZE {
+            return self.hashes.len() as u64;
+        }
+
+        // the kth smallest hash out of n uniformly distributed hashes is close to k/n of the hash range
+        let kth = *self.hashes.iter().next_back().unwrap() as f64;
+        ((DISTINCT_SKETCH_SIZE - 1) as f64 * (u64::MAX as f64 / kth)) as u64
+    }
+
+    /// Returns the most frequent non-blank values with their number of occurrences, most frequent first.
+    pub fn top_values(&self) -> Vec<(&str, u64)> {
+        let mut values: Vec<_> = self.values.iter().map(|(v, &n)| (v.as_str(), n)).collect();
+        values.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        values.truncate(self.top_n);
+        values
+    }
+}
+
+/// Profile of a record type.
+#[derive(Debug, Clone)]
+pub struct RecordProfile {
+    /// record name
+    pub name: String,
+    /// record description
+    pub description: String,
+    /// number of records read
+    pub count: u64,
+    /// profile of each field, in field order
+    pub fields: Vec<FieldProfile>,
+}
+
+impl RecordProfile {
+    /// Converts a record profile to an HTML table, styled like **Record::to_html()**.
+    pub fn to_html(&self) -> String {
+        let mut s = String::with_capacity(300*self.fields.len());
+
+        s += format!("<h2><span class=\"label label-primary\">{}-{}-{}</span></h2>",
+            self.name, self.description, self.count).as_str();
+
+        s += "<table class=\"table table-striped\">";
+        s += "<thead><tr><th>#</th><th>Field name</th><th>Type</th><th>Filled</th><th>Distinct</th>";
+        s += "<th>Min length</th><th>Max length</th><th>Pattern</th><th>Typed</th><th>Top values</th></tr></thead>";
+
+        for f in &self.fields {
+            let top: Vec<_> = f.top_values().iter()
+                .map(|(v, n)| format!("<code>{}</code> ({})", html_escape(v), n))
+                .collect();
+
+            s += format!("<tr><td>{}</td><td><strong>{}</strong></td><td>{}</td>", f.index, f.name, f.type_id).as_str();
+            s += format!("<td>{}</td><td>{}</td><td>{}</td><td>{}</td>",
+                percent(f.fill_rate()), f.distinct(), optional(f.min_length), optional(f.max_length)).as_str();
+            s += format!("<td>{}</td><td>{}</td><td>{}</td></tr>",
+                percent(f.match_rate()), percent(f.conversion_rate()), top.join("<br>")).as_str();
+        }
+
+        s += "</table>";
+
+        s
+    }
+}
+
+/// Profile of a whole record-based file.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    /// number of most frequent values kept for each field
+    pub top_n: usize,
+    /// number of lines read
+    pub nblines_read: u64,
+    /// profile of each record type read, by record name
+    pub records: BTreeMap<String, RecordProfile>,
+}
+
+impl Profile {
+    /// Creates an empty profile.
+    ///
+    /// # Arguments
+    ///
+    /// * `top_n` - number of most frequent values kept for each field
+    pub fn new(top_n: usize) -> Profile {
+        Profile { top_n, nblines_read: 0, records: BTreeMap::new() }
+    }
+
+    /// Profiles all records read by `reader`, up to end of file.
+    pub fn from_reader<T>(reader: &mut Reader<T>, top_n: usize) -> Profile
+        where Record<T>: ReadMode
+    {
+        let mut profile = Profile::new(top_n);
+
+        while let Some(rec) = reader.next() {
+            profile.add(rec);
+        }
+        profile.nblines_read = reader.nblines_read;
+
+        profile
+    }
+
+    /// Adds the values of a record to the profile.
+    pub fn add<T>(&mut self, rec: &Record<T>) {
+        let top_n = self.top_n;
+
+        if !self.records.contains_key(&rec.name) {
+            let fields = rec.flist.iter().map(|f| FieldProfile::new(f.index, &f.name, &f.ftype.id, top_n)).collect();
+            self.records.insert(rec.name.clone(), RecordProfile {
+                name: rec.name.clone(),
+                description: rec.description.clone(),
+                count: 0,
+                fields,
+            });
+        }
+
+        let profile = self.records.get_mut(&rec.name).unwrap();
+        profile.count += 1;
+
+        for (f, fv) in profile.fields.iter_mut().zip(rec.field_values()) {
+            let value = fv.value();
+            f.add(value, fv.is_match(), !value.is_empty() && matches!(fv.typed_value(), Ok(Some(_))));
+        }
+    }
+
+    /// Writes the profile as text tables, one per record type.
+    pub fn write_text<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "lines read: {}", self.nblines_read)?;
+
+        for rec in self.records.values() {
+            writeln!(w, "\nrecord {} ({}), {} records:", rec.name, rec.description, rec.count)?;
+
+            // column widths
+            let name_width = rec.fields.iter().map(|f| f.name.len()).max().unwrap_or(0).max(4);
+            let type_width = rec.fields.iter().map(|f| f.type_id.len()).max().unwrap_or(0).max(4);
+
+            writeln!(w, "  {:>3} {:<nw$} {:<tw$} {:>7} {:>8} {:>5} {:>5} {:>7} {:>7}  top values",
+                "#", "name", "type", "filled", "distinct", "min", "max", "pattern", "typed",
+                nw = name_width, tw = type_width)?;
+            for f in &rec.fields {
+                let top: Vec<_> = f.top_values().iter().map(|(v, n)| format!("'{}' ({})", v, n)).collect();
+                writeln!(w, "  {:>3} {:<nw$} {:<tw$} {:>7} {:>8} {:>5} {:>5} {:>7} {:>7}  {}",
+                    f.index, f.name, f.type_id, percent(f.fill_rate()), f.distinct(),
+                    optional(f.min_length), optional(f.max_length), percent(f.match_rate()), percent(f.conversion_rate()),
+                    top.join(", "), nw = name_width, tw = type_width)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Converts the profile to HTML: one table per record type, styled like **Record::to_html()**.
+    pub fn to_html(&self) -> String {
+        self.records.values().map(|rec| rec.to_html()).collect::<Vec<_>>().join("\n")
+    }
+
+    /// Converts the profile to a JSON object, with rates between 0 and 1.
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> ::serde_json::Value {
+        use serde_json::json;
+
+        let records: Vec<_> = self.records.values().map(|rec| {
+            let fields: Vec<_> = rec.fields.iter().map(|f| {
+                let top: Vec<_> = f.top_values().iter().map(|(v, n)| json!({ "value": v, "count": n })).collect();
+                json!({
+                    "index": f.index,
+                    "name": f.name,
+                    "type": f.type_id,
+                    "count": f.count,
+                    "filled": f.filled,
+                    "fill_rate": f.fill_rate(),
+                    "distinct": f.distinct(),
+                    "min_length": f.min_length,
+                    "max_length": f.max_length,
+                    "match_rate": f.match_rate(),
+                    "conversion_rate": f.conversion_rate(),
+                    "top_values": top,
+                })
+            }).collect();
+            json!({ "name": rec.name, "description": rec.description, "count": rec.count, "fields": fields })
+        }).collect();
+
+        json!({ "lines": self.nblines_read, "records": records })
+    }
+}
+
+// ratio of two counts, 1 if nothing was counted
+fn ratio(n: u64, total: u64) -> f64 {
+    if total == 0 { 1.0 } else { n as f64 / total as f64 }
+}
+
+fn percent(rate: f64) -> String {
+    format!("{:.1}%", 100.0 * rate)
+}
+
+fn optional(length: Option<usize>) -> String {
+    length.map_or("-".to_string(), |l| l.to_string())
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use profile::FieldProfile;
+
+    #[test]
+    fn profile_field() {
+        let mut f = FieldProfile::new(0, "F", "N", 2);
+        for v in &["12", "", "12", "345", "6", ""] {
+            f.add(v, true, *v != "345");
+        }
+
+        assert_eq!(f.count, 6);
+        assert_eq!(f.filled, 4);
+        assert_eq!(f.min_length, Some(1));
+        assert_eq!(f.max_length, Some(3));
+        assert_eq!(f.distinct(), 3);
+        assert_eq!(f.conversion_rate(), 0.75);
+        assert_eq!(f.top_values(), vec![("12", 2), ("345", 1)]);
+    }
+
+    #[test]
+    fn profile_distinct_estimate() {
+        let mut f = FieldProfile::new(0, "F", "N", 1);
+        for i in 0..100_000 {
+            let v = (i % 20_000).to_string();
+            f.add(&v, true, true);
+        }
+
+        // standard error is about 3% with 1024 hashes
+        let distinct = f.distinct() as f64;
+        assert!((distinct - 20_000.0).abs() < 0.15 * 20_000.0, "{}", distinct);
+    }
+}
@@ -0,0 +1,92 @@
+//! Progress reporting when reading a record-based file. A callback set on a
+//! [Reader](../reader/struct.Reader.html) is regularly called with a **Progress** struct, giving the
+//! percentage of the file read, throughput, estimated time left and the number of records read by
+//! record type.
+//!
+//! # Examples
+//! ```rust
+//! use rbf::record::AsciiMode;
+//! use rbf::layout::Layout;
+//! use rbf::reader::Reader;
+//! use rbf::progress::ProgressStep;
+//!
+//! let layout = Layout::<AsciiMode>::new("./tests/test.xml");
+//! let mapper = Box::new(|x: &str| x[0..2].to_string());
+//! let mut reader = Reader::<AsciiMode>::new("./tests/test_ascii.data", layout, mapper);
+//!
+//! reader.set_progress(ProgressStep::Lines(100), Box::new(|progress| {
+//!     println!("{:.1}% {:.0} lines/s {:.2} MB/s", progress.percent(), progress.lines_per_sec(), progress.mb_per_sec());
+//!     if progress.done {
+//!         assert_eq!(progress.records["LL"], 257);
+//!     }
+//! }));
+//! while reader.next().is_some() {}
+//! ```
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// How often the progress callback is called
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProgressStep {
+    /// every N lines read
+    Lines(u64),
+    /// every N bytes read
+    Bytes(u64),
+}
+
+/// Closure called to report progress.
+pub type ProgressCallback = Box<dyn FnMut(&Progress) + Send>;
+
+/// Snapshot of the reading progress.
+pub struct Progress<'a> {
+    /// size of the file being read
+    pub file_size: u64,
+    /// byte offset of the next line to read within the file
+    pub position: u64,
+    /// total number of bytes read so far
+    pub bytes_read: u64,
+    /// line number of the last line read
+    pub nblines_read: u64,
+    /// time elapsed since reading started
+    pub elapsed: Duration,
+    /// number of records read by record type, including records not selected by a filter
+    pub records: &'a HashMap<String, u64>,
+    /// set when end of file is reached
+    pub done: bool,
+}
+
+impl<'a> Progress<'a> {
+    /// Returns the percentage of the file already read.
+    pub fn percent(&self) -> f64 {
+        if self.file_size == 0 {
+            return 100.0;
+        }
+        100.0 * self.position as f64 / self.file_size as f64
+    }
+
+    /// Returns the number of lines read per second.
+    pub fn lines_per_sec(&self) -> f64 {
+        self.per_sec(self.nblines_read as f64)
+    }
+
+    /// Returns the number of megabytes (10^6 bytes) read per second.
+    pub fn mb_per_sec(&self) -> f64 {
+        self.per_sec(self.bytes_read as f64 / 1e6)
+    }
+
+    /// Returns the estimated time left to read the whole file, based on the current throughput.
+    pub fn eta(&self) -> Option<Duration> {
+        let bytes_per_sec = self.per_sec(self.bytes_read as f64);
+        if bytes_per_sec <= 0.0 {
+            return None;
+        }
+        let left = self.file_size.saturating_sub(self.position) as f64;
+        Some(Duration::from_secs_f64(left / bytes_per_sec))
+    }
+
+    // rate of a quantity since reading started
+    fn per_sec(&self, quantity: f64) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 { quantity / secs } else { 0.0 }
+    }
+}
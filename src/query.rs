@@ -0,0 +1,713 @@
+//! A small expression language to select records, e.g.: `COUN.POPULATION > 1e8 and CAPITAL =~ "^B"`.
+//!
+//! A query is first parsed, then compiled against a layout: unknown records or fields, and literals which
+//! can't be compared to a field because of its type, are reported before reading starts. The compiled
+//! query is then evaluated on each record.
+//!
+//! # Syntax
+//!
+//! * field references: `FIELD` (any record having this field), `REC.FIELD` (only records named `REC`), and
+//!   `FIELD[i]` or `REC.FIELD[i]` to reference the i-th occurrence (starting at 0) of a duplicated field name.
+//!   Without index, the first occurrence is used
+//! * record name: `record`
+//! * comparisons: `=` (or `==`), `!=` (or `<>`), `<`, `<=`, `>`, `>=` between a field reference and a
+//!   literal, either a number (`12`, `-3.5`, `1e8`) or a quoted string (`"abc"` or `'abc'`)
+//! * regex match: `FIELD =~ "regex"`, or `FIELD !~ "regex"`
+//! * lists: `FIELD in (1, 2, 3)`, or `FIELD not in ("A", "B")`
+//! * boolean operators `and`, `or`, `not` and parenthesis
+//!
+//! Comparisons are typed according to the field type: integer and decimal fields are compared as numbers,
+//! date fields to `"YYYY-MM-DD"` literals, time fields to `"HH:MM:SS"` literals, and string fields
+//! alphabetically. A field whose value can't be converted never matches. When the same field name has
+//! different types depending on the record, a literal which is not valid for the field type is compared
+//! as a string: it's only reported if it's valid for none of them. Regex matches always use the
+//! blank-stripped value.
+//!
+//! # Examples
+//! ```rust
+//! use rbf::record::{AsciiMode, ReadMode};
+//! use rbf::layout::Layout;
+//! use rbf::query::Query;
+//!
+//! let mut layout = Layout::<AsciiMode>::new("./tests/test.xml");
+//!
+//! // unknown fields are reported
+//! assert!(Query::compile("NB.FOO = 1", &layout).is_err());
+//!
+//! let query = Query::compile("NB.N3 > 300 and ID in ('NB', 'LL')", &layout).unwrap();
+//!
+//! let rec = layout.get_mut("NB").unwrap();
+//! rec.set_value("NB122333444455555666666777777788888888999999999");
+//! assert!(query.matches(rec));
+//! ```
+use std::cmp::Ordering;
+use std::fmt;
+
+use regex::Regex;
+
+use fieldtype::BaseDataType;
+use field::FieldValue;
+use record::Record;
+use layout::Layout;
+use value::{Date, Time, TypedValue};
+
+/// Format of date literals
+const DATE_LITERAL_FORMAT: &str = "%Y-%m-%d";
+/// Format of time literals
+const TIME_LITERAL_FORMAT: &str = "%H:%M:%S";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Number(String),
+    Str(String),
+    Op(&'static str),
+    Dot,
+    Comma,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Token::Word(ref s) | Token::Number(ref s) => write!(f, "{}", s),
+            Token::Str(ref s) => write!(f, "\"{}\"", s),
+            Token::Op(op) => write!(f, "{}", op),
+            Token::Dot => write!(f, "."),
+            Token::Comma => write!(f, ","),
+            Token::LParen => write!(f, "("),
+            Token::RParen => write!(f, ")"),
+            Token::LBracket => write!(f, "["),
+            Token::RBracket => write!(f, "]"),
+        }
+    }
+}
+
+// operators, longest first
+const OPERATORS: [&str; 11] = ["==", "!=", "<>", "<=", ">=", "=~", "!~", "=", "<", ">", "!"];
+
+// splits the query into tokens, each one with its position (in chars)
+fn tokenize(query: &str) -> Result<Vec<(usize, Token)>, String> {
+    let number_reg = Regex::new(r"^[+-]?(\d+\.?\d*|\.\d+)([eE][+-]?\d+)?").unwrap();
+    let word_reg = Regex::new(r"^\w+").unwrap();
+
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < query.len() {
+        let rest = &query[pos..];
+        let c = rest.chars().next().unwrap();
+        let col = query[..pos].chars().count();
+
+        if c.is_whitespace() {
+            pos += c.len_utf8();
+            continue;
+        }
+
+        // numbers, unless immediately followed by a word (e.g.: record name like 01)
+        if let Some(m) = number_reg.find(rest) {
+            let followed_by_word = rest[m.end()..].chars().next().is_some_and(|c| c.is_alphanumeric() || c == '_');
+            if !followed_by_word {
+                tokens.push((col, Token::Number(m.as_str().to_string())));
+                pos += m.end();
+                continue;
+            }
+        }
+
+        if let Some(m) = word_reg.find(rest) {
+            tokens.push((col, Token::Word(m.as_str().to_string())));
+            pos += m.end();
+            continue;
+        }
+
+        // quoted strings, quotes are escaped by doubling them or using a backslash
+        if c == '"' || c == '\'' {
+            let mut s = String::new();
+            let mut chars = rest.char_indices().skip(1).peekable();
+            let mut end = None;
+
+            while let Some((i, ch)) = chars.next() {
+                if ch == '\\' {
+                    if let Some((_, escaped)) = chars.next() {
+                        if escaped != c && escaped != '\\' {
+                            s.push('\\');
+                        }
+                        s.push(escaped);
+                    }
+                }
+                else if ch == c {
+                    if let Some(&(_, next)) = chars.peek() {
+                        if next == c {
+                            s.push(c);
+                            chars.next();
+                            continue;
+                        }
+                    }
+                    end = Some(i + 1);
+                    break;
+                }
+                else {
+                    s.push(ch);
+                }
+            }
+
+            match end {
+                Some(end) => {
+                    tokens.push((col, Token::Str(s)));
+                    pos += end;
+                    continue;
+                },
+                None => return Err(format!("unterminated string at position {}", col)),
+            }
+        }
+
+        if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            tokens.push((col, Token::Op(op)));
+            pos += op.len();
+            continue;
+        }
+
+        let token = match c {
+            '.' => Token::Dot,
+            ',' => Token::Comma,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '[' => Token::LBracket,
+            ']' => Token::RBracket,
+            _ => return Err(format!("unexpected character '{}' at position {}", c, col)),
+        };
+        tokens.push((col, token));
+        pos += c.len_utf8();
+    }
+
+    Ok(tokens)
+}
+
+/// What a condition applies to.
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    /// record name
+    Record,
+    /// field, optionally restricted to a record, and occurrence of the field name
+    Field { record: Option<String>, name: String, index: usize },
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Operand::Record => write!(f, "record"),
+            Operand::Field { ref record, ref name, index } => {
+                if let Some(ref r) = *record {
+                    write!(f, "{}.", r)?;
+                }
+                write!(f, "{}", name)?;
+                if index != 0 {
+                    write!(f, "[{}]", index)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CmpOp {
+    fn is_true(&self, ord: Ordering) -> bool {
+        match *self {
+            CmpOp::Eq => ord == Ordering::Equal,
+            CmpOp::Ne => ord != Ordering::Equal,
+            CmpOp::Lt => ord == Ordering::Less,
+            CmpOp::Le => ord != Ordering::Greater,
+            CmpOp::Gt => ord == Ordering::Greater,
+            CmpOp::Ge => ord != Ordering::Less,
+        }
+    }
+}
+
+/// A literal, with its possible typed values decoded once.
+#[derive(Debug, Clone)]
+struct Literal {
+    text: String,
+    number: Option<f64>,
+    date: Option<i64>,
+    time: Option<u32>,
+}
+
+impl Literal {
+    fn new(text: &str) -> Literal {
+        Literal {
+            text: text.to_string(),
+            number: text.parse::<f64>().ok(),
+            date: Date::parse(text, DATE_LITERAL_FORMAT).map(|d| d.days_since_epoch()),
+            time: Time::parse(text, TIME_LITERAL_FORMAT).map(|t| t.seconds_since_midnight()),
+        }
+    }
+
+    // checks the literal can be compared to a field of this type
+    fn check(&self, base_data_type: &BaseDataType) -> Result<(), String> {
+        let ok = match *base_data_type {
+            BaseDataType::String => true,
+            BaseDataType::Integer | BaseDataType::Decimal => self.number.is_some(),
+            BaseDataType::Date{..} => self.date.is_some(),
+            BaseDataType::Time{..} => self.time.is_some(),
+        };
+
+        if ok {
+            Ok(())
+        }
+        else {
+            Err(format!("<{}> is not a valid {} literal", self.text, match *base_data_type {
+                BaseDataType::Date{..} => "date (YYYY-MM-DD)",
+                BaseDataType::Time{..} => "time (HH:MM:SS)",
+                _ => "number",
+            }))
+        }
+    }
+
+    // compares a field value to the literal, according to the field type. When the literal is not valid
+    // for the field type, values are compared as strings
+    fn compare(&self, field: &FieldValue) -> Option<Ordering> {
+        if self.check(&field.field.ftype.base_data_type).is_err() {
+            return Some(field.value().cmp(self.text.as_str()));
+        }
+
+        match field.field.ftype.base_data_type {
+            BaseDataType::String => Some(field.value().cmp(self.text.as_str())),
+            _ => match field.typed_value() {
+                Ok(Some(TypedValue::Integer(i))) => (i as f64).partial_cmp(&self.number?),
+                Ok(Some(TypedValue::Decimal(d))) => d.partial_cmp(&self.number?),
+                Ok(Some(TypedValue::Date(d))) => Some(d.days_since_epoch().cmp(&self.date?)),
+                Ok(Some(TypedValue::Time(t))) => Some(t.seconds_since_midnight().cmp(&self.time?)),
+                _ => None,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Cmp(Operand, CmpOp, Literal),
+    Match(Operand, Regex),
+    In(Operand, Vec<Literal>),
+}
+
+// recursive descent parser
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    length: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|t| &t.1)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).map(|t| t.1.clone());
+        self.pos += 1;
+        t
+    }
+
+    fn error<R>(&self, expected: &str) -> Result<R, String> {
+        match self.tokens.get(self.pos) {
+            Some(&(col, ref t)) => Err(format!("expected {} at position {}, found '{}'", expected, col, t)),
+            None => Err(format!("expected {} at position {}, found end of query", expected, self.length)),
+        }
+    }
+
+    // tests whether next token is a keyword (case insensitive)
+    fn is_keyword(&self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(w)) => w.eq_ignore_ascii_case(keyword),
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, token: Token, expected: &str) -> Result<(), String> {
+        if self.peek() == Some(&token) {
+            self.pos += 1;
+            Ok(())
+        }
+        else {
+            self.error(expected)
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_and()?;
+        while self.is_keyword("or") {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_not()?;
+        while self.is_keyword("and") {
+            self.pos += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_not()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, String> {
+        if self.is_keyword("not") || self.peek() == Some(&Token::Op("!")) {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.parse_not()?)));
+        }
+
+        if self.peek() == Some(&Token::LParen) {
+            self.pos += 1;
+            let expr = self.parse_or()?;
+            self.expect(Token::RParen, "')'")?;
+            return Ok(expr);
+        }
+
+        self.parse_condition()
+    }
+
+    fn parse_operand(&mut self) -> Result<Operand, String> {
+        let first = match self.peek() {
+            Some(Token::Word(w)) => w.clone(),
+            _ => return self.error("a field name"),
+        };
+        self.pos += 1;
+
+        if first == "record" {
+            return Ok(Operand::Record);
+        }
+
+        // REC.FIELD
+        let (record, name) = if self.peek() == Some(&Token::Dot) {
+            self.pos += 1;
+            match self.next() {
+                Some(Token::Word(w)) => (Some(first), w),
+                _ => {
+                    self.pos -= 1;
+                    return self.error("a field name");
+                }
+            }
+        }
+        else {
+            (None, first)
+        };
+
+        // FIELD[i]
+        let mut index = 0;
+        if self.peek() == Some(&Token::LBracket) {
+            self.pos += 1;
+            index = match self.peek() {
+                Some(Token::Number(n)) => match n.parse::<usize>() {
+                    Ok(i) => i,
+                    Err(_) => return self.error("a field index"),
+                },
+                _ => return self.error("a field index"),
+            };
+            self.pos += 1;
+            self.expect(Token::RBracket, "']'")?;
+        }
+
+        Ok(Operand::Field { record, name, index })
+    }
+
+    fn parse_literal(&mut self) -> Result<Literal, String> {
+        match self.peek() {
+            Some(&Token::Number(ref s)) | Some(&Token::Str(ref s)) => {
+                let literal = Literal::new(s);
+                self.pos += 1;
+                Ok(literal)
+            },
+            _ => self.error("a number or a string"),
+        }
+    }
+
+    fn parse_condition(&mut self) -> Result<Expr, String> {
+        let operand = self.parse_operand()?;
+
+        // FIELD in (...) or FIELD not in (...)
+        let negate = self.is_keyword("not");
+        if negate {
+            self.pos += 1;
+            if !self.is_keyword("in") {
+                return self.error("'in'");
+            }
+        }
+        if self.is_keyword("in") {
+            self.pos += 1;
+            self.expect(Token::LParen, "'('")?;
+
+            let mut list = vec![self.parse_literal()?];
+            while self.peek() == Some(&Token::Comma) {
+                self.pos += 1;
+                list.push(self.parse_literal()?);
+            }
+            self.expect(Token::RParen, "')'")?;
+
+            let expr = Expr::In(operand, list);
+            return Ok(if negate { Expr::Not(Box::new(expr)) } else { expr });
+        }
+
+        let op = match self.peek() {
+            Some(&Token::Op(op)) => op,
+            _ => return self.error("an operator"),
+        };
+        self.pos += 1;
+
+        // regex matching
+        if op == "=~" || op == "!~" {
+            let col = self.tokens.get(self.pos).map_or(self.length, |t| t.0);
+            let re = match self.next() {
+                Some(Token::Str(s)) => Regex::new(&s).map_err(|e| format!("invalid regex at position {}: {}", col, e))?,
+                _ => {
+                    self.pos -= 1;
+                    return self.error("a quoted regex");
+                }
+            };
+
+            let expr = Expr::Match(operand, re);
+            return Ok(if op == "!~" { Expr::Not(Box::new(expr)) } else { expr });
+        }
+
+        let cmp = match op {
+            "=" | "==" => CmpOp::Eq,
+            "!=" | "<>" => CmpOp::Ne,
+            "<" => CmpOp::Lt,
+            "<=" => CmpOp::Le,
+            ">" => CmpOp::Gt,
+            ">=" => CmpOp::Ge,
+            _ => {
+                self.pos -= 1;
+                return self.error("a comparison operator");
+            }
+        };
+
+        Ok(Expr::Cmp(operand, cmp, self.parse_literal()?))
+    }
+}
+
+// returns the field referenced by the operand, if the record has it
+fn find_field<'a, T>(rec: &'a Record<T>, record: &Option<String>, name: &str, index: usize) -> Option<FieldValue<'a>> {
+    if let Some(ref r) = *record {
+        if *r != rec.name {
+            return None;
+        }
+    }
+    rec.indices_of(name).get(index).map(|&i| rec.field_value(i))
+}
+
+impl Expr {
+    fn eval<T>(&self, rec: &Record<T>) -> bool {
+        match *self {
+            Expr::And(ref a, ref b) => a.eval(rec) && b.eval(rec),
+            Expr::Or(ref a, ref b) => a.eval(rec) || b.eval(rec),
+            Expr::Not(ref a) => !a.eval(rec),
+            Expr::Cmp(Operand::Record, op, ref lit) => op.is_true(rec.name.cmp(&lit.text)),
+            Expr::Cmp(Operand::Field { ref record, ref name, index }, op, ref lit) => {
+                find_field(rec, record, name, index)
+                    .and_then(|f| lit.compare(&f))
+                    .is_some_and(|ord| op.is_true(ord))
+            },
+            Expr::Match(Operand::Record, ref re) => re.is_match(&rec.name),
+            Expr::Match(Operand::Field { ref record, ref name, index }, ref re) => {
+                find_field(rec, record, name, index).is_some_and(|f| re.is_match(f.value()))
+            },
+            Expr::In(Operand::Record, ref list) => list.iter().any(|lit| rec.name == lit.text),
+            Expr::In(Operand::Field { ref record, ref name, index }, ref list) => {
+                find_field(rec, record, name, index)
+                    .is_some_and(|f| list.iter().any(|lit| lit.compare(&f) == Some(Ordering::Equal)))
+            },
+        }
+    }
+
+    // checks record & field names, and literal types against the layout
+    fn check<T>(&self, layout: &Layout<T>) -> Result<(), String> {
+        let (operand, literals): (&Operand, Vec<&Literal>) = match *self {
+            Expr::And(ref a, ref b) | Expr::Or(ref a, ref b) => return a.check(layout).and_then(|_| b.check(layout)),
+            Expr::Not(ref a) => return a.check(layout),
+            Expr::Cmp(ref operand, _, ref lit) => (operand, vec![lit]),
+            Expr::Match(ref operand, _) => (operand, Vec::new()),
+            Expr::In(ref operand, ref list) => (operand, list.iter().collect()),
+        };
+
+        match *operand {
+            Operand::Record => {
+                // only equality tests can be checked
+                if let Expr::Cmp(_, CmpOp::Eq, _) | Expr::In(..) = *self {
+                    if let Some(lit) = literals.iter().find(|lit| !layout.contains_record(&lit.text)) {
+                        return Err(format!("record {} not found in layout", lit.text));
+                    }
+                }
+            },
+            Operand::Field { ref record, ref name, index } => {
+                // all records this field reference applies to
+                let recs: Vec<_> = match *record {
+                    Some(ref r) => match layout.get(r) {
+                        Some(rec) => vec![rec],
+                        None => return Err(format!("record {} not found in layout", r)),
+                    },
+                    None => layout.rec_map.values().collect(),
+                };
+
+                let fields: Vec<_> = recs.iter()
+                    .filter_map(|rec| find_field(rec, &None, name, index).map(|f| (&rec.name, f)))
+                    .collect();
+
+                if fields.is_empty() {
+                    return Err(format!("field {} not found in layout", operand));
+                }
+
+                // each literal should be valid for at least one of the fields
+                for lit in &literals {
+                    if !fields.iter().any(|&(_, f)| lit.check(&f.field.ftype.base_data_type).is_ok()) {
+                        let (rec_name, f) = fields[0];
+                        return lit.check(&f.field.ftype.base_data_type)
+                            .map_err(|e| format!("{} for field {} of record {}", e, operand, rec_name));
+                    }
+                }
+            },
+        }
+
+        Ok(())
+    }
+}
+
+/// A compiled query, used to select records.
+#[derive(Debug, Clone)]
+pub struct Query {
+    /// query as given
+    pub text: String,
+    /// expression tree
+    expr: Expr,
+}
+
+impl Query {
+    /// Parses a query, only checking its syntax.
+    pub fn parse(query: &str) -> Result<Query, String> {
+        let tokens = tokenize(query)?;
+
+        if tokens.is_empty() {
+            return Err("empty query".to_string());
+        }
+
+        let mut parser = Parser { tokens, pos: 0, length: query.chars().count() };
+        let expr = parser.parse_or()?;
+
+        if parser.pos < parser.tokens.len() {
+            return parser.error("'and', 'or' or end of query");
+        }
+
+        Ok(Query { text: query.to_string(), expr })
+    }
+
+    /// Checks the record and field names used in the query are found in the layout, and literals can be
+    /// compared to the fields according to their field type.
+    pub fn check<T>(&self, layout: &Layout<T>) -> Result<(), String> {
+        self.expr.check(layout)
+    }
+
+    /// Parses a query and checks it against a layout.
+    pub fn compile<T>(query: &str, layout: &Layout<T>) -> Result<Query, String> {
+        let q = Query::parse(query)?;
+        q.check(layout)?;
+        Ok(q)
+    }
+
+    /// Evaluates the query on a record.
+    pub fn matches<T>(&self, rec: &Record<T>) -> bool {
+        self.expr.eval(rec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use record::ReadMode;
+    use query::{Query, Token, tokenize};
+
+    #[test]
+    fn query_tokenize() {
+        let tokens: Vec<_> = tokenize("01.F1 >= -1e8 and X[2] in ('a''b', \"c\")").unwrap().into_iter().map(|t| t.1).collect();
+        assert_eq!(tokens, vec![
+            Token::Word("01".to_string()), Token::Dot, Token::Word("F1".to_string()), Token::Op(">="),
+            Token::Number("-1e8".to_string()), Token::Word("and".to_string()), Token::Word("X".to_string()),
+            Token::LBracket, Token::Number("2".to_string()), Token::RBracket, Token::Word("in".to_string()),
+            Token::LParen, Token::Str("a'b".to_string()), Token::Comma, Token::Str("c".to_string()), Token::RParen,
+        ]);
+
+        assert!(tokenize("A = 'abc").is_err());
+        assert!(tokenize("A # 1").is_err());
+    }
+
+    #[test]
+    fn query_parse() {
+        assert!(Query::parse("A = 1 and (B < 2 or not C =~ '^x')").is_ok());
+        assert!(Query::parse("REC.A not in (1, 2)").is_ok());
+        assert_eq!(Query::parse("A = ").unwrap_err(), "expected a number or a string at position 4, found end of query");
+        assert_eq!(Query::parse("A = 1 B").unwrap_err(), "expected 'and', 'or' or end of query at position 6, found 'B'");
+        assert!(Query::parse("A =~ '('").is_err());
+        assert!(Query::parse("(A = 1").is_err());
+        assert!(Query::parse("").is_err());
+    }
+
+    #[test]
+    fn query_check() {
+        let layout = ::layout::setup::layout_load_layout_ascii();
+
+        assert!(Query::compile("NB.N1 = 1 and LL.W1 = 'A' and F5[3] = 'D'", &layout).is_ok());
+        assert!(Query::compile("record in ('NB', 'LL')", &layout).is_ok());
+        assert_eq!(Query::compile("FOO = 1", &layout).unwrap_err(), "field FOO not found in layout");
+        assert_eq!(Query::compile("XX.N1 = 1", &layout).unwrap_err(), "record XX not found in layout");
+        assert_eq!(Query::compile("record = 'XX'", &layout).unwrap_err(), "record XX not found in layout");
+        assert!(Query::compile("F5[4] = 'D'", &layout).is_err());
+        assert_eq!(Query::compile("NB.N1 > 'abc'", &layout).unwrap_err(), "<abc> is not a valid number literal for field NB.N1 of record NB");
+    }
+
+    #[test]
+    fn query_matches() {
+        let mut layout = ::layout::setup::layout_load_layout_ascii();
+        let rec = layout.get_mut("NB").unwrap();
+        rec.set_value("NB122333444455555666666777777788888888999999999");
+
+        let q = |s: &str| Query::parse(s).unwrap().matches(rec);
+
+        // typed comparisons: N3 is decimal
+        assert!(q("N3 = 333"));
+        assert!(q("N3 > 1e2 and N3 <= 333.0"));
+        assert!(!q("N3 < 100"));
+        assert!(q("ID = 'NB' and ID != \"LL\""));
+        assert!(q("ID < 'NC'"));
+
+        // other records
+        assert!(!q("LL.ID = 'NB'"));
+        assert!(q("not LL.ID = 'NB'"));
+
+        // regex, lists & record names
+        assert!(q("N9 =~ '^9+$' and N9 !~ '8'"));
+        assert!(q("N1 in (1, 2) and N2 not in (1, 2)"));
+        assert!(q("record = 'NB' and record =~ '^N' and record in ('LL', 'NB')"));
+        assert!(q("ID = 'XX' or (N1 = 1 and N2 = 22)"));
+
+        // duplicated fields
+        let rec = layout.get_mut("DP").unwrap();
+        rec.set_value("DPAAAAABBBBBCCCCCDDDDD");
+        let q = |s: &str| Query::parse(s).unwrap().matches(rec);
+        assert!(q("F5 = 'AAAAA' and F5[0] = 'AAAAA' and F5[3] = 'DDDDD'"));
+        assert!(!q("F5[4] = 'DDDDD'"));
+    }
+}
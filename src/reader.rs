@@ -1,6 +1,9 @@
 //! Represents a way to read a record-based file by mapping each read line to a record. The mapping between
 //! the data from the file and the record name is made by the `mapper` function.
 //!
+//! With the `mmap` feature, **set_mmap()** reads the file through a memory mapping instead of buffered reads,
+//! which avoids copying each line.
+//!
 //! # Examples
 //! ```rust
 //!    use rbf::record::{AsciiMode, UTF8Mode};
@@ -60,12 +63,31 @@
 //! ```
 
 use std::error::Error;
-use std::io::{BufReader,BufRead};
+use std::io::BufReader;
 use std::fs::File;
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
+#[cfg(feature = "mmap")]
+use std::io;
+#[cfg(feature = "mmap")]
+use std::ops::Range;
+#[cfg(feature = "mmap")]
+use std::str;
+
+#[cfg(feature = "mmap")]
+use memchr::{memchr, memrchr};
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
 
 use record::{ReadMode, Record};
 use layout::Layout;
 use mapper::RecordHasher;
+use query::Query;
+use index::{LineIndex, DEFAULT_STEP};
+use progress::{Progress, ProgressCallback, ProgressStep};
+use util::read_line;
+#[cfg(feature = "mmap")]
+use util::into_string;
 
 /// This enum defines whether we should stop reading when an unknown record ID is found
 #[derive(PartialEq)]
@@ -80,6 +102,29 @@ pub enum ReaderLazyness {
 // function type to get the record ID from the whole line read from the target file
 //pub type RecordMapper = fn(&str) -> &str;
 
+/// Closure selecting which records are returned by the reader.
+pub type RecordFilter<T> = Box<dyn Fn(&Record<T>) -> bool>;
+
+// where lines are read from
+enum Source {
+    // buffered reads
+    File(BufReader<File>),
+    // memory mapping, with the byte range of the last line read when it's valid UTF-8 (otherwise, the line is
+    // decoded into Reader::line)
+    #[cfg(feature = "mmap")]
+    Mapped(Mmap, Option<Range<usize>>),
+}
+
+// last line read, either from the mapping or from the line buffer
+fn current_line<'a>(source: &'a Source, line: &'a str) -> &'a str {
+    match *source {
+        // the range was checked to be valid UTF-8 when reading the line
+        #[cfg(feature = "mmap")]
+        Source::Mapped(ref mmap, Some(ref range)) => unsafe { str::from_utf8_unchecked(&mmap[range.clone()]) },
+        _ => line,
+    }
+}
+
 pub struct Reader<T> {
     /// record-based file to read
     pub rbf_file: String,
@@ -87,9 +132,10 @@ pub struct Reader<T> {
     pub layout: Layout<T>,
     /// function to map each line to a record name
     pub mapper: RecordHasher,
-    /// buffer use when reading the file line by line
-    bufreader: BufReader<File>,
-    /// the line read from file
+    /// buffered reader or memory mapping, lines are read from
+    source: Source,
+    /// the line read from file. When the file is memory-mapped, lines are not copied here: use
+    /// **current_line()** instead
     pub line: String,
     /// lazyness when reading
     pub lazyness: ReaderLazyness,
@@ -97,8 +143,30 @@ pub struct Reader<T> {
     pub file_size: u64,
     /// number of chars read when reading a line
     pub chars_read: usize,
+    /// total number of bytes read so far
+    pub bytes_read: u64,
     /// number of lines read so far
     pub nblines_read: u64,
+    /// number of records read by record type
+    pub record_counts: HashMap<String, u64>,
+    /// only records for which this closure returns true are returned
+    pub filter: Option<RecordFilter<T>>,
+    /// when set, field values are not copied into fields but only borrowed from the record line
+    pub zero_copy: bool,
+    /// index of the file, loaded or built when first needed
+    pub index: Option<LineIndex>,
+    /// byte offset of the next line to read
+    offset: u64,
+    /// line numbers and offsets of the only lines to read, set by **records_of()**
+    selection: Option<VecDeque<(u64, u64)>>,
+    /// progress callback and how often it's called
+    progress: Option<(ProgressStep, ProgressCallback)>,
+    /// when reading started, for progress reporting
+    start: Option<Instant>,
+    /// number of lines or bytes at which progress is next reported
+    next_report: u64,
+    /// whether end of file was already reported, so that it's reported once
+    reported_done: bool,
 }
 
 impl<T> Reader<T> {
@@ -133,12 +201,23 @@ impl<T> Reader<T> {
             rbf_file: rbf_file.to_string(),
             layout: layout,
             mapper: mapper,
-            bufreader: bufreader,
+            source: Source::File(bufreader),
             line: String::new(),
             lazyness: ReaderLazyness::Lazy,
             file_size: metadata.len(),
             chars_read: 0,
+            bytes_read: 0,
             nblines_read: 0,
+            record_counts: HashMap::new(),
+            filter: None,
+            zero_copy: false,
+            index: None,
+            offset: 0,
+            selection: None,
+            progress: None,
+            start: None,
+            reported_done: false,
+            next_report: 0,
         }
     }
 
@@ -162,51 +241,433 @@ impl<T> Reader<T> {
 
         // try to get a record ID
         loop {
-            // clear buffer, otherwise buffer is growing  
-            self.line.clear();            
+            // only read selected lines, if any
+            if let Some(position) = self.selection.as_mut().map(|s| s.pop_front()) {
+                let (line_number, offset) = position?;
+                self.seek(offset);
+                self.nblines_read = line_number - 1;
+            }
 
-            // read one line of text
-            match self.bufreader.read_line(&mut self.line) {
+            // read one line of text, invalid UTF-8 (e.g. HIGH-VALUES) being replaced
+            match self.read_next_line() {
                 // No bytes read? This is EOF and we must end the iteration
-                Ok(chars_read) => if chars_read == 0 { 
-                        return None; 
-                    } else { 
-                        self.chars_read = chars_read;
-                        self.nblines_read += 1; 
-                    },
-                // error reading bytes
-                Err(why) => panic!("error {} when reading file {}", why.description(), self.rbf_file),
+                0 => {
+                    if !self.reported_done {
+                        self.reported_done = true;
+                        self.report(true);
+                    }
+                    return None; 
+                },
+                chars_read => {
+                    self.chars_read = chars_read;
+                    self.nblines_read += 1; 
+                    self.offset += chars_read as u64;
+                    self.bytes_read += chars_read as u64;
+                    self.check_progress();
+                },
             }; 
 
             // get the record ID using mapper
-            rec_id = (self.mapper)(&self.line);
+            let line = current_line(&self.source, &self.line);
+            rec_id = (self.mapper)(line);
 
             // record ID could not exist
-            match self.layout.contains_record(&rec_id) {
-                true => break,
-                false => if self.lazyness == ReaderLazyness::Stringent {
+            match self.layout.get_mut(&rec_id) {
+                Some(rec) => {
+                    // count records by type, only allocating the first time
+                    match self.record_counts.get_mut(&rec_id) {
+                        Some(n) => *n += 1,
+                        None => { self.record_counts.insert(rec_id.clone(), 1); },
+                    }
+
+                    // set all field values
+                    if self.zero_copy {
+                        rec.parse(line);
+                    }
+                    else {
+                        rec.set_value(line);
+                    }
+                },
+                None => if self.lazyness == ReaderLazyness::Stringent {
                                 panic!("couldn't find record ID {} in file {}", rec_id, self.rbf_file);
                         } 
                         else {
                                 continue;
                         }
             };
+
+            // skip records not selected by the filter
+            match self.filter {
+                Some(ref filter) if !filter(self.layout.get(&rec_id).unwrap()) => continue,
+                _ => break,
+            };
         }
 
-        // set value for this record
-        let rec = self.layout.get_mut(&rec_id).unwrap();
+        // return our record
+        self.layout.get_mut(&rec_id)
+    }
+
+    /// Moves to the beginning of line `n` (starting from 1), so that **next()** reads from this line. The
+    /// file index is used to jump near the line: it's loaded from its sidecar file, or built and saved
+    /// if missing or stale (it's only kept in memory if it can't be saved). Returns **false** if the file
+    /// has less than `n` lines.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rbf::record::AsciiMode;
+    /// use rbf::layout::Layout;
+    /// use rbf::reader::Reader;
+    /// use rbf::index::LineIndex;
+    ///
+    /// let layout = Layout::<AsciiMode>::new("./tests/test.xml");
+    /// let mapper = Box::new(|x: &str| x[0..2].to_string());
+    /// let mut reader = Reader::<AsciiMode>::new("./tests/test_ascii.data", layout, mapper);
+    ///
+    /// // use an index in memory, without sidecar file
+    /// let index = LineIndex::build("./tests/test_ascii.data", &reader.mapper, 100).unwrap();
+    /// reader.set_index(index);
+    ///
+    /// assert!(reader.seek_line(250));
+    /// assert_eq!(reader.next().unwrap().name, "LL");
+    /// assert_eq!(reader.nblines_read, 250);
+    ///
+    /// // only read LL records from now on
+    /// assert_eq!(reader.records_of("LL"), 166);
+    /// while let Some(rec) = reader.next() {
+    ///     assert_eq!(rec.name, "LL");
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    /// If the index could not be built, or if an error is met when reading the file.
+    pub fn seek_line(&mut self, n: u64) -> bool {
+        self.check_index();
+        self.selection = None;
+        self.reported_done = false;
 
-        // set all field values
-        rec.set_value(&self.line);
+        let (line_number, offset) = match self.index.as_ref().and_then(|index| index.locate_line(n)) {
+            Some(position) => position,
+            None => return false,
+        };
+        self.seek(offset);
+        self.nblines_read = line_number - 1;
 
-        // return our record
-        return Some(rec);
+        // then skip lines up to line n
+        while self.nblines_read < n - 1 {
+            match self.read_next_line() {
+                0 => return false,
+                chars_read => {
+                    self.nblines_read += 1;
+                    self.offset += chars_read as u64;
+                },
+            }
+        }
+        true
+    }
+
+    /// Returns the record corresponding to the last line of the file, without changing the position of the
+    /// reader (e.g. to check a trailer record before reading the whole file). Returns **None** if the file is
+    /// empty or if the record ID is not found in the layout. The last line is found with the file index (see
+    /// **seek_line()**), or directly when the file is memory-mapped.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rbf::record::AsciiMode;
+    /// use rbf::layout::Layout;
+    /// use rbf::reader::Reader;
+    /// use rbf::index::LineIndex;
+    ///
+    /// let layout = Layout::<AsciiMode>::new("./tests/test.xml");
+    /// let mapper = Box::new(|x: &str| x[0..2].to_string());
+    /// let mut reader = Reader::<AsciiMode>::new("./tests/test_ascii.data", layout, mapper);
+    ///
+    /// let index = LineIndex::build("./tests/test_ascii.data", &reader.mapper, 100).unwrap();
+    /// reader.set_index(index);
+    ///
+    /// assert_eq!(reader.last_record().unwrap().name, "DP");
+    /// assert_eq!(reader.next().unwrap().name, "LL");
+    /// assert_eq!(reader.nblines_read, 1);
+    /// ```
+    ///
+    /// # Panics
+    /// If the index could not be built, or if an error is met when reading the file.
+    pub fn last_record(&mut self) -> Option<&mut Record<T>>
+        where Record<T>: ReadMode
+    {
+        let (offset, nblines_read, selection) = (self.offset, self.nblines_read, self.selection.take());
+
+        let found = match self.source {
+            #[cfg(feature = "mmap")]
+            Source::Mapped(ref mmap, _) => {
+                // skip the trailing newline, if any
+                let end = mmap.len() - (mmap.last() == Some(&b'\n')) as usize;
+                self.offset = memrchr(b'\n', &mmap[..end]).map_or(0, |i| i + 1) as u64;
+                end > 0
+            },
+            Source::File(_) => {
+                self.check_index();
+                let nb_lines = self.index.as_ref().map_or(0, |index| index.nb_lines);
+                nb_lines > 0 && self.seek_line(nb_lines)
+            },
+        };
+
+        // keep the offset in line with the buffered reader, to seek back from there
+        let length = if found { self.read_next_line() } else { 0 };
+        self.offset += length as u64;
+
+        let rec_id = if length > 0 {
+            let line = current_line(&self.source, &self.line);
+            let rec_id = (self.mapper)(line);
+            if let Some(rec) = self.layout.get_mut(&rec_id) {
+                if self.zero_copy { rec.parse(line) } else { rec.set_value(line) }
+            }
+            Some(rec_id)
+        }
+        else {
+            None
+        };
+
+        // back to where we were
+        self.seek(offset);
+        self.nblines_read = nblines_read;
+        self.selection = selection;
+
+        self.layout.get_mut(&rec_id?)
+    }
+
+    /// Only returns records having the record ID `name` from now on, i.e. after the last line read, jumping
+    /// directly from one to the other using the file index (see **seek_line()**). Returns the number of such
+    /// records left. Calling **seek_line()** gets back to reading all records.
+    ///
+    /// # Panics
+    /// If the index could not be built.
+    pub fn records_of(&mut self, name: &str) -> usize {
+        self.check_index();
+
+        let positions: VecDeque<_> = match self.index {
+            Some(ref index) => index.records.get(name).map_or(VecDeque::new(), |p| {
+                p.iter().filter(|&&(line_number, _)| line_number > self.nblines_read).cloned().collect()
+            }),
+            None => VecDeque::new(),
+        };
+        let nb_records = positions.len();
+        self.selection = Some(positions);
+
+        nb_records
+    }
+
+    /// Sets the file index, e.g. built with another step or loaded from elsewhere.
+    pub fn set_index(&mut self, index: LineIndex) {
+        self.index = Some(index);
+    }
+
+    // loads or builds the index, unless it's already there and up to date
+    fn check_index(&mut self) {
+        let step = match self.index {
+            Some(ref index) if index.is_valid() => return,
+            Some(ref index) => index.step,
+            None => DEFAULT_STEP,
+        };
+
+        if let Ok(index) = LineIndex::load(&self.rbf_file) {
+            if index.is_valid() {
+                self.index = Some(index);
+                return;
+            }
+        }
+
+        let index = match LineIndex::build(&self.rbf_file, &self.mapper, step) {
+            Ok(index) => index,
+            Err(why) => panic!("couldn't index file {}: {}", self.rbf_file, why),
+        };
+
+        // when the index file can't be written (e.g. read-only directory), the index is only kept in memory
+        let _ = index.save();
+        self.index = Some(index);
+    }
+
+    // moves to a byte offset, keeping buffered data when possible
+    fn seek(&mut self, offset: u64) {
+        match self.source {
+            Source::File(ref mut bufreader) => {
+                if let Err(why) = bufreader.seek_relative(offset as i64 - self.offset as i64) {
+                    panic!("error {} when seeking into file {}", why, self.rbf_file);
+                }
+            },
+            #[cfg(feature = "mmap")]
+            Source::Mapped(..) => (),
+        }
+        self.offset = offset;
+    }
+
+    // reads the line at the current offset, and returns its length in bytes (0 at end of file). The offset is
+    // not moved
+    fn read_next_line(&mut self) -> usize {
+        match self.source {
+            Source::File(ref mut bufreader) => match read_line(bufreader, &mut self.line) {
+                Ok(length) => length,
+                Err(why) => panic!("error {} when reading file {}", why, self.rbf_file),
+            },
+            #[cfg(feature = "mmap")]
+            Source::Mapped(ref mmap, ref mut current) => {
+                let start = (self.offset as usize).min(mmap.len());
+                let end = memchr(b'\n', &mmap[start..]).map_or(mmap.len(), |i| start + i + 1);
+
+                // lines are only copied when they're not UTF-8
+                *current = if str::from_utf8(&mmap[start..end]).is_ok() {
+                    Some(start..end)
+                }
+                else {
+                    self.line = into_string(mmap[start..end].to_vec());
+                    None
+                };
+                end - start
+            },
+        }
+    }
+
+    /// Returns the last line read, including its newline.
+    pub fn current_line(&self) -> &str {
+        current_line(&self.source, &self.line)
+    }
+
+    /// Reads the file through a memory mapping instead of buffered reads, from the current position. Lines
+    /// are then located with a fast newline search, and given to the mapper and records straight from the
+    /// mapping, without being copied into **line**. The file should not be modified while being read. Only
+    /// available with the `mmap` feature.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rbf::record::AsciiMode;
+    /// use rbf::layout::Layout;
+    /// use rbf::reader::Reader;
+    /// use rbf::index::LineIndex;
+    ///
+    /// let layout = Layout::<AsciiMode>::new("./tests/test.xml");
+    /// let mapper = Box::new(|x: &str| x[0..2].to_string());
+    /// let mut reader = Reader::<AsciiMode>::new("./tests/test_ascii.data", layout, mapper);
+    /// reader.set_mmap().unwrap();
+    ///
+    /// // random access works the same
+    /// assert_eq!(reader.last_record().unwrap().name, "DP");
+    /// let index = LineIndex::build("./tests/test_ascii.data", &reader.mapper, 100).unwrap();
+    /// reader.set_index(index);
+    /// assert!(reader.seek_line(4));
+    /// assert_eq!(reader.next().unwrap().get_value("N3"), "333");
+    /// assert!(reader.current_line().starts_with("NB"));
+    /// ```
+    #[cfg(feature = "mmap")]
+    pub fn set_mmap(&mut self) -> io::Result<()> {
+        let file = File::open(&self.rbf_file)?;
+
+        // the mapping is only read, and the file is not expected to change while reading it
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        // the last line read, if any, is still in the line buffer
+        self.source = Source::Mapped(mmap, None);
+        Ok(())
+    }
+
+    /// Calls `callback` every N lines or bytes read (according to `step`), and once when end of file is
+    /// reached, to report reading progress.
+    pub fn set_progress(&mut self, step: ProgressStep, callback: ProgressCallback) {
+        self.progress = Some((step, callback));
+        self.next_report = 0;
+        self.reported_done = false;
+    }
+
+    // reports progress when the next step is reached
+    fn check_progress(&mut self) {
+        if let Some((step, _)) = self.progress {
+            self.start.get_or_insert_with(Instant::now);
+
+            let (current, n) = match step {
+                ProgressStep::Lines(n) => (self.nblines_read, n),
+                ProgressStep::Bytes(n) => (self.bytes_read, n),
+            };
+            if current >= self.next_report {
+                self.next_report = current + n.max(1);
+                self.report(false);
+            }
+        }
+    }
+
+    // calls the progress callback, if any
+    fn report(&mut self, done: bool) {
+        if let Some((_, ref mut callback)) = self.progress {
+            let progress = Progress {
+                file_size: self.file_size,
+                position: self.offset,
+                bytes_read: self.bytes_read,
+                nblines_read: self.nblines_read,
+                elapsed: self.start.map_or(Default::default(), |start| start.elapsed()),
+                records: &self.record_counts,
+                done,
+            };
+            callback(&progress);
+        }
     }
 
     /// Sets reader lazyness
     pub fn set_lazyness(&mut self, lazyness: ReaderLazyness) {
         self.lazyness = lazyness;
     }
+
+    /// When set, field values are not copied anymore into the **raw_value** and **str_value** field members,
+    /// which saves two allocations per field and per line. Values are then only available through
+    /// **Record::field_value()**, **Record::field_values()** or **Record::view()**.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rbf::record::AsciiMode;
+    /// use rbf::layout::Layout;
+    /// use rbf::reader::Reader;
+    ///
+    /// let layout = Layout::<AsciiMode>::new("./tests/test.xml");
+    /// let mapper = Box::new(|x: &str| x[0..2].to_string());
+    /// let mut reader = Reader::<AsciiMode>::new("./tests/test_ascii.data", layout, mapper);
+    ///
+    /// reader.set_zero_copy(true);
+    /// while let Some(rec) = reader.next() {
+    ///     if rec.name == "NB" {
+    ///         assert_eq!(rec.view().get_value("N3"), "333");
+    ///         assert_eq!(rec.field_value(3).value(), "333");
+    ///     }
+    /// }
+    /// ```
+    pub fn set_zero_copy(&mut self, zero_copy: bool) {
+        self.zero_copy = zero_copy;
+    }
+
+    /// Only returns records for which `filter` returns true. Other lines are read but skipped.
+    pub fn set_filter(&mut self, filter: RecordFilter<T>) {
+        self.filter = Some(filter);
+    }
+
+    /// Only returns records matching the query, which is first checked against the reader layout.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rbf::record::AsciiMode;
+    /// use rbf::layout::Layout;
+    /// use rbf::reader::Reader;
+    ///
+    /// let layout = Layout::<AsciiMode>::new("./tests/test.xml");
+    /// let mapper = Box::new(|x: &str| x[0..2].to_string());
+    /// let mut reader = Reader::<AsciiMode>::new("./tests/test_ascii.data", layout, mapper);
+    ///
+    /// reader.set_query("NB.N3 = 333").unwrap();
+    /// while let Some(rec) = reader.next() {
+    ///     assert_eq!(rec.name, "NB");
+    /// }
+    /// ```
+    pub fn set_query(&mut self, query: &str) -> Result<(), String>
+        where T: 'static
+    {
+        let query = Query::compile(query, &self.layout)?;
+        self.set_filter(Box::new(move |rec| query.matches(rec)));
+        Ok(())
+    }
  
 }
 
@@ -3,13 +3,13 @@
 //!
 //! # Examples
 //! ```rust
-//! use std::rc::Rc;
+//! use std::sync::Arc;
 //!
 //! use rbf::fieldtype::FieldDataType;
 //! use rbf::field::Field;
 //! use rbf::record::{ReadMode, UTF8Mode, Record};
 //!
-//! let ft1 = Rc::new(FieldDataType::new("I", "integer"));                  
+//! let ft1 = Arc::new(FieldDataType::new("I", "integer"));                  
 //! 
 //! let f1 = Field::from_length("FIELD1", "Description for field 1", &ft1, 10);
 //! let f2 = Field::from_length("FIELD2", "Description for field 2", &ft1, 10);
@@ -69,13 +69,15 @@
 //! assert_eq!(rec[3].value(), "δδδδδδδδδδ");  
 //! ```
 
+use std::cmp::max;
 use std::fmt;
+use std::mem;
+use std::collections::HashMap;
 use std::ops::{Index, IndexMut};
 use std::slice::{Iter, IterMut};
 use std::marker::PhantomData;
-use std::borrow::Cow;
 
-use field::{FieldCreationType, Field};
+use field::{FieldCreationType, Field, FieldValue};
 
 /// This allows to define a way to read either pure Ascii data or UTF-8 data. Because the way
 /// of slicing is not the same, it's much more efficient using Ascii.
@@ -84,38 +86,147 @@ pub struct UTF8Mode;
 
 /// This trait will be implemented by readers
 pub trait ReadMode {
-    fn set_value(&mut self, value: &str); 
+    /// Sets the record value without copying field values: the line is kept in the record buffer, and each
+    /// field only holds the byte range of its value. Values are then accessed with **field_value()**,
+    /// **field_values()** or **view()**.
+    fn parse(&mut self, value: &str);
+
+    /// Sets the record value (which is equivalent to setting all fields), also copying each value into the
+    /// **raw_value** and **str_value** field members.
+    fn set_value(&mut self, value: &str);
+
+    /// Sets the byte range of each field within `line`, which is neither copied nor padded. Returns
+    /// **false** when `line` is too short to hold all fields.
+    fn locate(&mut self, line: &str) -> bool;
 }
 
 /// Implement Ascii read mode
 impl ReadMode for Record<AsciiMode> {
-    /// Sets the record value (which is equivalent to setting all fields).
+    fn parse(&mut self, value: &str) {
+        self.load(value, Self::locate);
+    }
+
     fn set_value(&mut self, value: &str) {
-        let s = self.adjust_value(value);       
+        self.parse(value);
+        self.copy_values();
+    }
+
+    fn locate(&mut self, line: &str) -> bool {
+        if line.len() < self.calculated_length {
+            return false;
+        }
 
-        // setting record value is setting value for all fields/records composing the record
+        // chars are bytes
         for f in &mut self.flist {
-            let r = f.lower_offset..f.upper_offset+1;
-            f.set_value(&s[r]);
-        } 
+            f.byte_range = f.lower_offset..f.upper_offset+1;
+        }
+        true
     }
 }
 
 /// Implement UTF-8 read mode
 impl ReadMode for Record<UTF8Mode> {
-    /// Sets the record value (which is equivalent to setting all fields).
+    fn parse(&mut self, value: &str) {
+        self.load(value, Self::locate);
+    }
+
     fn set_value(&mut self, value: &str) {
-        let s = self.adjust_value(value);
+        self.parse(value);
+        self.copy_values();
+    }
 
-        // this is made for UTF-8 strings
+    fn locate(&mut self, line: &str) -> bool {
+        // no multi-byte char: same as Ascii mode
+        if line.is_ascii() {
+            if line.len() < self.calculated_length {
+                return false;
+            }
+            for f in &mut self.flist {
+                f.byte_range = f.lower_offset..f.upper_offset+1;
+            }
+            return true;
+        }
+
+        // scan the line once to map each char index to its byte offset, last offset being the end of line
+        self.char_offsets.clear();
+        self.char_offsets.extend(line.char_indices().map(|(i, _)| i));
+        if self.char_offsets.len() < self.calculated_length {
+            return false;
+        }
+        self.char_offsets.push(line.len());
+
+        // each field is then sliced in O(1)
         for f in &mut self.flist {
-            let fvalue: String = s.chars().skip(f.lower_offset).take(f.length).collect();
-            f.set_value(&fvalue);
-        }         
+            f.byte_range = self.char_offsets[f.lower_offset]..self.char_offsets[f.upper_offset+1];
+        }
+        true
     }
 }
 
+/// A record together with the line its field values are borrowed from, which can be the record own
+/// line buffer (see **Record::view()**) or any other string (see **Record::view_of()**).
+pub struct RecordView<'a, T: 'a> {
+    /// record definition, holding field byte ranges
+    pub rec: &'a Record<T>,
+    /// line field values are borrowed from
+    pub line: &'a str,
+}
 
+impl<'a, T> RecordView<'a, T> {
+    /// Returns the i-th field with its value.
+    ///
+    /// #panics
+    /// If `i` is out of bounds.
+    pub fn field_value(&self, i: usize) -> FieldValue<'a> {
+        let f = &self.rec[i];
+        FieldValue { field: f, raw: &self.line[f.byte_range.clone()] }
+    }
+
+    /// Returns an iterator on all fields with their values.
+    pub fn field_values(&self) -> impl Iterator<Item = FieldValue<'a>> + 'a {
+        let line = self.line;
+        self.rec.flist.iter().map(move |f| FieldValue { field: f, raw: &line[f.byte_range.clone()] })
+    }
+
+    /// Returns the blank-stripped value of a field when there's only one field matching the field name.
+    ///
+    /// #panics
+    /// If `fname` is not found.
+    pub fn get_value(&self, fname: &str) -> &'a str {
+        self.get_value_with_index(fname, 0)
+    }
+
+    /// Returns the blank-stripped value of the i-th field matching the field name (starting from 0).
+    ///
+    /// #panics
+    /// If `fname` is not found.
+    pub fn get_value_with_index(&self, fname: &str, i: usize) -> &'a str {
+        // check for key existence
+        let positions = match self.rec.name_index.get(fname) {
+            Some(positions) => positions,
+            None => panic!("Key {} not found in record {}", fname, self.rec.name),
+        };
+
+        // check also index
+        match positions.get(i) {
+            Some(&index) => self.field_value(index).value(),
+            None => panic!("Index {} is out of bound for field {} in record {}", i, fname, self.rec.name),
+        }
+    }
+
+    /// Returns the blank-stripped value of the field pointed to by the handle.
+    pub fn value_of(&self, handle: FieldHandle) -> &'a str {
+        self.field_value(handle.0).value()
+    }
+}
+
+/// Lists all field name and values, like for a Record.
+impl<'a, T> fmt::Display for RecordView<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s: Vec<_> = self.field_values().map(|fv| format!("{}", fv)).collect();
+        write!(f, "({})", s.join(","))
+    }
+}
 
 /// Macro which builds a vector of Record data fields.
 ///
@@ -147,6 +258,37 @@ pub struct Record<T> {
     pub calculated_length: usize,
     /// Reader mode struct, just a place holder
     pub reader_mode: PhantomData<T>,
+    /// Line buffer holding the last value set, which field values are borrowed from
+    line: String,
+    /// Byte offset of each char of the line buffer (only used for UTF-8 data)
+    char_offsets: Vec<usize>,
+    /// Positions in **flist** of the fields having a given name, kept in sync by **push()**, **retain()**
+    /// and **remove()**. Fields should not be renamed through **flist**.
+    name_index: HashMap<String, Vec<usize>>,
+}
+
+/// A field of a record, resolved by its name once and for all. When the same field is accessed
+/// for each line read, this avoids looking up the field name again.
+///
+/// # Examples
+/// ```rust
+/// use rbf::record::{ReadMode, AsciiMode};
+///
+/// let mut rec = ::rbf::record::setup::set_up_by_length::<AsciiMode>();
+/// let h = rec.handle_with_index("FIELD2", 1).unwrap();
+///
+/// rec.parse("AAAAAAAAAABBBBBBBBBBCCCCCCCCCCCCCCCCCCCCDDDDDDDDDD");
+/// assert_eq!(rec.view().value_of(h), "DDDDDDDDDD");
+/// assert_eq!(h.index(), 3);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FieldHandle(usize);
+
+impl FieldHandle {
+    /// Returns the index of the field within its record.
+    pub fn index(&self) -> usize {
+        self.0
+    }
 }
 
 impl<T> Record<T> {
@@ -175,6 +317,9 @@ impl<T> Record<T> {
             flist: Vec::new(),
             calculated_length: 0,
             reader_mode: PhantomData,
+            line: String::new(),
+            char_offsets: Vec::new(),
+            name_index: HashMap::new(),
         }        
     }
 
@@ -200,16 +345,19 @@ impl<T> Record<T> {
                 self.calculated_length += field.length;                      
             },
             FieldCreationType::ByOffset => {
-                // now length is the greastest bound value
-                self.calculated_length = field.upper_offset+1;              
+                // now length is the greastest bound value, unless a previous field goes further
+                self.calculated_length = max(self.calculated_length, field.upper_offset+1);
             }
         };
             
-        // get last field having the same name (if any)
-        match self.get(&field.name) {
-            Some(ref mut v) => { field.multiplicity = v.pop().unwrap().multiplicity + 1; }
-            None => ()
-        }
+        // get last field having the same name (if any). A field taken from another record (e.g.: a base
+        // record) doesn't keep its multiplicity
+        let positions = self.name_index.entry(field.name.clone()).or_default();
+        field.multiplicity = match positions.last() {
+            Some(&last) => self.flist[last].multiplicity + 1,
+            None => 0,
+        };
+        positions.push(length);
 
         // finally, save Field struct
         self.flist.push(field);
@@ -218,7 +366,7 @@ impl<T> Record<T> {
 
     /// Tests whether a Record contains a Field by giving its name.
     pub fn contains_field(&self, fname: &str) -> bool {
-        self.flist.iter().any(|f| f.name == fname)
+        self.name_index.contains_key(fname)
     }
 
     /// Returns the number of fields in the record.
@@ -248,43 +396,135 @@ impl<T> Record<T> {
     /// Returns a vector of fields matching the field name (this returns a vector 
     /// because a Field could appear more than once in a Record).
     pub fn get(&self, fname: &str) -> Option<Vec<&Field>> {
-        self.filter(|f| f.name == fname)
+        self.name_index.get(fname).map(|positions| positions.iter().map(|&i| &self.flist[i]).collect())
+    }
+
+    /// Returns the indices of the fields matching the field name, without allocating. The returned
+    /// slice is empty if the field is not found.
+    pub fn indices_of(&self, fname: &str) -> &[usize] {
+        self.name_index.get(fname).map_or(&[], |positions| positions.as_slice())
+    }
+
+    /// Returns a handle on the first field matching the field name.
+    pub fn handle(&self, fname: &str) -> Option<FieldHandle> {
+        self.handle_with_index(fname, 0)
+    }
+
+    /// Returns a handle on the i-th field matching the field name (starting from 0), when there're
+    /// duplicated fields.
+    pub fn handle_with_index(&self, fname: &str, i: usize) -> Option<FieldHandle> {
+        self.indices_of(fname).get(i).map(|&index| FieldHandle(index))
+    }
+
+    /// Returns the value of the field pointed to by the handle.
+    ///
+    /// #panics
+    /// If the handle is out of bounds, e.g. when it was obtained before removing fields.
+    pub fn value_of(&self, handle: FieldHandle) -> &str {
+        self[handle.0].value()
     }
 
     /// Only keeps fields matching the predicate.
     pub fn retain<F>(&mut self, pred: F)
         where F: Fn(&Field) -> bool
     {
-        self.flist.retain(|e| pred(e))
+        self.flist.retain(|e| pred(e));
+        self.build_name_index();
     }
 
     /// Removes fields matching the predicate.
     pub fn remove<F>(&mut self, pred: F)
         where F: Fn(&Field) -> bool
     {
-        self.flist.retain(|e| !pred(e))
+        self.flist.retain(|e| !pred(e));
+        self.build_name_index();
+    }
+
+    // rebuilds the name index from the field list
+    fn build_name_index(&mut self) {
+        self.name_index.clear();
+        for (i, f) in self.flist.iter().enumerate() {
+            self.name_index.entry(f.name.clone()).or_default().push(i);
+        }
     }    
 
     /// Returns the record value (concatenation of all field values).
     pub fn value(&self) -> String {
-        let v: Vec<_> = self.flist.iter().map(|f| f.raw_value.clone()).collect();   
+        let v: Vec<_> = self.flist.iter().map(|f| f.raw_value.clone()).collect();
         v.join("")
     }
 
+    /// Returns the line buffer the record value was last set from, padded with blanks to the record length.
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+
+    /// Builds a record line from field values given in field order. Each value is padded to its field according
+    /// to the field type, and positions not covered by a value are blanks.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rbf::record::AsciiMode;
+    ///
+    /// let rec = ::rbf::record::setup::set_up_by_offset::<AsciiMode>();
+    /// let line = rec.format_line(vec!["A", "B"]);
+    /// assert_eq!(line.len(), rec.calculated_length);
+    /// assert!(line.starts_with("    A         B    "));
+    /// ```
+    pub fn format_line<'v, I>(&self, values: I) -> String
+        where I: IntoIterator<Item = &'v str>
+    {
+        let mut line = vec![' '; self.calculated_length];
+
+        for (f, value) in self.flist.iter().zip(values) {
+            for (i, c) in f.pad(value).chars().enumerate() {
+                line[f.lower_offset + i] = c;
+            }
+        }
+
+        line.into_iter().collect()
+    }
+
+    /// Returns the record with its values borrowed from the record line buffer.
+    pub fn view(&self) -> RecordView<'_, T> {
+        RecordView { rec: self, line: &self.line }
+    }
+
+    /// Sets field byte ranges within `line`, and returns the record with its values borrowed from `line`.
+    /// The line is only copied into the record buffer when it's too short to hold all fields.
+    pub fn view_of<'a>(&'a mut self, line: &'a str) -> RecordView<'a, T>
+        where Self: ReadMode
+    {
+        let line = line.trim_end_matches(['\n', '\r']);
+        if self.locate(line) {
+            return RecordView { rec: self, line };
+        }
+
+        self.parse(line);
+        self.view()
+    }
+
+    /// Returns the i-th field with its value borrowed from the record line.
+    ///
+    /// #panics
+    /// If `i` is out of bounds.
+    pub fn field_value(&self, i: usize) -> FieldValue<'_> {
+        self.view().field_value(i)
+    }
+
+    /// Returns an iterator on all fields with their values borrowed from the record line.
+    pub fn field_values<'a>(&'a self) -> impl Iterator<Item = FieldValue<'a>> + 'a {
+        self.view().field_values()
+    }
+
     /// Returns the value from a field when it's sure there's only one field (no duplication) matching the
-    /// field name. 
+    /// field name. When the record was only parsed (see **ReadMode::parse()**), use **view().get_value()**
+    /// instead.
     ///
     /// #panics
     /// If `fname` is not found.
     pub fn get_value(&self, fname: &str) -> &str {
-        // check for key existence
-        let fields = match self.get(fname) {
-            Some(f) => f,
-            None => panic!("Key {} not found in record {}", fname, self.name),
-        };
-
-        // safely returns value
-        fields[0].value()
+        self.get_value_with_index(fname, 0)
     } 
 
     /// Returns the value from a field when there're duplicated fields matching the
@@ -294,30 +534,44 @@ impl<T> Record<T> {
     /// If `fname` is not found.    
     pub fn get_value_with_index(&self, fname: &str, i: usize) -> &str {
         // check for key existence
-        let fields = match self.get(fname) {
-            Some(f) => f,
+        let positions = match self.name_index.get(fname) {
+            Some(positions) => positions,
             None => panic!("Key {} not found in record {}", fname, self.name),
         };
 
         // check also index
-        let f = match fields.get(i) {
-            Some(f) => f,
+        match positions.get(i) {
+            Some(&index) => self.flist[index].value(),
             None => panic!("Index {} is out of bound for field {} in record {}", i, fname, self.name),
-        };
-
-        f.value()
+        }
     } 
 
-    /// Adjusts the line value to the record length. Use Cow to avoid string duplication
-    /// when the value is not padded with blanks.
-    fn adjust_value<'a>(&self, value: &'a str) -> Cow<'a, str> {
-        // if shorter, left-pad with blanks
-        if value.len() < self.calculated_length {
-            //format!("{:length$} ", value, length=self.calculated_length)
-            Cow::Owned(format!("{:length$} ", value, length=self.calculated_length))
+    /// Copies the line into the record buffer without its newline, and sets field byte ranges within the
+    /// buffer. The buffer is reused from one line to another.
+    fn load<F>(&mut self, value: &str, locate: F)
+        where F: Fn(&mut Self, &str) -> bool
+    {
+        let mut line = mem::take(&mut self.line);
+        line.clear();
+        line.push_str(value.trim_end_matches(['\n', '\r']));
+
+        // if shorter, right-pad with blanks
+        if !locate(self, &line) {
+            for _ in line.chars().count()..self.calculated_length {
+                line.push(' ');
+            }
+            locate(self, &line);
         }
-        else {
-            Cow::Borrowed(value)
+
+        self.line = line;
+    }
+
+    /// Copies field values from the record line into each field **raw_value** and **str_value**.
+    fn copy_values(&mut self) {
+        let line = &self.line;
+        for f in &mut self.flist {
+            let r = f.byte_range.clone();
+            f.set_value(&line[r]);
         }
     }
 
@@ -429,6 +683,7 @@ impl<T> Clone for Record<T> {
         for f in self {
             cloned.push(f.clone());
         }                                        
+        cloned.line = self.line.clone();
 
         cloned
     }
@@ -443,7 +698,7 @@ impl<'a, T> From<(&'a str, &'a str, usize)> for Record<T> {
 
 // module to setup test data for record
 pub mod setup {
-    use std::rc::Rc;
+    use std::sync::Arc;
 
     use fieldtype::FieldDataType;
     use field::Field;
@@ -451,7 +706,7 @@ pub mod setup {
 
     // this fn sets up the relevant data for testing a record, when fields are contiguous
     pub fn set_up_by_length<T>() -> Record<T> {
-        let ft1 = Rc::new(FieldDataType::new("I", "integer"));                  
+        let ft1 = Arc::new(FieldDataType::new("I", "integer"));                  
         
         let f1 = Field::from_length("FIELD1", "Description for field 1", &ft1, 10);
         let f2 = Field::from_length("FIELD2", "Description for field 2", &ft1, 10);
@@ -470,7 +725,7 @@ pub mod setup {
 
     // this fn sets up the relevant data for testing a record, when fields are not-contiguous
     pub fn set_up_by_offset<T>() -> Record<T> {
-        let ft1 = Rc::new(FieldDataType::new("I", "integer"));                  
+        let ft1 = Arc::new(FieldDataType::new("I", "integer"));                  
         
         let f1 = Field::from_offset("FIELD1", "Description for field 1", &ft1, 5, 9);
         let f2 = Field::from_offset("FIELD2", "Description for field 2", &ft1, 15, 19);
@@ -490,7 +745,7 @@ pub mod setup {
     // this fn sets up the relevant data for testing a hug record
     #[allow(unused_variables)]
     pub fn set_up_by_length_huge<T>(size: usize) -> Record<T> {
-        let ft1 = Rc::new(FieldDataType::new("I", "integer")); 
+        let ft1 = Arc::new(FieldDataType::new("I", "integer")); 
         let mut rec = Record::<T>::new("RECORD1", "Description for record 1", 0);
 
         for i in 0..size {
@@ -630,6 +885,118 @@ mod tests {
         assert_eq!(vector_of!(rec, raw_value), vec!["αααααααααα", "ββββββββββ", "γγγγγγγγγγγγγγγγγγγγ", "δδδδδδδδδδ"]);
     }    
 
+    #[test]
+    fn record_parse() {
+
+        // setup data
+        let mut rec = ::record::setup::set_up_by_length::<UTF8Mode>();
+
+        // field values are borrowed from the line, and not copied
+        rec.parse("  αααα    ββββββββββγγγγγγγγγγ\n");
+        assert_eq!(rec[0].raw_value, "");
+        assert_eq!(rec.field_value(0).raw_value(), "  αααα    ");
+        assert_eq!(rec.field_value(0).value(), "αααα");
+        assert_eq!(rec.view().get_value("FIELD3"), "γγγγγγγγγγ");
+        assert_eq!(rec.view().get_value_with_index("FIELD2", 1), "");
+
+        let v: Vec<_> = rec.field_values().map(|fv| fv.value()).collect();
+        assert_eq!(v, vec!["αααα", "ββββββββββ", "γγγγγγγγγγ", ""]);
+        assert_eq!(rec.line().chars().count(), 50);
+        assert_eq!(format!("{}", rec.view()), "(FIELD1='αααα',FIELD2='ββββββββββ',FIELD3='γγγγγγγγγγ',FIELD2='')");
+
+        // set_value() also copies values
+        rec.set_value("AAAAAAAAAABBBBBBBBBB");
+        assert_eq!(rec[1].raw_value, "BBBBBBBBBB");
+        assert_eq!(rec.clone().get_value("FIELD2"), "BBBBBBBBBB");
+    }
+
+    #[test]
+    fn record_set_field_value() {
+
+        // setup data
+        let mut rec = ::record::setup::set_up_by_length::<AsciiMode>();
+        rec.set_value("AAAAAAAAAABBBBBBBBBBCCCCCCCCCCCCCCCCCCCCDDDDDDDDDD");
+
+        // a value set on a field is seen from the record, but not from its line
+        rec[2].set_value("XXXXXXXXXXXXXXXXXXXX");
+        assert_eq!(rec.get_value("FIELD3"), "XXXXXXXXXXXXXXXXXXXX");
+        assert_eq!(rec.value(), "AAAAAAAAAABBBBBBBBBBXXXXXXXXXXXXXXXXXXXXDDDDDDDDDD");
+        assert_eq!(format!("{}", rec), "(FIELD1='AAAAAAAAAA',FIELD2='BBBBBBBBBB',FIELD3='XXXXXXXXXXXXXXXXXXXX',FIELD2='DDDDDDDDDD')");
+        assert_eq!(rec.view().get_value("FIELD3"), "CCCCCCCCCCCCCCCCCCCC");
+    }
+
+    #[test]
+    fn record_push_from_other_record() {
+        use std::sync::Arc;
+        use field::Field;
+        use fieldtype::FieldDataType;
+        use record::Record;
+
+        // fields of a base record, the second FIELD2 having a multiplicity of 1
+        let base = ::record::setup::set_up_by_length::<AsciiMode>();
+
+        let mut rec = Record::<AsciiMode>::new("RECORD2", "Record with a FIELD2 only", 0);
+        rec.push(base[3].clone());
+        assert_eq!(rec[0].multiplicity, 0);
+        assert_eq!((rec[0].lower_offset, rec[0].upper_offset), (0, 9));
+
+        // a field within the record doesn't shorten it
+        let ft = Arc::new(FieldDataType::new("I", "integer"));
+        rec.push(Field::from_offset("FIELD5", "Field within FIELD2", &ft, 3, 5));
+        assert_eq!(rec.calculated_length, 10);
+    }
+
+    #[test]
+    fn record_format_line() {
+        use std::sync::Arc;
+        use field::Field;
+        use fieldtype::{FieldDataType, Justify};
+        use record::Record;
+
+        let mut ft = FieldDataType::new("N", "decimal");
+        ft.set_pad_char('0');
+        ft.set_justify(Justify::Right);
+        let num = Arc::new(ft);
+        let string = Arc::new(FieldDataType::new("S", "string"));
+
+        let mut rec = Record::<AsciiMode>::new("RECORD1", "Record with a gap", 0);
+        rec.push(Field::from_length("ID", "Record ID", &string, 2));
+        rec.push(Field::from_offset("AMOUNT", "Amount", &num, 5, 10));
+        rec.push(Field::from_length("NAME", "Name", &string, 5));
+
+        let line = rec.format_line(vec!["R1", "12.5", "αβ"]);
+        assert_eq!(line, "R1  0012.5αβ   ");
+
+        // read back
+        rec.parse(&line);
+        assert_eq!(rec.view().get_value("AMOUNT"), "12.5");
+        assert_eq!(rec.view().get_value("NAME"), "αβ");
+    }
+
+    #[test]
+    fn record_name_index() {
+
+        // setup data
+        let mut rec = ::record::setup::set_up_by_length::<AsciiMode>();
+        assert_eq!(rec.indices_of("FIELD2"), &[1, 3]);
+        assert_eq!(rec.get("FIELD2").unwrap()[1].multiplicity, 1);
+        assert!(rec.indices_of("FOO").is_empty());
+
+        // index is kept in sync when removing fields
+        rec.remove(|f| f.name == "FIELD1");
+        assert!(!rec.contains_field("FIELD1"));
+        assert_eq!(rec.indices_of("FIELD2"), &[0, 2]);
+
+        rec.set_value("AAAAAAAAAABBBBBBBBBBCCCCCCCCCCCCCCCCCCCCDDDDDDDDDD");
+        let h = rec.handle_with_index("FIELD2", 1).unwrap();
+        assert_eq!(rec.value_of(h), "DDDDDDDDDD");
+        assert_eq!(rec.get_value("FIELD3"), "CCCCCCCCCCCCCCCCCCCC");
+
+        rec.retain(|f| f.name != "FIELD2");
+        assert!(rec.handle("FIELD2").is_none());
+        assert_eq!(rec.handle("FIELD3").unwrap().index(), 0);
+    }
+
     #[test]
     #[should_panic]
     #[allow(unused_variables)]
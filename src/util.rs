@@ -1,4 +1,6 @@
 use std::collections::HashMap;
+use std::io::{self, BufRead};
+use std::mem;
 
 /// Converts a comma-separated string into a vector of trimmed string refs.
 /// # Example
@@ -35,3 +37,68 @@ pub fn into_rec_map(s: &str) -> HashMap<&str, Vec<&str>> {
     rec_map
 } 
 
+/// Converts bytes read from a data file to a string. Invalid UTF-8 sequences are replaced with U+FFFD: this is
+/// the case of COBOL HIGH-VALUES, 0xFF bytes never being valid UTF-8, which are each read as one U+FFFD char.
+/// # Example
+/// ```rust
+/// use rbf::util::into_string;
+///
+/// assert_eq!(into_string(b"AB".to_vec()), "AB");
+/// assert_eq!(into_string(b"A\xFF\xFFB".to_vec()), "A\u{FFFD}\u{FFFD}B");
+/// ```
+pub fn into_string(bytes: Vec<u8>) -> String {
+    String::from_utf8(bytes).unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())
+}
+
+/// Reads a line of a data file into `line`, replacing its content. Same as **BufRead::read_line()**, but invalid
+/// UTF-8 sequences are replaced as with **into_string()** instead of failing. Returns the number of bytes read.
+pub fn read_line<R: BufRead>(reader: &mut R, line: &mut String) -> io::Result<usize> {
+    // reuse the line buffer
+    let mut bytes = mem::take(line).into_bytes();
+    bytes.clear();
+
+    let length = reader.read_until(b'\n', &mut bytes)?;
+    *line = into_string(bytes);
+    Ok(length)
+}
+
+
+// module to setup test files
+pub mod setup {
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::process;
+
+    /// A file or directory path in the temp directory, removed when dropped, even if the test panics.
+    pub struct TempPath {
+        path: PathBuf,
+    }
+
+    impl TempPath {
+        /// Builds the path from `name` (e.g. `"export_csv"` or `"layout.xml"`) and the process ID, so that
+        /// concurrent test runs don't share files. Nothing is created.
+        pub fn new(name: &str) -> TempPath {
+            TempPath { path: env::temp_dir().join(format!("rbf_{}_{}", process::id(), name)) }
+        }
+
+        pub fn path(&self) -> &Path {
+            &self.path
+        }
+
+        pub fn as_str(&self) -> &str {
+            self.path.to_str().unwrap()
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            if self.path.is_dir() {
+                let _ = fs::remove_dir_all(&self.path);
+            }
+            else {
+                let _ = fs::remove_file(&self.path);
+            }
+        }
+    }
+}
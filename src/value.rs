@@ -0,0 +1,282 @@
+//! Represents a field value converted to its underlying base type. Conversion is made according to the
+//! `BaseDataType` of the field type: integers and decimals are parsed as numbers, dates and times are
+//! decoded using the format given in the field type definition.
+//!
+//! Only a subset of the `strftime()` directives is recognized when decoding dates and times:
+//!
+//!  * `%Y`: year with 4 digits
+//!  * `%y`: year with 2 digits (00-69 are mapped to 20xx, 70-99 to 19xx)
+//!  * `%m`: month with 2 digits
+//!  * `%b`: abbreviated month name, whatever the case (e.g. `JAN`)
+//!  * `%d`: day of month with 2 digits
+//!  * `%H`: hour with 2 digits
+//!  * `%M`: minutes with 2 digits
+//!  * `%S`: seconds with 2 digits
+//!  * `%%`: a literal `%`
+//!
+//! Any other character is expected as is in the value. Without a format in the field type definition, dates
+//! are decoded using `%Y%m%d` and times using `%H%M%S`.
+//!
+//! # Examples
+//! ```rust
+//! use rbf::fieldtype::FieldDataType;
+//! use rbf::value::{TypedValue, Date};
+//!
+//! let mut ft = FieldDataType::new("D", "date");
+//! ft.set_date_format("%Y%m%d");
+//!
+//! assert_eq!(ft.convert("20170825").unwrap(), TypedValue::Date(Date { year: 2017, month: 8, day: 25 }));
+//! assert!(ft.convert("2017-08-25").is_err());
+//! ```
+use std::fmt;
+
+/// A calendar date.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+/// A time of day.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Time {
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+/// A field value, converted according to its field type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypedValue {
+    String(String),
+    Integer(i64),
+    Decimal(f64),
+    Date(Date),
+    Time(Time),
+}
+
+impl Date {
+    /// Tests whether the year is a leap year.
+    pub fn is_leap_year(year: i32) -> bool {
+        (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+    }
+
+    /// Returns the number of days in a given month.
+    pub fn days_in_month(year: i32, month: u32) -> u32 {
+        match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 => if Date::is_leap_year(year) { 29 } else { 28 },
+            _ => 0,
+        }
+    }
+
+    /// Decodes a date from `value` according to `format`. Returns `None` if the value
+    /// doesn't match the format or is not a valid calendar date.
+    pub fn parse(value: &str, format: &str) -> Option<Date> {
+        let parts = parse_with_format(value, format)?;
+        let (year, month, day) = (parts.year?, parts.month?, parts.day?);
+
+        if !(1..=12).contains(&month) || day < 1 || day > Date::days_in_month(year, month) {
+            return None;
+        }
+
+        Some(Date { year, month, day })
+    }
+
+    /// Returns the number of days elapsed since 1970-01-01 (negative before).
+    pub fn days_since_epoch(&self) -> i64 {
+        // shift year so that it starts in March, which pushes leap day at the end
+        let y = if self.month <= 2 { self.year as i64 - 1 } else { self.year as i64 };
+        let m = self.month as i64;
+        let d = self.day as i64;
+
+        let era = (if y >= 0 { y } else { y - 399 }) / 400;
+        let yoe = y - era * 400;
+        let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+        era * 146097 + doe - 719468
+    }
+}
+
+impl Time {
+    /// Decodes a time from `value` according to `format`. Returns `None` if the value
+    /// doesn't match the format or is not a valid time.
+    pub fn parse(value: &str, format: &str) -> Option<Time> {
+        let parts = parse_with_format(value, format)?;
+        let (hour, minute, second) = (parts.hour?, parts.minute.unwrap_or(0), parts.second.unwrap_or(0));
+
+        if hour > 23 || minute > 59 || second > 59 {
+            return None;
+        }
+
+        Some(Time { hour, minute, second })
+    }
+
+    /// Returns the number of seconds elapsed since midnight.
+    pub fn seconds_since_midnight(&self) -> u32 {
+        self.hour * 3600 + self.minute * 60 + self.second
+    }
+}
+
+// ISO 8601 representation
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+// ISO 8601 representation
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02}:{:02}:{:02}", self.hour, self.minute, self.second)
+    }
+}
+
+impl fmt::Display for TypedValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TypedValue::String(ref s) => write!(f, "{}", s),
+            TypedValue::Integer(i) => write!(f, "{}", i),
+            TypedValue::Decimal(d) => write!(f, "{}", d),
+            TypedValue::Date(ref d) => write!(f, "{}", d),
+            TypedValue::Time(ref t) => write!(f, "{}", t),
+        }
+    }
+}
+
+// all date & time components found when decoding a value
+#[derive(Default)]
+struct DateTimeParts {
+    year: Option<i32>,
+    month: Option<u32>,
+    day: Option<u32>,
+    hour: Option<u32>,
+    minute: Option<u32>,
+    second: Option<u32>,
+}
+
+// reads exactly `n` ascii digits from `value` starting at `pos`
+fn take_digits(value: &[u8], pos: &mut usize, n: usize) -> Option<u32> {
+    if *pos + n > value.len() {
+        return None;
+    }
+
+    let mut result = 0u32;
+    for b in &value[*pos..*pos + n] {
+        if !b.is_ascii_digit() {
+            return None;
+        }
+        result = result * 10 + (b - b'0') as u32;
+    }
+
+    *pos += n;
+    Some(result)
+}
+
+// abbreviated month names, for %b
+const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+
+// reads an abbreviated month name from `value` starting at `pos`
+fn take_month(value: &[u8], pos: &mut usize) -> Option<u32> {
+    let name = value.get(*pos..*pos + 3)?;
+    let month = MONTHS.iter().position(|m| name.eq_ignore_ascii_case(m.as_bytes()))?;
+
+    *pos += 3;
+    Some(month as u32 + 1)
+}
+
+// walks through the format and the value simultaneously to extract date & time parts
+fn parse_with_format(value: &str, format: &str) -> Option<DateTimeParts> {
+    let mut parts = DateTimeParts::default();
+    let bytes = value.as_bytes();
+    let mut pos = 0;
+    let mut chars = format.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            // literal char should be found as is
+            let mut buf = [0u8; 4];
+            let lit = c.encode_utf8(&mut buf).as_bytes();
+            if !bytes[pos..].starts_with(lit) {
+                return None;
+            }
+            pos += lit.len();
+            continue;
+        }
+
+        match chars.next()? {
+            'Y' => parts.year = Some(take_digits(bytes, &mut pos, 4)? as i32),
+            'y' => {
+                let y = take_digits(bytes, &mut pos, 2)? as i32;
+                parts.year = Some(if y < 70 { 2000 + y } else { 1900 + y });
+            }
+            'm' => parts.month = Some(take_digits(bytes, &mut pos, 2)?),
+            'b' => parts.month = Some(take_month(bytes, &mut pos)?),
+            'd' => parts.day = Some(take_digits(bytes, &mut pos, 2)?),
+            'H' => parts.hour = Some(take_digits(bytes, &mut pos, 2)?),
+            'M' => parts.minute = Some(take_digits(bytes, &mut pos, 2)?),
+            'S' => parts.second = Some(take_digits(bytes, &mut pos, 2)?),
+            '%' => {
+                if bytes.get(pos) != Some(&b'%') {
+                    return None;
+                }
+                pos += 1;
+            }
+            _ => return None,
+        }
+    }
+
+    // the whole value should have been consumed
+    if pos != bytes.len() {
+        return None;
+    }
+
+    Some(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use value::{Date, Time};
+
+    #[test]
+    fn date_parse() {
+        assert_eq!(Date::parse("20170825", "%Y%m%d"), Some(Date { year: 2017, month: 8, day: 25 }));
+        assert_eq!(Date::parse("25/08/17", "%d/%m/%y"), Some(Date { year: 2017, month: 8, day: 25 }));
+        assert_eq!(Date::parse("29-FEB-99", "%d-FEB-%y"), None);
+        assert_eq!(Date::parse("01-Aug-17", "%d-%b-%y"), Some(Date { year: 2017, month: 8, day: 1 }));
+        assert_eq!(Date::parse("01-AUX-17", "%d-%b-%y"), None);
+        assert_eq!(Date::parse("20000229", "%Y%m%d"), Some(Date { year: 2000, month: 2, day: 29 }));
+        assert_eq!(Date::parse("20170832", "%Y%m%d"), None);
+        assert_eq!(Date::parse("2017082", "%Y%m%d"), None);
+        assert_eq!(Date::parse("201708250", "%Y%m%d"), None);
+    }
+
+    #[test]
+    fn date_default_format() {
+        use fieldtype::FieldDataType;
+        use value::TypedValue;
+
+        // no format given in the field type definition
+        let ft = FieldDataType::new("D", "date");
+        assert_eq!(ft.convert("20170825").unwrap(), TypedValue::Date(Date { year: 2017, month: 8, day: 25 }));
+        assert!(ft.convert("25082017").is_err());
+    }
+
+    #[test]
+    fn date_epoch() {
+        assert_eq!(Date { year: 1970, month: 1, day: 1 }.days_since_epoch(), 0);
+        assert_eq!(Date { year: 2000, month: 3, day: 1 }.days_since_epoch(), 11017);
+        assert_eq!(Date { year: 1969, month: 12, day: 31 }.days_since_epoch(), -1);
+    }
+
+    #[test]
+    fn time_parse() {
+        assert_eq!(Time::parse("235959", "%H%M%S"), Some(Time { hour: 23, minute: 59, second: 59 }));
+        assert_eq!(Time::parse("12:30", "%H:%M"), Some(Time { hour: 12, minute: 30, second: 0 }));
+        assert_eq!(Time::parse("246000", "%H%M%S"), None);
+        assert_eq!(format!("{}", Time { hour: 1, minute: 2, second: 3 }), "01:02:03");
+    }
+}
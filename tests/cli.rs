@@ -0,0 +1,235 @@
+// tests of the rbf command-line tool
+#![cfg(feature = "cli")]
+extern crate rbf;
+use std::process::{Command, Output};
+use rbf::util::setup::TempPath;
+
+fn rbf(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rbf"))
+        .args(args)
+        .output()
+        .expect("couldn't run rbf")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn cli_count() {
+    let output = rbf(&["count", "tests/test.xml", "tests/test_ascii.data", "--mapper", "type:1 map:0..2"]);
+    assert!(output.status.success());
+
+    let s = stdout(&output);
+    assert!(s.contains("Input file has 743 lines"));
+    assert!(s.contains("Number of LL records = 257"));
+    assert!(s.contains("Number of NB records = 247"));
+
+    // only count selected records
+    let output = rbf(&["count", "tests/test.xml", "tests/test_ascii.data", "-m", "type:1 map:0..2", "-s", "NB:ID", "-f", "csv"]);
+    assert_eq!(stdout(&output), "record,count\nNB,247\n");
+}
+
+#[test]
+fn cli_head_tail() {
+    let output = rbf(&["head", "tests/test.xml", "tests/test_utf8.data", "-e", "utf8", "-m", "type:1 map:0..2", "-s", "GL:ID,G2", "-n", "2"]);
+    assert!(output.status.success());
+
+    let lines: Vec<_> = stdout(&output).lines().map(|l| l.to_string()).collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].ends_with(":GL:(ID='GL',G2='ββ')"));
+
+    let output = rbf(&["tail", "tests/test.xml", "tests/test_ascii.data", "-m", "type:1 map:0..2", "-s", "NB:ID,N3", "-n", "3", "-f", "csv"]);
+    let s = stdout(&output);
+    assert_eq!(s.lines().count(), 3);
+    assert!(s.lines().all(|l| l.ends_with(",NB,NB,333")));
+}
+
+#[test]
+fn cli_grep() {
+    let output = rbf(&["grep", "NB.N3 = 333 and N1 in (1, 2)", "tests/test.xml", "tests/test_ascii.data", "-m", "type:1 map:0..2", "-c"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "247\n");
+
+    // original lines with line numbers
+    let output = rbf(&["grep", "record = 'LL'", "tests/test.xml", "tests/test_ascii.data", "-m", "type:1 map:0..2", "-n"]);
+    let s = stdout(&output);
+    assert_eq!(s.lines().count(), 257);
+    assert!(s.starts_with("1:LLABBCCC"));
+
+    // selected fields
+    let output = rbf(&["grep", "F5[3] =~ '^D'", "tests/test.xml", "tests/test_ascii.data", "-m", "type:1 map:0..2", "-s", "DP:F5", "-f", "csv"]);
+    let s = stdout(&output);
+    assert_eq!(s.lines().count(), 238);
+    assert!(s.lines().all(|l| l.ends_with(",DP,AAAAA,BBBBB,CCCCC,DDDDD")));
+
+    // unknown field
+    let output = rbf(&["grep", "FOO > 1", "tests/test.xml", "tests/test_ascii.data", "-m", "type:1 map:0..2"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("field FOO not found in layout"));
+}
+
+#[test]
+fn cli_validate() {
+    // DP records are longer than declared, and FA is not a record ID
+    let output = rbf(&["validate", "tests/test.xml", "tests/test_ascii.data", "-m", "type:1 map:0..2", "-f", "csv"]);
+    assert_eq!(output.status.code(), Some(3));
+
+    let s = stdout(&output);
+    assert_eq!(s.lines().count(), 239);
+    assert_eq!(s.lines().filter(|l| l.contains("unknown record ID <FA>")).count(), 1);
+
+    // stop at first error
+    let output = rbf(&["validate", "tests/test.xml", "tests/test_ascii.data", "-m", "type:1 map:0..2", "-z", "stringent"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(stdout(&output).lines().count(), 1);
+
+    // valid records only
+    let output = rbf(&["validate", "tests/test.xml", "tests/test_ascii.data", "-m", "type:1 map:0..2", "-s", "LL:ID,W1; NB:ID,N1"]);
+    assert!(output.status.success());
+}
+
+#[test]
+fn cli_layout_show() {
+    let output = rbf(&["layout", "show", "tests/test.xml", "-f", "csv"]);
+    assert!(output.status.success());
+
+    let s = stdout(&output);
+    assert!(s.starts_with("record,index,field,description,type,length,start,end\nDP,0,ID,Record ID,A,2,1,2\n"));
+    assert_eq!(s.lines().count(), 1 + 5 + 25 + 27 + 10);
+}
+
+#[test]
+fn cli_progress() {
+    let output = rbf(&["count", "tests/test.xml", "tests/test_ascii.data", "-m", "type:1 map:0..2", "--progress"]);
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("Number of LL records = 257"));
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("100.0% 743 lines"));
+    assert!(stderr.ends_with("\n"));
+}
+
+#[test]
+fn cli_mmap() {
+    let args = ["grep", "record = 'LL'", "tests/test.xml", "tests/test_utf8.data", "-e", "utf8", "-m", "type:1 map:0..2", "-n"];
+    let output = rbf(&[&args[..], &["--mmap"]].concat());
+
+    if cfg!(feature = "mmap") {
+        assert!(output.status.success());
+        assert_eq!(stdout(&output), stdout(&rbf(&args)));
+    }
+    else {
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("requires rbf to be built with the `mmap` feature"));
+    }
+}
+
+#[test]
+fn cli_profile() {
+    let output = rbf(&["profile", "tests/test.xml", "tests/test_ascii.data", "-m", "type:1 map:0..2", "-s", "NB:ID,N3", "--top", "1"]);
+    assert!(output.status.success());
+
+    let s = stdout(&output);
+    assert!(s.contains("record NB (Second record: numbers), 247 records:"));
+    assert!(s.lines().any(|l| l.trim_start().starts_with("3 N3") && l.ends_with("'333' (247)")));
+
+    // HTML page with one table per record type
+    let output = rbf(&["profile", "tests/test.xml", "tests/test_ascii.data", "-m", "type:1 map:0..2", "-f", "html"]);
+    let s = stdout(&output);
+    assert!(s.starts_with("<!DOCTYPE html>"));
+    assert_eq!(s.matches("<table class=\"table table-striped\">").count(), 3);
+}
+
+#[test]
+fn cli_layout_check() {
+    let output = rbf(&["layout", "check", "tests/test.xml"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "tests/test.xml: 0 error(s), 0 warning(s)\n");
+
+    // overlapping fields and a record shorter than the mapper range
+    let xml_file = TempPath::new("layout_check.xml");
+    ::std::fs::write(xml_file.path(), r#"<rbfile><meta mapper="type:1 map:0..2"/><fieldtype name="A" type="string"/>
+        <record name="AA" description="" length="4">
+            <field name="ID" description="" type="A" length="2"/><field name="X" description="" type="A" start="2" end="4"/>
+        </record>
+        <record name="B" description="" length="1"><field name="I" description="" type="A" length="1"/></record>
+    </rbfile>"#).unwrap();
+
+    let output = rbf(&["layout", "check", xml_file.as_str(), "-f", "csv"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(stdout(&output), "severity,record,field,message\n\
+        error,AA,X,overlaps field ID (positions 1 to 2)\n\
+        error,B,,mapper range 0..2 is outside of the record (length 1)\n");
+}
+
+#[test]
+fn cli_layout_diff() {
+    let output = rbf(&["layout", "diff", "tests/test.xml", "tests/test.xml"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "0 change(s), 0 breaking\n");
+
+    // a longer field moves the following ones
+    let xml = ::std::fs::read_to_string("tests/test.xml").unwrap()
+        .replace(r#"name="N8" description="Number 8" length="8""#, r#"name="NUMBER8" description="Number 8" length="9""#);
+    let xml_file = TempPath::new("layout_diff.xml");
+    ::std::fs::write(xml_file.path(), xml).unwrap();
+
+    let output = rbf(&["layout", "diff", "tests/test.xml", xml_file.as_str()]);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(stdout(&output), "[breaking] record NB: length changed from 47 to 48\n\
+        record NB, field NUMBER8: field renamed from N8 to NUMBER8\n\
+        [breaking] record NB, field NUMBER8: length changed from 8 to 9\n\
+        [breaking] record NB, field N9: moved from position 39 to 40\n\
+        4 change(s), 3 breaking\n");
+}
+
+#[test]
+fn cli_layout_infer() {
+    let output = rbf(&["layout", "infer", "tests/test_ascii.data", "--sample", "100"]);
+    assert!(output.status.success());
+
+    let s = stdout(&output);
+    assert!(s.starts_with("<?xml"));
+    assert!(s.contains("mapper=\"type:1 map:0..2\""));
+    assert!(s.contains("<record name=\"LL\" description=\"Record LL\" length=\"353\">"));
+    assert!(s.contains("<record name=\"NB\""));
+}
+
+#[test]
+fn cli_layout_convert() {
+    let yaml_file = TempPath::new("layout_convert.yaml");
+    let yaml_file = yaml_file.as_str();
+
+    let output = rbf(&["layout", "convert", "tests/test.xml", yaml_file]);
+    if cfg!(feature = "yaml") {
+        assert!(output.status.success());
+        let output = rbf(&["count", yaml_file, "tests/test_ascii.data", "-m", "type:1 map:0..2"]);
+        assert!(stdout(&output).contains("Number of LL records = 257"));
+    }
+    else {
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("`yaml` feature"));
+    }
+}
+
+#[test]
+fn cli_errors() {
+    // unknown files
+    let output = rbf(&["count", "tests/foo.xml", "tests/test_ascii.data"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("layout file tests/foo.xml not found"));
+
+    // bad mapper
+    let output = rbf(&["cat", "tests/test.xml", "tests/test_ascii.data", "-m", "type:1 map:0.."]);
+    assert_eq!(output.status.code(), Some(1));
+
+    // unknown record ID with stringent reader
+    let output = rbf(&["cat", "tests/test.xml", "tests/test_ascii.data", "-m", "type:1 map:0..2", "-z", "stringent"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("couldn't find record ID FA"));
+
+    // usage error
+    let output = rbf(&["count", "tests/test.xml"]);
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -1,9 +1,17 @@
 // main.rs
 #[macro_use]
 extern crate rbf;
+#[cfg(feature = "arrow")]
+extern crate parquet;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
 use rbf::record::{AsciiMode, UTF8Mode};
 use rbf::layout::Layout;
 use rbf::reader::{ReaderLazyness, Reader};
+use rbf::parallel::ParallelReader;
+use rbf::util::setup::TempPath;
+use std::collections::HashMap;
+use std::sync::Mutex;
 
 #[test]
 fn record_filter()
@@ -199,3 +207,714 @@ fn reader_lazy() {
     }
 
 }
+
+#[cfg(feature = "json")]
+#[test]
+fn export_json_lines() {
+    extern crate serde_json;
+    use rbf::export::ValueKind;
+    use rbf::export::json::{JsonOptions, JsonLinesExporter};
+
+    // load our layout
+    let layout = Layout::<AsciiMode>::new("./tests/test.xml");
+
+    // create reader
+    let mapper = Box::new(|x: &str| x[0..2].to_string());
+    let mut reader = Reader::<AsciiMode>::new("./tests/test_ascii.data", layout, mapper);
+
+    // export to memory
+    let options = JsonOptions {
+        value_kind: ValueKind::Raw,
+        typed: true,
+        with_record_name: true,
+        with_line_number: true,
+    };
+    let mut output: Vec<u8> = Vec::new();
+    let nb_records = JsonLinesExporter::new(options).export(&mut reader, &mut output).unwrap();
+
+    let lines: Vec<_> = ::std::str::from_utf8(&output).unwrap().lines().collect();
+    assert_eq!(lines.len() as u64, nb_records);
+
+    let mut last_line = 0;
+    for l in lines {
+        let json: serde_json::Value = serde_json::from_str(l).unwrap();
+
+        // line numbers are strictly increasing
+        let line = json["_line"].as_u64().unwrap();
+        assert!(line > last_line);
+        last_line = line;
+
+        match json["_record"].as_str().unwrap() {
+            "LL" => assert_eq!(json["W3"], "CCC"),
+            "NB" => assert_eq!(json["N3"], 333.0),
+            "DP" => assert_eq!(json["F5"][3], "DDDDD"),
+            "GL" => (),
+            other => panic!("unexpected record {}", other),
+        }
+    }
+}
+
+#[test]
+fn export_csv() {
+    use std::fs;
+    use std::io::Read;
+    use rbf::export::csv::{CsvOptions, CsvExporter};
+
+    // load our layout
+    let layout = Layout::<UTF8Mode>::new("./tests/test.xml");
+
+    // create reader
+    let mapper = Box::new(|x: &str| x[0..2].to_string());
+    let mut reader = Reader::<UTF8Mode>::new("./tests/test_utf8.data", layout, mapper);
+
+    // export into a temporary directory
+    let dir = TempPath::new("export_csv");
+    fs::create_dir_all(dir.path()).unwrap();
+
+    let options = CsvOptions { delimiter: ';', ..CsvOptions::default() };
+    let mut exporter = CsvExporter::new(dir.as_str(), options);
+    exporter.set_retain("NB:ID,N2; DP:ID,F5; GL:G2");
+
+    let nb_rows = exporter.export(&mut reader).unwrap();
+    assert_eq!(nb_rows.len(), 3);
+    assert!(!exporter.csv_file("LL").exists());
+
+    // the reader layout is left as is
+    assert_eq!(reader.layout.len(), 4);
+    assert_eq!(reader.layout.get("NB").unwrap().count(), 10);
+
+    // check each file
+    let read = |name: &str| {
+        let mut s = String::new();
+        fs::File::open(exporter.csv_file(name)).unwrap().read_to_string(&mut s).unwrap();
+        s
+    };
+
+    let nb = read("NB");
+    assert_eq!(nb.lines().count() as u64, nb_rows["NB"] + 1);
+    assert_eq!(nb.lines().next().unwrap(), "ID;N2");
+    assert_eq!(nb.lines().nth(1).unwrap(), "NB;22");
+
+    let dp = read("DP");
+    assert_eq!(dp.lines().next().unwrap(), "ID;F5_0;F5_1;F5_2;F5_3");
+    assert_eq!(dp.lines().nth(1).unwrap(), "DP;AAAAA;BBBBB;CCCCC;DDDDD");
+
+    let gl = read("GL");
+    assert_eq!(gl.lines().nth(1).unwrap(), "ββ");
+}
+
+#[cfg(feature = "arrow")]
+#[test]
+fn export_parquet() {
+    use std::fs;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use rbf::export::arrow::ParquetExporter;
+
+    // load our layout
+    let layout = Layout::<AsciiMode>::new("./tests/test.xml");
+
+    // create reader
+    let mapper = Box::new(|x: &str| x[0..2].to_string());
+    let mut reader = Reader::<AsciiMode>::new("./tests/test_ascii.data", layout, mapper);
+
+    // export into a temporary directory
+    let dir = TempPath::new("export_parquet");
+    fs::create_dir_all(dir.path()).unwrap();
+
+    let exporter = ParquetExporter::new(dir.as_str(), 100);
+    let counts = exporter.export(&mut reader).unwrap();
+    assert_eq!(counts.rows.len(), 3);
+
+    // NB record IDs are declared as decimals: they are nulls, but counted as rejects
+    assert_eq!(counts.rejects, vec![(("NB".to_string(), "ID".to_string()), 247)].into_iter().collect());
+
+    // read back each file
+    for (name, rows) in counts.rows {
+        let file = fs::File::open(exporter.parquet_file(&name)).unwrap();
+        let builder = ParquetRecordBatchReaderBuilder::try_new(file).unwrap();
+        assert_eq!(builder.metadata().file_metadata().num_rows() as u64, rows);
+        assert!(builder.metadata().num_row_groups() >= 2);
+
+        if name == "DP" {
+            let columns: Vec<_> = builder.schema().fields().iter().map(|f| f.name().clone()).collect();
+            assert_eq!(columns, vec!["ID", "F5_0", "F5_1", "F5_2", "F5_3"]);
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn export_sqlite() {
+    use rusqlite::Connection;
+    use rbf::export::sqlite::SqliteLoader;
+
+    // load our layout
+    let layout = Layout::<AsciiMode>::new("./tests/test.xml");
+
+    // create reader
+    let mapper = Box::new(|x: &str| x[0..2].to_string());
+    let mut reader = Reader::<AsciiMode>::new("./tests/test_ascii.data", layout, mapper);
+
+    // load into a temporary database
+    let db_file = TempPath::new("export_sqlite.db");
+    let mut loader = SqliteLoader::new(db_file.as_str());
+    loader.commit_size = 100;
+    loader.replace = true;
+
+    let counts = loader.load(&mut reader).unwrap();
+    assert_eq!(counts.rows["DP"], 238);
+    assert_eq!(counts.rows["LL"], 257);
+    assert_eq!(counts.rows["NB"], 247);
+
+    // NB record IDs are declared as decimals: they are kept as text
+    assert_eq!(counts.nb_rejects(), 247);
+    assert_eq!(counts.rejects[&("NB".to_string(), "ID".to_string())], 247);
+
+    // check rows & line numbers
+    let conn = Connection::open(db_file.path()).unwrap();
+    let count: i64 = conn.query_row("SELECT COUNT(*) FROM \"NB\"", [], |r| r.get(0)).unwrap();
+    assert_eq!(count, 247);
+
+    let (min, max): (i64, i64) = conn.query_row("SELECT MIN(LINE_NUMBER), MAX(LINE_NUMBER) FROM \"DP\"", [], |r| Ok((r.get(0)?, r.get(1)?))).unwrap();
+    assert!(min >= 1 && max <= reader.nblines_read as i64);
+
+    let f5: String = conn.query_row("SELECT F5_3 FROM \"DP\" ORDER BY LINE_NUMBER LIMIT 1", [], |r| r.get(0)).unwrap();
+    assert_eq!(f5.len(), 5);
+
+    let id: String = conn.query_row("SELECT ID FROM \"NB\" LIMIT 1", [], |r| r.get(0)).unwrap();
+    assert_eq!(id, "NB");
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn export_sqlite_nulls() {
+    use std::fs;
+    use rusqlite::Connection;
+    use rbf::builder::LayoutBuilder;
+    use rbf::export::sqlite::SqliteLoader;
+
+    let layout = LayoutBuilder::new()
+        .field_type("A", "string")
+        .field_type_with("N", "integer", |t| t.null("blanks, zeros"))
+        .field_type_with("S", "string", |t| t.null("low-values"))
+        .record("AA", |r| r.field("ID", 2, "A").field("QTY", 4, "N").field("CODE", 3, "S"))
+        .build::<AsciiMode>()
+        .unwrap();
+
+    let data_file = TempPath::new("export_sqlite_nulls.data");
+    fs::write(data_file.path(), "AA0042ABC\nAA0000\0\0\0\nAA    XYZ\nAA00X1DEF\n").unwrap();
+
+    let mapper = Box::new(|x: &str| x[0..2].to_string());
+    let mut reader = Reader::new(data_file.as_str(), layout, mapper);
+
+    let conn = Connection::open_in_memory().unwrap();
+    let counts = SqliteLoader::new("").load_into(&conn, &mut reader).unwrap();
+
+    let mut stmt = conn.prepare("SELECT QTY, CODE FROM \"AA\" WHERE LINE_NUMBER < 4 ORDER BY LINE_NUMBER").unwrap();
+    let rows: Vec<(Option<i64>, Option<String>)> = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?))).unwrap()
+        .map(|r| r.unwrap()).collect();
+    assert_eq!(rows, vec![(Some(42), Some("ABC".to_string())), (None, None), (None, Some("XYZ".to_string()))]);
+
+    // an invalid value is not a null: it's kept as text and counted
+    let qty: String = conn.query_row("SELECT QTY FROM \"AA\" WHERE LINE_NUMBER = 4", [], |r| r.get(0)).unwrap();
+    assert_eq!(qty, "00X1");
+    assert_eq!(counts.rejects[&("AA".to_string(), "QTY".to_string())], 1);
+    assert_eq!(counts.nb_rejects(), 1);
+}
+
+#[test]
+fn reader_high_values() {
+    use std::fs;
+    use rbf::builder::LayoutBuilder;
+
+    let layout = || LayoutBuilder::new()
+        .field_type("A", "string")
+        .field_type_with("N", "integer", |t| t.null("high-values"))
+        .record("AA", |r| r.field("ID", 2, "A").field("QTY", 3, "N").field("CODE", 3, "A"))
+        .mapper_fn(Box::new(|x: &str| x[0..2].to_string()))
+        .build::<UTF8Mode>()
+        .unwrap();
+
+    // HIGH-VALUES are 0xFF bytes, which are not UTF-8
+    let data_file = TempPath::new("reader_high_values.data");
+    fs::write(data_file.path(), b"AA\xFF\xFF\xFFABC\nAA042XYZ\n").unwrap();
+
+    let mut reader = Reader::new(data_file.as_str(), layout(), Box::new(|x: &str| x[0..2].to_string()));
+
+    let rec = reader.next().unwrap();
+    assert!(rec.get("QTY").unwrap()[0].is_null());
+    assert_eq!(rec.get("QTY").unwrap()[0].typed_value(), Ok(None));
+    assert_eq!(rec.get_value("CODE"), "ABC");
+
+    let rec = reader.next().unwrap();
+    assert!(!rec.get("QTY").unwrap()[0].is_null());
+    assert_eq!(rec.get_value("QTY"), "042");
+
+    // same with several threads
+    let reader = ParallelReader::new(data_file.as_str(), layout());
+    let mut nulls = Vec::new();
+    reader.map_ordered(|_, rec| Some(rec.field_values().nth(1).unwrap().is_null()), |_, null| nulls.push(null));
+    assert_eq!(nulls, vec![true, false]);
+}
+
+#[test]
+fn reader_filter() {
+    // load our layout
+    let layout = Layout::<UTF8Mode>::new("./tests/test.xml");
+
+    // create reader
+    let mapper = Box::new(|x: &str| x[0..2].to_string());
+    let mut reader = Reader::<UTF8Mode>::new("./tests/test_utf8.data", layout, mapper);
+
+    // unknown fields are reported before reading
+    assert!(reader.set_query("GL.FOO = 'α'").is_err());
+
+    reader.set_query("GL.G2 = 'ββ' or (record = 'NB' and N3 > 300)").unwrap();
+
+    let mut nb_records = HashMap::new();
+    while let Some(rec) = reader.next() {
+        *nb_records.entry(rec.name.clone()).or_insert(0) += 1;
+    }
+
+    assert_eq!(nb_records.len(), 2);
+    assert!(nb_records.contains_key("GL"));
+    assert!(nb_records.contains_key("NB"));
+
+    // all lines are read anyway
+    assert_eq!(reader.nblines_read, 1000);
+}
+
+#[test]
+fn parallel_reader() {
+    // records read sequentially, with their line number
+    let layout = Layout::<UTF8Mode>::new("./tests/test.xml");
+    let mapper = Box::new(|x: &str| x[0..2].to_string());
+    let mut reader = Reader::<UTF8Mode>::new("./tests/test_utf8.data", layout, mapper);
+
+    let mut expected = Vec::new();
+    while let Some(value) = reader.next().map(|rec| format!("{}", rec)) {
+        expected.push((reader.nblines_read, value));
+    }
+
+    // same with small chunks, in line order
+    let mut layout = Layout::<UTF8Mode>::new("./tests/test.xml");
+    layout.mapper = Box::new(|x: &str| x[0..2].to_string());
+    let mut reader = ParallelReader::<UTF8Mode>::new("./tests/test_utf8.data", layout);
+    reader.set_threads(4);
+    reader.set_chunk_size(1000);
+
+    let mut records = Vec::new();
+    reader.map_ordered(|_, rec| Some(format!("{}", rec.view())), |line_number, value| records.push((line_number, value)));
+    assert_eq!(records, expected);
+
+    // chunks are given in any order, but line numbers are absolute
+    let chunks = Mutex::new(Vec::new());
+    reader.for_each_chunk(|chunk| {
+        let (first_line, index) = (chunk.first_line, chunk.index);
+        let mut nb_lines = 0;
+        while let Some((line_number, _)) = chunk.next() {
+            assert_eq!(line_number, first_line + nb_lines);
+            nb_lines += 1;
+        }
+        chunks.lock().unwrap().push((index, first_line, nb_lines));
+    });
+
+    let mut chunks = chunks.into_inner().unwrap();
+    chunks.sort();
+    assert!(chunks.len() > 4);
+    assert!(chunks.windows(2).all(|w| w[1].1 == w[0].1 + w[0].2));
+    assert_eq!(chunks.iter().map(|c| c.2).sum::<u64>(), 1000);
+}
+
+#[test]
+#[cfg(feature = "mmap")]
+fn mmap_reader() {
+    use rbf::index::LineIndex;
+
+    // records read with a buffered reader
+    let layout = Layout::<UTF8Mode>::new("./tests/test.xml");
+    let mapper = Box::new(|x: &str| x[0..2].to_string());
+    let mut reader = Reader::<UTF8Mode>::new("./tests/test_utf8.data", layout, mapper);
+
+    let mut expected = Vec::new();
+    while let Some(value) = reader.next().map(|rec| format!("{}", rec)) {
+        expected.push((reader.nblines_read, value));
+    }
+
+    // same with the mapping
+    let layout = Layout::<UTF8Mode>::new("./tests/test.xml");
+    let mapper = Box::new(|x: &str| x[0..2].to_string());
+    let mut reader = Reader::<UTF8Mode>::new("./tests/test_utf8.data", layout, mapper);
+    reader.set_mmap().unwrap();
+
+    // the last record doesn't move the reader
+    let last = format!("{}", reader.last_record().unwrap());
+    assert_eq!(last, expected[999].1);
+    assert_eq!(reader.nblines_read, 0);
+
+    let mut records = Vec::new();
+    while let Some((name, value)) = reader.next().map(|rec| (rec.name.clone(), format!("{}", rec))) {
+        assert!(reader.current_line().starts_with(&name));
+        records.push((reader.nblines_read, value));
+    }
+    assert_eq!(records, expected);
+    assert!(reader.next().is_none());
+
+    // random access, with an index in memory
+    let index = LineIndex::build("./tests/test_utf8.data", &reader.mapper, 100).unwrap();
+    reader.set_index(index);
+
+    assert!(reader.seek_line(500));
+    let value = format!("{}", reader.next().unwrap());
+    assert_eq!(value, expected[499].1);
+    assert_eq!(reader.nblines_read, 500);
+
+    assert!(reader.seek_line(1000));
+    assert!(!reader.seek_line(1001));
+    assert_eq!(reader.nblines_read, 999);
+}
+
+#[test]
+fn reader_index() {
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+    use rbf::index::LineIndex;
+
+    // work on a copy, as the index is saved next to the data file
+    let temp_file = TempPath::new("reader_index.data");
+    let _index_file = TempPath::new("reader_index.data.rbfidx");
+    let data_file = temp_file.as_str();
+    fs::copy("./tests/test_ascii.data", data_file).unwrap();
+
+    let layout = Layout::<AsciiMode>::new("./tests/test.xml");
+    let mapper = Box::new(|x: &str| x[0..2].to_string());
+    let mut reader = Reader::<AsciiMode>::new(data_file, layout, mapper);
+
+    // index is built and saved when first needed
+    assert!(reader.seek_line(743));
+    assert_eq!(reader.next().unwrap().name, "DP");
+    assert!(!reader.seek_line(744));
+
+    let index = LineIndex::load(data_file).unwrap();
+    assert!(index.is_valid());
+    assert_eq!(index.nb_lines, 743);
+    assert_eq!(index.records["FA"].len(), 1);
+
+    // records of a given type after the current line, with their line numbers
+    assert_eq!(reader.records_of("NB"), 0);
+    assert!(reader.seek_line(1));
+    assert_eq!(reader.records_of("NB"), 247);
+    let mut lines = Vec::new();
+    while let Some(rec) = reader.next().map(|rec| rec.get_value("N3").to_string()) {
+        assert_eq!(rec, "333");
+        lines.push(reader.nblines_read);
+    }
+    assert_eq!(lines, index.records["NB"].iter().map(|p| p.0).collect::<Vec<_>>());
+
+    // index is stale once the data file changes
+    let mut f = OpenOptions::new().append(true).open(data_file).unwrap();
+    writeln!(f, "LLABBCCCDDDD").unwrap();
+    assert!(!index.is_valid());
+
+    assert!(reader.seek_line(744));
+    assert_eq!(reader.index.as_ref().unwrap().nb_lines, 744);
+    assert_eq!(LineIndex::load(data_file).unwrap().nb_lines, 744);
+}
+
+#[test]
+fn reader_index_in_memory() {
+    use std::fs;
+    use rbf::index::LineIndex;
+
+    // the index file can't be written when a directory has its name
+    let temp_file = TempPath::new("reader_index_in_memory.data");
+    let index_file = TempPath::new("reader_index_in_memory.data.rbfidx");
+    let data_file = temp_file.as_str();
+    fs::copy("./tests/test_ascii.data", data_file).unwrap();
+    fs::create_dir(index_file.path()).unwrap();
+
+    let layout = Layout::<AsciiMode>::new("./tests/test.xml");
+    let mapper = Box::new(|x: &str| x[0..2].to_string());
+    let mut reader = Reader::<AsciiMode>::new(data_file, layout, mapper);
+
+    // index is then only kept in memory
+    assert!(reader.seek_line(743));
+    assert_eq!(reader.next().unwrap().name, "DP");
+    assert!(reader.index.is_some());
+    assert!(LineIndex::load(data_file).is_err());
+}
+
+#[test]
+fn reader_progress() {
+    use std::sync::Arc;
+    use rbf::index::LineIndex;
+    use rbf::progress::ProgressStep;
+
+    let layout = Layout::<AsciiMode>::new("./tests/test.xml");
+    let mapper = Box::new(|x: &str| x[0..2].to_string());
+    let mut reader = Reader::<AsciiMode>::new("./tests/test_ascii.data", layout, mapper);
+
+    // line number, percentage and LL count at each call
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let calls_cb = calls.clone();
+    reader.set_progress(ProgressStep::Lines(100), Box::new(move |progress| {
+        calls_cb.lock().unwrap().push((progress.nblines_read, progress.percent(), progress.records.get("LL").cloned(), progress.done));
+    }));
+    while reader.next().is_some() {}
+
+    // end of file is only reported once
+    assert!(reader.next().is_none());
+    assert!(reader.next().is_none());
+
+    {
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 9);
+        assert_eq!(calls.iter().map(|c| c.0).collect::<Vec<_>>(), vec![1, 101, 201, 301, 401, 501, 601, 701, 743]);
+        assert!(calls.windows(2).all(|w| w[0].1 < w[1].1));
+        assert!(calls[..8].iter().all(|c| !c.3));
+
+        let last = calls.last().unwrap();
+        assert_eq!(last.1, 100.0);
+        assert_eq!(last.2, Some(257));
+        assert!(last.3);
+    }
+
+    assert_eq!(reader.bytes_read, reader.file_size);
+    assert_eq!(reader.record_counts["NB"], 247);
+    assert!(!reader.record_counts.contains_key("FA"));
+
+    // reaching end of file again after a seek is reported again
+    let index = LineIndex::build("./tests/test_ascii.data", &reader.mapper, 100).unwrap();
+    reader.set_index(index);
+    assert!(reader.seek_line(743));
+    while reader.next().is_some() {}
+    assert!(reader.next().is_none());
+
+    let calls = calls.lock().unwrap();
+    assert_eq!(calls.len(), 10);
+    assert!(calls[9].3);
+}
+
+#[test]
+fn infer_layout() {
+    use std::fs;
+    use std::mem;
+    use rbf::infer::LayoutGuess;
+    use rbf::mapper::RecordMapper;
+    use rbf::value::{Date, TypedValue};
+
+    // customers with a name, a birth date and an amount, their cities, and a trailer
+    let names = ["JOHN", "JANE", "ROBERT", "AL", "MARGARET", "TOM", "LUCY", "PETER", "ANNA", "MAX"];
+    let cities = ["PARIS", "LONDON", "ROME", "BERLIN", "OSLO", "MADRID", "VIENNA"];
+    let mut sample = String::new();
+    for i in 0..200 {
+        sample += &format!("01{:<10}{:04}{:02}{:02}{:>9.2}\n", names[i % 10], 1950 + i % 50, 1 + i % 12, 1 + i % 28, i as f64 * 13.7);
+        if i % 2 == 0 {
+            sample += &format!("02{:06}{:<8}\n", i * 31, cities[i % 7]);
+        }
+    }
+    sample += "99000300\n";
+
+    let data_file = TempPath::new("infer.data");
+    let xml_file = TempPath::new("infer.xml");
+    fs::write(data_file.path(), &sample).unwrap();
+
+    let guess = LayoutGuess::from_file(data_file.as_str(), 0).unwrap();
+    assert_eq!(guess.nb_lines, 301);
+    assert_eq!(guess.prefix_length, 2);
+    assert!(guess.prefix_confidence > 0.5);
+
+    // guessed layout can be loaded back
+    fs::write(xml_file.path(), guess.to_xml("customers")).unwrap();
+    let mut layout = Layout::<AsciiMode>::new(xml_file.as_str());
+    assert!(layout.validate().is_empty());
+    assert_eq!(layout.len(), 3);
+    assert_eq!(layout.description, "customers");
+
+    let rec = layout.get("01").unwrap();
+    let fields: Vec<_> = rec.flist.iter().map(|f| (f.name.as_str(), f.length, f.ftype.id.as_str())).collect();
+    assert_eq!(fields, vec![("ID", 2, "A"), ("F1", 10, "A"), ("F2", 8, "YYYYMMDD"), ("F3", 9, "N")]);
+
+    let rec = layout.get("02").unwrap();
+    let fields: Vec<_> = rec.flist.iter().map(|f| (f.name.as_str(), f.length, f.ftype.id.as_str())).collect();
+    assert_eq!(fields, vec![("ID", 2, "A"), ("F1", 6, "I"), ("F2", 8, "A")]);
+
+    // and used to read the file
+    let mapper = mem::replace(&mut layout.mapper, RecordMapper::default().hasher);
+    let mut reader = Reader::new(data_file.as_str(), layout, mapper);
+    let rec = reader.next().unwrap();
+    assert_eq!(rec.name, "01");
+    assert_eq!(rec.get_value("F1"), "JOHN");
+    assert_eq!(rec.get("F2").unwrap()[0].typed_value().unwrap(), Some(TypedValue::Date(Date { year: 1950, month: 1, day: 1 })));
+}
+
+#[test]
+fn layout_formats() {
+    use rbf::format::{LayoutFormat, LayoutModel};
+
+    let xml = Layout::<AsciiMode>::new("./tests/test.xml");
+    let model = LayoutModel::from_file("./tests/test.xml").unwrap();
+
+    let mut formats = Vec::new();
+    if cfg!(feature = "json") { formats.push(LayoutFormat::Json); }
+    if cfg!(feature = "yaml") { formats.push(LayoutFormat::Yaml); }
+    if cfg!(feature = "toml") { formats.push(LayoutFormat::Toml); }
+
+    for format in formats {
+        // same layout whatever the format
+        let layout_file = format!("./tests/test.{}", format.extension());
+        let layout = Layout::<AsciiMode>::new(&layout_file);
+        assert_eq!(layout.version, xml.version);
+        assert_eq!(layout.description, xml.description);
+        assert_eq!(layout.ignore_line.as_str(), xml.ignore_line.as_str());
+        assert_eq!(layout.len(), xml.len());
+        assert_eq!(layout.ftypes.len(), xml.ftypes.len());
+
+        for (name, rec) in &xml {
+            let other = layout.get(name).unwrap();
+            assert_eq!(other.description, rec.description);
+            assert_eq!(other.calculated_length, rec.calculated_length);
+
+            let fields: Vec<_> = rec.flist.iter().map(|f| (&f.name, f.lower_offset, f.upper_offset, &f.ftype.id)).collect();
+            let other_fields: Vec<_> = other.flist.iter().map(|f| (&f.name, f.lower_offset, f.upper_offset, &f.ftype.id)).collect();
+            assert_eq!(other_fields, fields);
+        }
+
+        // conversion keeps the whole definition
+        let mut buffer = Vec::new();
+        model.write(&mut buffer, format).unwrap();
+        assert_eq!(LayoutModel::read(&buffer[..], format).unwrap(), model);
+    }
+
+    // fields must belong to a record
+    let result = LayoutModel::read(&b"<rbfile><field name=\"F\" description=\"\" type=\"A\" length=\"2\"/></rbfile>"[..], LayoutFormat::Xml);
+    assert_eq!(result, Err("field F is not within a record".to_string()));
+}
+
+#[test]
+fn layout_include() {
+    use rbf::format::LayoutModel;
+
+    // field types and records of common.xml are available
+    let layout = Layout::<AsciiMode>::new("./tests/include/orders.xml");
+    assert_eq!(layout.description, "Orders");
+    assert_eq!(layout.len(), 3);
+    assert_eq!(layout.ftypes.len(), 3);
+    assert_eq!(layout.get("HD").unwrap().calculated_length, 12);
+    assert!(layout.validate().is_empty());
+
+    // redeclared field types and records override included ones
+    assert_eq!(layout.get_type("N").unwrap().scale, Some(2));
+    assert_eq!(layout.get("TR").unwrap().description, "Orders trailer");
+    assert_eq!(layout.get("OR").unwrap().get("AMOUNT").unwrap()[0].ftype.scale, Some(2));
+
+    // but records declared twice in the same file are still reported
+    let layout = Layout::<AsciiMode>::new("./tests/include/duplicates.xml");
+    assert_eq!(layout.get("TR").unwrap().description, "Longer trailer");
+    let diagnostics: Vec<_> = layout.validate().iter().map(|d| d.to_string()).collect();
+    assert_eq!(diagnostics, vec!["error: record OR: record is declared more than once, only the last declaration is kept"]);
+
+    // cycles are detected
+    let mut model = LayoutModel::from_file("./tests/include/cycle_a.xml").unwrap();
+    let error = model.resolve_includes("./tests/include/cycle_a.xml").unwrap_err();
+    assert_eq!(error, "include cycle: ./tests/include/cycle_a.xml -> ./tests/include/cycle_b.xml -> ./tests/include/cycle_a.xml");
+}
+
+#[test]
+fn layout_extends() {
+    use std::fs;
+    use std::mem;
+    use rbf::mapper::RecordMapper;
+
+    let mut layout = Layout::<AsciiMode>::new("./tests/extends.xml");
+    assert!(layout.validate().is_empty());
+
+    // base fields come first
+    let fields = |rec: &str| layout.get(rec).unwrap().flist.iter()
+        .map(|f| (f.name.clone(), f.lower_offset, f.upper_offset, f.multiplicity)).collect::<Vec<_>>();
+    let prefix = vec![("ID".to_string(), 0, 1, 0), ("SEQ".to_string(), 2, 9, 0), ("TIMESTAMP".to_string(), 10, 29, 0)];
+
+    let mut expected = prefix.clone();
+    expected.extend(vec![("NAME".to_string(), 30, 39, 0), ("CODE".to_string(), 40, 44, 0)]);
+    assert_eq!(fields("CU"), expected);
+    expected.push(("AMOUNT".to_string(), 45, 52, 0));
+    assert_eq!(fields("OR"), expected);
+
+    // repeated names
+    let mut expected = prefix.clone();
+    expected.push(("SEQ".to_string(), 30, 33, 1));
+    assert_eq!(fields("DT"), expected);
+    assert_eq!(layout.get("OR").unwrap().calculated_length, 53);
+
+    // read data
+    let data_file = TempPath::new("layout_extends.data");
+    fs::write(data_file.path(), "OR000000422017-01-01T10:00:00ZJOHN      C0001  123.45\nDT000000422017-01-01T10:00:00Z0002\n").unwrap();
+
+    let mapper = mem::replace(&mut layout.mapper, RecordMapper::default().hasher);
+    let mut reader = Reader::new(data_file.as_str(), layout, mapper);
+
+    let rec = reader.next().unwrap();
+    assert_eq!(rec.get_value("NAME"), "JOHN");
+    assert_eq!(rec.get_value("AMOUNT"), "123.45");
+
+    let rec = reader.next().unwrap();
+    assert_eq!(rec.get("SEQ").unwrap()[1].value(), "0002");
+}
+
+#[test]
+fn layout_pdb() {
+    use std::mem;
+    use rbf::mapper::RecordMapper;
+    use rbf::value::{Date, TypedValue};
+
+    let mut layout = Layout::<AsciiMode>::new("./tests/pcb/pdb.xml");
+
+    // sub-records (e.g. JRNL AUTH and JRNL TITL) have their own name, so none is overwritten
+    assert!(layout.validate().iter().all(|d| !d.is_error()));
+    assert_eq!(layout.len(), 68);
+    assert!(layout.get("JRNL_PUBL").unwrap().contains_field("pub"));
+
+    // only generic ones are found by the mapper
+    assert!(layout.get("JRNL  ").unwrap().contains_field("text"));
+    assert!(layout.get("REMARK").unwrap().contains_field("remarkNum"));
+
+    // undeclared but parameterized or base type names
+    let real = layout.get_type("Real(8.3)").unwrap();
+    assert_eq!((real.base_data_type.type_name(), real.scale), ("decimal", Some(3)));
+    assert_eq!(layout.get_type("String(40)").unwrap().base_data_type.type_name(), "string");
+    assert_eq!(layout.get_type("Integer").unwrap().base_data_type.type_name(), "integer");
+    assert_eq!(layout.get_type("IDCode").unwrap().pattern.as_str(), layout.get_type("IDcode").unwrap().pattern.as_str());
+
+    let mapper = mem::replace(&mut layout.mapper, RecordMapper::default().hasher);
+    let mut reader = Reader::new("./tests/pcb/sample.pdb", layout, mapper);
+
+    let rec = reader.next().unwrap();
+    assert_eq!(rec.name, "HEADER");
+    assert_eq!(rec.get_value("classification"), "HYDROLASE");
+    assert_eq!(rec.get("depDate").unwrap()[0].typed_value(), Ok(Some(TypedValue::Date(Date { year: 2003, month: 3, day: 12 }))));
+    assert!(rec.get("idCode").unwrap()[0].is_match());
+
+    // first line of a title, without continuation number
+    let rec = reader.next().unwrap();
+    assert!(rec.get("continuation").unwrap()[0].is_null());
+    assert_eq!(rec.get("continuation").unwrap()[0].typed_value(), Ok(None));
+    let rec = reader.next().unwrap();
+    assert_eq!(rec.name, "CRYST1");
+    assert_eq!(rec.get("beta").unwrap()[0].typed_value(), Ok(Some(TypedValue::Decimal(90.77))));
+    assert_eq!(rec.get_value("sGroup"), "P 1 21 1");
+
+    let rec = reader.next().unwrap();
+    assert_eq!(rec.name, "ATOM  ");
+    assert_eq!(rec.get("serial").unwrap()[0].typed_value(), Ok(Some(TypedValue::Integer(1))));
+    assert_eq!(rec.get_value("resName"), "THR");
+    assert_eq!(rec.get("x").unwrap()[0].typed_value(), Ok(Some(TypedValue::Decimal(17.047))));
+    assert_eq!(rec.get("tempFactor").unwrap()[0].typed_value(), Ok(Some(TypedValue::Decimal(13.79))));
+    assert_eq!(rec.get_value("element"), "N");
+
+    // 4 atoms, a chain terminator, a water molecule and the end of the entry
+    let mut names = Vec::new();
+    while let Some(rec) = reader.next() {
+        names.push(rec.name.trim().to_string());
+    }
+    assert_eq!(names, vec!["ATOM", "ATOM", "ATOM", "TER", "HETATM", "END"]);
+}
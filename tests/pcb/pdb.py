@@ -1,3 +1,12 @@
+# Generates the PDB layout from the PDB format description:
+#
+#   python3 pdb.py pdb_purged.txt pdb_recs.txt > pdb.xml
+#
+# The layout header (meta and field types) is taken from pdb_meta.xml.
+#
+# Sub-records (e.g. "JRNL  " AUTH or "REMARK" 1 TITL) are named after the record name and the literal
+# values starting them (JRNL_AUTH, REMARK_1_TITL), followed by a number when still repeated. Only the
+# generic JRNL and REMARK records, having no such literal, are found by the mapper.
 import sys
 import re
 
@@ -13,6 +22,32 @@ re_recname = r'.*"(?P<recname>.+)"'
 re_offsets = r"^(?P<inf>\d+)[\s\-–]{0,3}(?P<sup>\d*)"
 
 recs = {}
+names = {}
+
+# current record: name, description, fields, and literal values starting it
+record = None
+
+
+# prints the current record, naming sub-records after their leading literal values
+def print_record():
+    recname, recdesc, fields, literals = record
+
+    if literals:
+        recname = "_".join([recname.strip()] + literals)
+    names[recname] = names.get(recname, 0) + 1
+    if names[recname] > 1:
+        recname = "{}_{}".format(recname, names[recname])
+
+    print('<record name="{0}" description="{1}">'.format(recname, recdesc))
+    print('\t<field name="recName" description="Record name" type="String" start="1" end="6"/>')
+    for field in fields:
+        print(field)
+    print("</record>\n")
+
+
+# layout header, up to the closing tag
+header = open("pdb_meta.xml").read()
+print(header[:header.index("</rbfile>")].rstrip() + "\n")
 
 # first, read records
 for line in open(sys.argv[2]):
@@ -36,12 +71,11 @@ for line in open(sys.argv[1]):
     if "Record name" in line:
         m = re.match(re_recname, line)
         if m:
-            print("</record>\n")
+            if record:
+                print_record()
 
             recname = m.group('recname').strip().ljust(6)
-
-            print('<record name="{0}" description="{1}">'.format(recname, recs[recname]))
-            print('\t<field name="recName" description="Record name" type="String" start="1" end="6"/>')
+            record = (recname, recs[recname], [], [])
             continue
         else:
             print("Error for line: ", line)
@@ -49,7 +83,7 @@ for line in open(sys.argv[1]):
 
     # field here
     offsets = line[cols[0]:cols[1]]
-    m = re.match(re_offsets, line)
+    m = re.match(re_offsets, line.strip())
     if m:
         # manage single lower offset
         if m.group('sup') == "": 
@@ -63,6 +97,10 @@ for line in open(sys.argv[1]):
 
     fname = line[cols[2]:cols[3]].strip()
     if '"' in fname:
+        # literal values following the record name start a sub-record
+        if len(record[2]) == len(record[3]):
+            record[3].append(re.sub(r'\W', "", re.search('"(.*)"', fname).group(1)))
+
         fname = fname.strip().lower()
         fname = re.sub('"', "", fname)
 
@@ -71,8 +109,9 @@ for line in open(sys.argv[1]):
 
     #print("<{}><{}><{}><{}>".format(offsets,data_type, fname, fdesc))
 
-    print('\t<field name="{3}" description="{4}" type="{2}" start="{0}" end="{1}"/>'.format(
+    record[2].append('\t<field name="{3}" description="{4}" type="{2}" start="{0}" end="{1}"/>'.format(
         inf, sup, data_type, fname, fdesc))
     continue
 
-
+print_record()
+print("</rbfile>")